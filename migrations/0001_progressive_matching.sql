@@ -0,0 +1,41 @@
+-- Pending requests of progressive matching and the audit of finished ones.
+-- Timestamps of ongoing_matches used to be stored as RFC 3339 TEXT.
+CREATE TABLE IF NOT EXISTS ongoing_matches (
+    conversation_id TEXT NOT NULL,
+    endpoint_id TEXT NOT NULL,
+    parameters TEXT NOT NULL,
+    completion_percentage REAL NOT NULL DEFAULT 0.0,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (conversation_id, endpoint_id)
+);
+
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'ongoing_matches'
+          AND column_name = 'created_at'
+          AND data_type = 'text'
+    ) THEN
+        ALTER TABLE ongoing_matches
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at::timestamptz,
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ USING updated_at::timestamptz;
+    END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS ongoing_matches_updated_at
+    ON ongoing_matches (updated_at);
+
+CREATE TABLE IF NOT EXISTS progressive_match_audit (
+    id BIGSERIAL PRIMARY KEY,
+    conversation_id TEXT NOT NULL,
+    endpoint_id TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    parameters TEXT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS progressive_match_audit_conversation_id
+    ON progressive_match_audit (conversation_id);
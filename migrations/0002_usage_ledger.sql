@@ -0,0 +1,34 @@
+-- One row per LLM call, and the outcome of each request in a prompt experiment
+CREATE TABLE IF NOT EXISTS usage_ledger (
+    id BIGSERIAL PRIMARY KEY,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    email TEXT,
+    conversation_id TEXT,
+    endpoint TEXT NOT NULL,
+    step TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    input_tokens BIGINT NOT NULL,
+    output_tokens BIGINT NOT NULL,
+    estimated BOOLEAN NOT NULL,
+    latency_ms BIGINT NOT NULL,
+    cost DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS usage_ledger_email_recorded_at
+    ON usage_ledger (email, recorded_at);
+
+ALTER TABLE usage_ledger ADD COLUMN IF NOT EXISTS experiment TEXT;
+ALTER TABLE usage_ledger ADD COLUMN IF NOT EXISTS variant TEXT;
+
+CREATE TABLE IF NOT EXISTS prompt_experiment_outcomes (
+    id BIGSERIAL PRIMARY KEY,
+    recorded_at TIMESTAMPTZ NOT NULL,
+    experiment TEXT NOT NULL,
+    variant TEXT NOT NULL,
+    email TEXT,
+    outcome TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS prompt_experiment_outcomes_recorded_at
+    ON prompt_experiment_outcomes (recorded_at);
@@ -0,0 +1,8 @@
+-- Rate limit counters shared by all instances
+CREATE TABLE IF NOT EXISTS rate_limit_counters (
+    subject TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    value BIGINT NOT NULL DEFAULT 0,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (subject, bucket)
+);
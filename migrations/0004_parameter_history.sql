@@ -0,0 +1,4 @@
+-- Every value given, corrected or removed for a parameter of a pending match,
+-- as a JSON array of {name, value, source, at}
+ALTER TABLE ongoing_matches ADD COLUMN IF NOT EXISTS history TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE progressive_match_audit ADD COLUMN IF NOT EXISTS history TEXT NOT NULL DEFAULT '[]';
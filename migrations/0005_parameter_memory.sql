@@ -0,0 +1,11 @@
+-- Parameter values remembered per user: set in their profile, or learned from
+-- completed matches (uses counts the matches in a row that gave the same value)
+CREATE TABLE IF NOT EXISTS parameter_memory (
+    email TEXT NOT NULL,
+    name TEXT NOT NULL,
+    source TEXT NOT NULL,
+    value TEXT NOT NULL,
+    uses INTEGER NOT NULL DEFAULT 1,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (email, name, source)
+);
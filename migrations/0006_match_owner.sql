@@ -0,0 +1,4 @@
+-- The user each pending match belongs to. Matches saved before have no owner and
+-- cannot be read or changed by anyone until they expire.
+ALTER TABLE ongoing_matches ADD COLUMN IF NOT EXISTS email TEXT;
+ALTER TABLE progressive_match_audit ADD COLUMN IF NOT EXISTS email TEXT;
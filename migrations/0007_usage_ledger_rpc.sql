@@ -0,0 +1,3 @@
+-- The column holds the gRPC method a call was made for (AnalyzeSentence,
+-- SendMessage, or cli), not an endpoint of the catalog
+ALTER TABLE usage_ledger RENAME COLUMN endpoint TO rpc;
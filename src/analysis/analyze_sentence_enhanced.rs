@@ -5,29 +5,36 @@ use crate::analysis::response_builders::{
 use crate::analysis::retry_logic::analyze_with_retry;
 use crate::app_log;
 use crate::endpoint_client::get_enhanced_endpoints;
+use crate::error::SemanticError;
 use crate::models::config::load_analysis_config;
 use crate::models::providers::ModelProvider;
 use crate::models::EnhancedAnalysisResult;
-use crate::progressive_matching::{get_database_url, ProgressiveMatchingManager};
+use crate::parameter_memory::ParameterDefaults;
+use crate::progressive_matching::ProgressiveMatchingManager;
+use crate::trace;
 use crate::utils::email::validate_email;
 use crate::workflow::actions::classify_intent::classify_intent;
 use crate::workflow::classify_intent::IntentType;
-use std::error::Error;
 use std::sync::Arc;
 
-// Enhanced analysis function with progressive matching as FIRST priority
+// Enhanced analysis function with progressive matching as FIRST priority.
+// Pending requests are looked up in `progressive_manager`, the server's shared one.
 pub async fn analyze_sentence_enhanced(
     sentence: &str,
     provider: Arc<dyn ModelProvider>,
     api_url: Option<String>,
     email: &str,
     conversation_id: Option<String>,
-) -> Result<EnhancedAnalysisResult, Box<dyn Error + Send + Sync>> {
+    defaults: &ParameterDefaults,
+    progressive_manager: Option<&ProgressiveMatchingManager>,
+) -> Result<EnhancedAnalysisResult, SemanticError> {
     let model = provider.get_model_name().to_string();
     if email.is_empty() {
-        return Err("Email is required".into());
+        return Err(SemanticError::InvalidRequest(
+            "Email is required".to_string(),
+        ));
     }
-    validate_email(email)?;
+    validate_email(email).map_err(|e| SemanticError::InvalidRequest(e.to_string()))?;
 
     let analysis_config = load_analysis_config().await.unwrap_or_default();
 
@@ -49,57 +56,64 @@ pub async fn analyze_sentence_enhanced(
             conv_id
         );
 
-        if let Ok(db_url) = get_database_url() {
-            if let Ok(progressive_manager) = ProgressiveMatchingManager::new(&db_url).await {
-                // Check if there's an ongoing incomplete match
-                match progressive_manager.get_incomplete_match(conv_id).await {
-                    Ok(Some(ongoing_match)) => {
-                        app_log!(
-                            info,
-                            "Found ongoing progressive match for endpoint: {}",
-                            ongoing_match.endpoint_id
-                        );
+        if let Some(progressive_manager) = progressive_manager {
+            // Check if there's an ongoing incomplete match
+            match progressive_manager
+                .get_incomplete_match(conv_id, email)
+                .await
+            {
+                Ok(Some(ongoing_match)) => {
+                    app_log!(
+                        info,
+                        "Found ongoing progressive match for endpoint: {}",
+                        ongoing_match.endpoint_id
+                    );
 
-                        // Process this as a progressive follow-up
-                        match handle_progressive_followup(
+                    // Process this as a progressive follow-up
+                    match trace::step(
+                        "progressive_followup",
+                        handle_progressive_followup(
                             sentence,
                             conv_id,
                             &ongoing_match,
                             provider.clone(),
-                            &progressive_manager,
+                            progressive_manager,
                             api_url_ref,
                             email,
-                        )
-                        .await
-                        {
-                            Ok(progressive_result) => {
-                                app_log!(info, "Progressive matching completed successfully");
-                                return Ok(progressive_result);
-                            }
-                            Err(e) => {
-                                app_log!(
-                                    warn,
-                                    "Progressive matching failed: {}, continuing with normal flow",
-                                    e
-                                );
-                                // Continue to normal flow if progressive matching fails
-                            }
+                        ),
+                    )
+                    .await
+                    {
+                        Ok(Some(progressive_result)) => {
+                            app_log!(info, "Progressive matching completed successfully");
+                            return Ok(progressive_result);
+                        }
+                        Ok(None) => {
+                            app_log!(info, "Message is not a follow-up of the pending request");
+                        }
+                        Err(e) => {
+                            app_log!(
+                                warn,
+                                "Progressive matching failed: {}, continuing with normal flow",
+                                e
+                            );
+                            // Continue to normal flow if progressive matching fails
                         }
                     }
-                    Ok(None) => {
-                        app_log!(
-                            debug,
-                            "No ongoing progressive match found for conversation: {}",
-                            conv_id
-                        );
-                    }
-                    Err(e) => {
-                        app_log!(
-                            warn,
-                            "Error checking for progressive match: {}, continuing with normal flow",
-                            e
-                        );
-                    }
+                }
+                Ok(None) => {
+                    app_log!(
+                        debug,
+                        "No ongoing progressive match found for conversation: {}",
+                        conv_id
+                    );
+                }
+                Err(e) => {
+                    app_log!(
+                        warn,
+                        "Error checking for progressive match: {}, continuing with normal flow",
+                        e
+                    );
                 }
             }
         }
@@ -118,9 +132,13 @@ pub async fn analyze_sentence_enhanced(
         .map(|e| e.description.clone())
         .collect();
 
-    let intent = classify_intent(sentence, &endpoint_descriptions, provider.clone()).await?;
+    let (intent, classification_usage) = trace::step(
+        "classify_intent",
+        classify_intent(sentence, &endpoint_descriptions, provider.clone()),
+    )
+    .await?;
 
-    match intent {
+    let result = match intent {
         IntentType::ActionableRequest => {
             app_log!(info, "Processing as NEW actionable request");
             match analyze_with_retry(
@@ -130,6 +148,7 @@ pub async fn analyze_sentence_enhanced(
                 email,
                 conversation_id.clone(),
                 analysis_config.retry_attempts,
+                defaults,
             )
             .await
             {
@@ -141,7 +160,12 @@ pub async fn analyze_sentence_enhanced(
                             "All retries failed, falling back to general question handler: {}",
                             e
                         );
-                        create_fallback_response(sentence, provider, model, conversation_id).await
+                        trace::step(
+                            "general_question",
+                            create_fallback_response(sentence, provider, model, conversation_id),
+                        )
+                        .await
+                        .map_err(SemanticError::from)
                     } else {
                         Err(e)
                     }
@@ -151,12 +175,167 @@ pub async fn analyze_sentence_enhanced(
 
         IntentType::HelpRequest => {
             app_log!(info, "Processing as help request");
-            create_help_response(sentence, &enhanced_endpoints, provider, conversation_id).await
+            trace::step(
+                "help_response",
+                create_help_response(sentence, &enhanced_endpoints, provider, conversation_id),
+            )
+            .await
+            .map_err(SemanticError::from)
         }
 
         IntentType::GeneralQuestion => {
             app_log!(info, "Processing as general question");
-            create_general_response(sentence, provider, model, conversation_id).await
+            trace::step(
+                "general_question",
+                create_general_response(sentence, provider, model, conversation_id),
+            )
+            .await
+            .map_err(SemanticError::from)
         }
+    };
+
+    // The classification call is part of what the request consumed
+    result.map(|mut analysis| {
+        analysis.add_usage(&classification_usage);
+        analysis
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint_client::endpoint::endpoint_service_server::{
+        EndpointService, EndpointServiceServer,
+    };
+    use crate::endpoint_client::endpoint::{
+        ApiGroup, Endpoint, GetApiGroupsRequest, GetApiGroupsResponse, GetUserPreferencesRequest,
+        GetUserPreferencesResponse, Parameter, ResetUserPreferencesRequest,
+        ResetUserPreferencesResponse, UpdateUserPreferencesRequest, UpdateUserPreferencesResponse,
+        UploadApiGroupsRequest, UploadApiGroupsResponse,
+    };
+    use crate::models::config::ProgressiveMatchingConfig;
+    use crate::progressive_matching::ParameterValue;
+    use crate::workflow::engine::tests::NoopProvider;
+    use tonic::transport::server::TcpIncoming;
+    use tonic::transport::Server;
+    use tonic::{Request, Response, Status};
+
+    /// Endpoint service with a single send_email endpoint
+    struct FakeEndpointService;
+
+    #[tonic::async_trait]
+    impl EndpointService for FakeEndpointService {
+        type GetApiGroupsStream = tokio_stream::Once<Result<GetApiGroupsResponse, Status>>;
+
+        async fn get_api_groups(
+            &self,
+            _request: Request<GetApiGroupsRequest>,
+        ) -> Result<Response<Self::GetApiGroupsStream>, Status> {
+            let parameter = |name: &str| Parameter {
+                name: name.to_string(),
+                required: "true".to_string(),
+                ..Default::default()
+            };
+            let group = ApiGroup {
+                id: "mail".to_string(),
+                endpoints: vec![Endpoint {
+                    id: "send_email".to_string(),
+                    text: "send an email".to_string(),
+                    verb: "POST".to_string(),
+                    parameters: vec![parameter("to"), parameter("subject")],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            Ok(Response::new(tokio_stream::once(Ok(
+                GetApiGroupsResponse {
+                    api_groups: vec![group],
+                },
+            ))))
+        }
+
+        async fn upload_api_groups(
+            &self,
+            _request: Request<UploadApiGroupsRequest>,
+        ) -> Result<Response<UploadApiGroupsResponse>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn get_user_preferences(
+            &self,
+            _request: Request<GetUserPreferencesRequest>,
+        ) -> Result<Response<GetUserPreferencesResponse>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn update_user_preferences(
+            &self,
+            _request: Request<UpdateUserPreferencesRequest>,
+        ) -> Result<Response<UpdateUserPreferencesResponse>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn reset_user_preferences(
+            &self,
+            _request: Request<ResetUserPreferencesRequest>,
+        ) -> Result<Response<ResetUserPreferencesResponse>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_followup_uses_the_shared_in_memory_manager() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(EndpointServiceServer::new(FakeEndpointService))
+                .serve_with_incoming(incoming),
+        );
+
+        // Pending requests in sqlite::memory: only exist in this manager
+        let manager = ProgressiveMatchingManager::new(
+            "sqlite::memory:",
+            &ProgressiveMatchingConfig::default(),
+        )
+        .await
+        .unwrap();
+        let email = "ann@example.com";
+        manager
+            .update_match(
+                "conv",
+                "send_email",
+                email,
+                vec![ParameterValue {
+                    name: "to".to_string(),
+                    value: "john@example.com".to_string(),
+                    description: String::new(),
+                }],
+                &[],
+                "email john",
+            )
+            .await
+            .unwrap();
+
+        // A cancellation is recognized without an LLM call
+        let result = analyze_sentence_enhanced(
+            "never mind",
+            Arc::new(NoopProvider),
+            Some(api_url),
+            email,
+            Some("conv".to_string()),
+            &ParameterDefaults::default(),
+            Some(&manager),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.endpoint_id, "request_cancelled");
+        assert!(manager
+            .get_incomplete_match("conv", email)
+            .await
+            .unwrap()
+            .is_none());
     }
 }
@@ -0,0 +1,365 @@
+// src/analysis/confirmation.rs - Confirm endpoints that change or delete data before use
+//
+// A complete match of an endpoint at or above `confirmation.min_risk_level` is sent
+// with a summary to confirm and held in the conversation. The next message confirms
+// it, declines it, or changes some of its values, after which it is confirmed again.
+use crate::analysis::followup_intent::detect_cancellation;
+use crate::analysis::missing_fields;
+use crate::analysis::parameter_extraction::{extract_parameters_from_followup, FollowupParameters};
+use crate::analysis::progressive_handler::describe_changes;
+use crate::analysis::response_builders::{
+    create_declined_confirmation_response, generate_missing_fields_prompt,
+};
+use crate::app_log;
+use crate::endpoint_client::get_enhanced_endpoints;
+use crate::metrics;
+use crate::models::config::ConfirmationConfig;
+use crate::models::providers::ModelProvider;
+use crate::models::{
+    Confirmation, ConfirmationStatus, EndpointParameter, EnhancedAnalysisResult, MatchingInfo,
+    MatchingStatus, ParameterChange, ParameterMatch,
+};
+use crate::progressive_matching::ParameterValue;
+use crate::workflow::classify_intent::IntentType;
+use std::error::Error;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfirmationReply {
+    Yes,
+    No,
+    /// Anything else: changes to the values, or an unrelated message
+    Edit,
+}
+
+/// Words that confirm, in the languages of `utils/language.rs`
+const YES_WORDS: &[&str] = &[
+    "yes",
+    "yep",
+    "yeah",
+    "y",
+    "sure",
+    "ok",
+    "okay",
+    "confirm",
+    "confirmed",
+    "correct",
+    "proceed",
+    "ahead",
+    "oui",
+    "ouais",
+    "d'accord",
+    "confirme",
+    "confirmé",
+    "vas-y",
+    "sí",
+    "si",
+    "claro",
+    "vale",
+    "adelante",
+    "confirmo",
+    "dale",
+    "ja",
+    "klar",
+    "genau",
+    "bestätige",
+    "bestätigt",
+    "sì",
+    "certo",
+    "confermo",
+    "procedi",
+    "bene",
+    "sim",
+    "pode",
+    "akkoord",
+    "prima",
+];
+
+/// Words that decline. Cancellations ("never mind") are recognized as well.
+const NO_WORDS: &[&str] = &[
+    "no", "nope", "nah", "n", "don't", "dont", "stop", "non", "nein", "nicht", "não", "nao", "nee",
+    "niet",
+];
+
+/// Words that may surround a yes or a no
+const FILLER_WORDS: &[&str] = &[
+    "please", "do", "it", "that", "this", "go", "thanks", "thank", "you", "fine", "good", "right",
+    "just", "merci", "s'il", "vous", "plaît", "plait", "fais", "le", "pas", "por", "favor",
+    "gracias", "hazlo", "bitte", "danke", "mach", "es", "grazie", "va", "fallo", "obrigado", "faz",
+    "isso", "dank", "je", "doe", "maar", "wel",
+];
+
+/// Tell a yes or a no from anything else. A reply that says more than yes or no
+/// ("no, the date is the 12th") is an edit.
+pub fn classify_reply(sentence: &str) -> ConfirmationReply {
+    if detect_cancellation(sentence) {
+        return ConfirmationReply::No;
+    }
+    let text = sentence.to_lowercase();
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '-')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let yes = words.iter().any(|word| YES_WORDS.contains(word));
+    let no = words.iter().any(|word| NO_WORDS.contains(word));
+    let only_reply = words.iter().all(|word| {
+        YES_WORDS.contains(word) || NO_WORDS.contains(word) || FILLER_WORDS.contains(word)
+    });
+    match (yes, no, only_reply) {
+        (true, false, true) => ConfirmationReply::Yes,
+        (false, true, true) => ConfirmationReply::No,
+        _ => ConfirmationReply::Edit,
+    }
+}
+
+/// Does `result` have to be confirmed before the client calls its endpoint?
+pub fn needs_confirmation(result: &EnhancedAnalysisResult, config: &ConfirmationConfig) -> bool {
+    config.enabled
+        && result.intent == IntentType::ActionableRequest
+        && matches!(result.matching_info.status, MatchingStatus::Complete)
+        && result.confirmation.is_none()
+        && result.risk_level >= config.min_risk_level
+}
+
+/// The summary to confirm, in the request's language
+pub fn summarize(result: &EnhancedAnalysisResult) -> String {
+    missing_fields::confirmation_summary(&missing_fields::request_language(), result)
+}
+
+/// Mark `result` as waiting for confirmation and ask for it in `user_prompt`
+pub fn require_confirmation(result: &mut EnhancedAnalysisResult) {
+    let summary = summarize(result);
+    result.user_prompt = Some(if result.changed_parameters.is_empty() {
+        summary.clone()
+    } else {
+        format!(
+            "{} {}",
+            describe_changes(&result.changed_parameters),
+            summary
+        )
+    });
+    result.confirmation = Some(Confirmation {
+        status: ConfirmationStatus::Required,
+        summary,
+    });
+}
+
+/// The match was accounted for by the message that asked for the confirmation
+fn clear_usage(result: &mut EnhancedAnalysisResult) {
+    result.usage.input_tokens = 0;
+    result.usage.output_tokens = 0;
+    result.usage.total_tokens = 0;
+    result.usage.estimated = false;
+    result.total_input_tokens = 0;
+    result.total_output_tokens = 0;
+}
+
+/// Apply the values a reply changed to the matched parameters
+fn apply_edits(
+    parameters: &mut Vec<ParameterMatch>,
+    edits: FollowupParameters,
+    endpoint_parameters: &[EndpointParameter],
+) -> Vec<ParameterChange> {
+    let mut changes = Vec::new();
+    for edit in edits.values {
+        match parameters.iter_mut().find(|p| p.name == edit.name) {
+            Some(param) if param.value.as_deref() == Some(edit.value.as_str()) => {}
+            Some(param) => {
+                param.default_source = None;
+                if let Some(previous) = param.value.replace(edit.value.clone()) {
+                    changes.push(ParameterChange {
+                        name: edit.name,
+                        previous,
+                        value: Some(edit.value),
+                    });
+                }
+            }
+            None => parameters.push(ParameterMatch {
+                description: endpoint_parameters
+                    .iter()
+                    .find(|p| p.name == edit.name)
+                    .map(|p| p.description.clone())
+                    .unwrap_or(edit.description),
+                name: edit.name,
+                value: Some(edit.value),
+                default_source: None,
+            }),
+        }
+    }
+    for name in edits.removed {
+        if let Some(previous) = parameters
+            .iter_mut()
+            .find(|p| p.name == name)
+            .and_then(|p| {
+                p.default_source = None;
+                p.value.take()
+            })
+        {
+            changes.push(ParameterChange {
+                name,
+                previous,
+                value: None,
+            });
+        }
+    }
+    changes
+}
+
+/// Answer the message that follows a confirmation request. `Ok(None)` means the
+/// message is not a reply: the confirmation is dropped and the message analyzed
+/// normally. An edited request comes back without a confirmation, to be confirmed
+/// again once it is complete.
+pub async fn handle_confirmation_reply(
+    sentence: &str,
+    pending: &EnhancedAnalysisResult,
+    provider: Arc<dyn ModelProvider>,
+    api_url: &str,
+    email: &str,
+) -> Result<Option<EnhancedAnalysisResult>, Box<dyn Error + Send + Sync>> {
+    let summary = pending
+        .confirmation
+        .as_ref()
+        .map(|c| c.summary.clone())
+        .unwrap_or_else(|| summarize(pending));
+
+    match classify_reply(sentence) {
+        ConfirmationReply::Yes => {
+            app_log!(info, "User confirmed endpoint {}", pending.endpoint_id);
+            metrics::record_confirmation("confirmed");
+            let mut result = pending.clone();
+            clear_usage(&mut result);
+            result.changed_parameters.clear();
+            result.user_prompt = Some(missing_fields::confirmed(
+                &missing_fields::request_language(),
+                &result.endpoint_name,
+            ));
+            result.confirmation = Some(Confirmation {
+                status: ConfirmationStatus::Confirmed,
+                summary,
+            });
+            return Ok(Some(result));
+        }
+        ConfirmationReply::No => {
+            app_log!(info, "User declined endpoint {}", pending.endpoint_id);
+            metrics::record_confirmation("declined");
+            return Ok(Some(create_declined_confirmation_response(
+                pending, summary,
+            )));
+        }
+        ConfirmationReply::Edit => {}
+    }
+
+    let enhanced_endpoints = get_enhanced_endpoints(api_url, email).await?;
+    let endpoint = enhanced_endpoints
+        .iter()
+        .find(|e| e.id == pending.endpoint_id)
+        .ok_or("Endpoint of the pending confirmation no longer exists")?;
+    let current: Vec<ParameterValue> = pending
+        .parameters
+        .iter()
+        .filter_map(|p| {
+            p.value.as_ref().map(|value| ParameterValue {
+                name: p.name.clone(),
+                value: value.clone(),
+                description: p.description.clone(),
+            })
+        })
+        .collect();
+    let edits =
+        extract_parameters_from_followup(sentence, provider, &endpoint.parameters, &current)
+            .await?;
+    if edits.is_empty() {
+        app_log!(
+            info,
+            "Message is not a reply to the confirmation of {}",
+            pending.endpoint_id
+        );
+        metrics::record_confirmation("dropped");
+        return Ok(None);
+    }
+
+    metrics::record_confirmation("edited");
+    let mut result = pending.clone();
+    // Only the extraction call is spent on this reply
+    clear_usage(&mut result);
+    result.add_usage(&edits.usage);
+    result.confirmation = None;
+    result.changed_parameters = apply_edits(&mut result.parameters, edits, &endpoint.parameters);
+    result.matching_info = MatchingInfo::compute(&result.parameters, &endpoint.parameters);
+    if !matches!(result.matching_info.status, MatchingStatus::Complete) {
+        // A required value was removed: the request continues as a pending one
+        result.user_prompt = Some(format!(
+            "{} {}",
+            describe_changes(&result.changed_parameters),
+            generate_missing_fields_prompt(&result.matching_info.missing_required_fields)
+        ));
+    }
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RiskLevel;
+
+    #[test]
+    fn test_replies_in_several_languages() {
+        for yes in [
+            "yes",
+            "Yes please!",
+            "ok, go ahead",
+            "Oui, vas-y",
+            "sí",
+            "Ja, bitte",
+        ] {
+            assert_eq!(classify_reply(yes), ConfirmationReply::Yes, "{yes}");
+        }
+        for no in [
+            "no",
+            "No thanks",
+            "don't do it",
+            "non merci",
+            "nein",
+            "never mind",
+            "cancela eso",
+            "cancelar",
+            "annuleer maar",
+        ] {
+            assert_eq!(classify_reply(no), ConfirmationReply::No, "{no}");
+        }
+        for edit in [
+            "no, the date is the 12th",
+            "yes but send it to ann",
+            "yes no",
+            "list my invoices",
+        ] {
+            assert_eq!(classify_reply(edit), ConfirmationReply::Edit, "{edit}");
+        }
+    }
+
+    #[test]
+    fn test_edits_change_and_remove_values() {
+        let param = |name: &str, value: Option<&str>| ParameterMatch {
+            name: name.to_string(),
+            description: String::new(),
+            value: value.map(str::to_string),
+            default_source: None,
+        };
+        let mut parameters = vec![param("date", Some("10")), param("note", Some("late"))];
+        let edits = FollowupParameters {
+            values: vec![ParameterValue {
+                name: "date".to_string(),
+                value: "12".to_string(),
+                description: String::new(),
+            }],
+            removed: vec!["note".to_string()],
+            ..Default::default()
+        };
+        let changes = apply_edits(&mut parameters, edits, &[]);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(parameters[0].value.as_deref(), Some("12"));
+        assert_eq!(parameters[1].value, None);
+        assert_eq!(RiskLevel::from_verb("delete"), RiskLevel::High);
+        assert_eq!(RiskLevel::from_verb("GET"), RiskLevel::Low);
+    }
+}
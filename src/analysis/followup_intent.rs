@@ -0,0 +1,225 @@
+// src/analysis/followup_intent.rs - Does a follow-up continue the pending request?
+use crate::app_log;
+use crate::models::config::load_models_config;
+use crate::models::providers::token_counter::TokenUsage;
+use crate::models::providers::ModelProvider;
+use crate::models::EnhancedEndpoint;
+use crate::prompts::budget::{self, PromptSection};
+use crate::prompts::template::Vars;
+use crate::prompts::PromptManager;
+use std::error::Error;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FollowupIntent {
+    /// Gives or corrects parameters of the pending request
+    Continue,
+    /// Drops the pending request and asks for nothing else
+    Cancel,
+    /// Drops the pending request and asks for something else
+    Replace,
+    /// Asks for something else, keeping the pending request for later
+    Switch,
+    /// Goes back to another pending request of the conversation
+    Resume(String),
+}
+
+/// Phrases that drop the pending request, in the languages of `utils/language.rs`
+const CANCEL_PHRASES: &[&str] = &[
+    "cancel",
+    "never mind",
+    "nevermind",
+    "forget it",
+    "forget that",
+    "forget about it",
+    "abort",
+    "annule",
+    "laisse tomber",
+    "oublie",
+    "cancela",
+    "olvídalo",
+    "olvidalo",
+    "abbrechen",
+    "vergiss es",
+    "annulla",
+    "lascia perdere",
+    "cancelar",
+    "esquece",
+    "annuleer",
+    "laat maar",
+];
+
+/// Words that may surround a cancellation without asking for anything else
+const FILLER_WORDS: &[&str] = &[
+    "actually", "please", "that", "this", "it", "the", "request", "ok", "okay", "oh", "no", "just",
+    "all", "en", "fait", "ça", "cela", "tout", "eso", "todo", "das", "alles", "bitte", "merci",
+    "thanks", "por", "favor", "isso", "tudo", "dat", "maar",
+];
+
+fn split_words(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Recognize a message that only cancels, e.g. "never mind" or "actually, cancel that".
+/// A cancellation followed by a new request ("cancel that and list my invoices")
+/// is left to the LLM.
+pub fn detect_cancellation(sentence: &str) -> bool {
+    let text = sentence.to_lowercase();
+    let mut words = split_words(&text);
+    // Whole words only, longest phrase first, so "cancelar" is not read as "cancel"
+    let mut phrases: Vec<Vec<&str>> = CANCEL_PHRASES
+        .iter()
+        .map(|phrase| phrase.split(' ').collect())
+        .collect();
+    phrases.sort_by_key(|phrase| std::cmp::Reverse(phrase.len()));
+    let found = phrases.iter().find_map(|phrase| {
+        words
+            .windows(phrase.len())
+            .position(|window| window == phrase.as_slice())
+            .map(|start| start..start + phrase.len())
+    });
+    let Some(found) = found else {
+        return false;
+    };
+    words.drain(found);
+    words.iter().all(|word| FILLER_WORDS.contains(word))
+}
+
+fn parse_response(response: &str, others: &[&EnhancedEndpoint]) -> FollowupIntent {
+    let response = response.trim().trim_matches(|c| c == '"' || c == '\'');
+    let mut words = response.split_whitespace();
+    let keyword = words.next().unwrap_or("").to_uppercase();
+    match keyword.trim_end_matches(|c: char| !c.is_alphanumeric()) {
+        "CANCEL" => FollowupIntent::Cancel,
+        "REPLACE" => FollowupIntent::Replace,
+        "SWITCH" => FollowupIntent::Switch,
+        "RESUME" => {
+            let id = words
+                .next()
+                .unwrap_or("")
+                .trim_matches(|c: char| !c.is_alphanumeric() && c != '_' && c != '-' && c != '.');
+            match others.iter().find(|endpoint| endpoint.id == id) {
+                Some(endpoint) => FollowupIntent::Resume(endpoint.id.clone()),
+                None => {
+                    app_log!(warn, "Follow-up resumes unknown request '{}'", id);
+                    FollowupIntent::Switch
+                }
+            }
+        }
+        _ => FollowupIntent::Continue,
+    }
+}
+
+/// Classify a follow-up of `pending`, the request on top of the conversation's stack.
+/// `others` are the conversation's other pending requests, most recent first.
+pub async fn classify_followup(
+    sentence: &str,
+    pending: &EnhancedEndpoint,
+    missing_fields: &[String],
+    others: &[&EnhancedEndpoint],
+    provider: Arc<dyn ModelProvider>,
+    use_llm: bool,
+) -> Result<(FollowupIntent, TokenUsage), Box<dyn Error + Send + Sync>> {
+    if detect_cancellation(sentence) {
+        app_log!(info, "Follow-up cancels the pending request");
+        return Ok((FollowupIntent::Cancel, TokenUsage::default()));
+    }
+    if !use_llm {
+        return Ok((FollowupIntent::Continue, TokenUsage::default()));
+    }
+
+    let prompt_manager = PromptManager::new().await?;
+    let models_config = load_models_config().await?;
+    let template = prompt_manager
+        .get_prompt("followup_intent", None)
+        .ok_or("followup_intent prompt not found")?;
+
+    let other_requests: Vec<Vars> = others
+        .iter()
+        .map(|endpoint| {
+            Vars::from([
+                ("id".to_string(), endpoint.id.as_str().into()),
+                (
+                    "description".to_string(),
+                    endpoint.description.as_str().into(),
+                ),
+            ])
+        })
+        .collect();
+    let prompt = budget::fit(
+        template,
+        vec![
+            PromptSection::fixed("sentence", sentence),
+            PromptSection::fixed(
+                "pending_request",
+                format!("{} ({})", pending.name, pending.description),
+            ),
+            PromptSection::fixed("missing_fields", missing_fields.join(", ")),
+            PromptSection::records("other_requests", other_requests)
+                .keep_at_least(0)
+                .shorten_items_to(120),
+        ],
+        provider.get_model_name(),
+        &models_config.default,
+    )
+    .await?;
+
+    let response = provider
+        .generate(&prompt.prompt, &prompt.model_config)
+        .await?;
+    let intent = parse_response(&response.content, others);
+    app_log!(
+        info,
+        "Follow-up classified as {:?} (response: {})",
+        intent,
+        response.content.trim()
+    );
+    Ok((intent, response.usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_and_llm_responses() {
+        assert!(detect_cancellation("Never mind"));
+        assert!(detect_cancellation("actually, cancel that please"));
+        assert!(detect_cancellation("Laisse tomber."));
+        assert!(detect_cancellation("Cancela eso, por favor"));
+        assert!(detect_cancellation("Olvídalo"));
+        assert!(detect_cancellation("Cancelar isso"));
+        assert!(detect_cancellation("Esquece"));
+        assert!(detect_cancellation("Annuleer dat maar"));
+        assert!(detect_cancellation("Laat maar"));
+        assert!(!detect_cancellation("cancelled invoices of may"));
+        assert!(!detect_cancellation("cancel that and list my invoices"));
+        assert!(!detect_cancellation("cancel my order 42"));
+        assert!(!detect_cancellation("the amount is 30 euros"));
+
+        let invoices = EnhancedEndpoint {
+            id: "list_invoices".to_string(),
+            ..Default::default()
+        };
+        let others = [&invoices];
+        assert_eq!(
+            parse_response("CONTINUE", &others),
+            FollowupIntent::Continue
+        );
+        assert_eq!(
+            parse_response("\"Replace\"", &others),
+            FollowupIntent::Replace
+        );
+        assert_eq!(
+            parse_response("RESUME list_invoices", &others),
+            FollowupIntent::Resume("list_invoices".to_string())
+        );
+        assert_eq!(
+            parse_response("RESUME unknown", &others),
+            FollowupIntent::Switch
+        );
+        assert_eq!(parse_response("no idea", &others), FollowupIntent::Continue);
+    }
+}
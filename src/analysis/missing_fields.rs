@@ -0,0 +1,712 @@
+// src/analysis/missing_fields.rs - Questions for missing parameters in the user's language
+//
+// The question is built from the templates of the request's language: the phrase
+// for one missing field or for several, chosen by the language's plural rule, with
+// the fields listed the way the language joins lists. Each field shows a few sample
+// values from the endpoint catalog. Languages without templates get English, unless
+// `missing_fields.llm_polish` has the LLM rewrite the question in their language.
+// The messages sent along with the question (a cancelled or resumed request,
+// changed values, a summary to confirm) come from the same templates.
+use crate::app_log;
+use crate::models::config::{load_missing_fields_config, load_models_config};
+use crate::models::providers::token_counter::TokenUsage;
+use crate::models::providers::ModelProvider;
+use crate::models::{EnhancedAnalysisResult, MissingField, ParameterChange, RiskLevel};
+use crate::prompts::budget::{self, PromptSection};
+use crate::prompts::PromptManager;
+use crate::trace;
+use crate::usage;
+use std::error::Error;
+use std::sync::Arc;
+
+/// Catalog examples shown per field
+const MAX_EXAMPLES: usize = 2;
+
+/// Plural categories the templates distinguish
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Plural {
+    One,
+    Other,
+}
+
+/// A one-field and a several-field variant, in that order
+type Forms = [&'static str; 2];
+
+/// The translatable text of one language. `{endpoint}`, `{fields}`, `{count}`,
+/// `{field}`, `{name}`, `{examples}`, `{previous}`, `{value}` and `{values}` are
+/// filled in.
+struct Templates {
+    code: &'static str,
+    /// The question when the request's endpoint is known
+    with_endpoint: Forms,
+    /// The question on its own
+    without_endpoint: Forms,
+    /// A field known only by its name
+    field: &'static str,
+    /// A field followed by its examples
+    examples: &'static str,
+    /// Between the last two items of a list
+    and: &'static str,
+    /// Between the examples of a field
+    or: &'static str,
+    /// The user cancelled the request to `{endpoint}`
+    cancelled: &'static str,
+    /// After `cancelled`, when the request to `{endpoint}` is pending again
+    back_to_earlier: &'static str,
+    /// The user went back to the request to `{endpoint}`
+    resumed: &'static str,
+    /// A request completed while the one to `{endpoint}` is still pending
+    still_pending: &'static str,
+    /// A follow-up changed `{name}` from `{previous}` to `{value}`
+    updated: &'static str,
+    /// A follow-up withdrew the value of `{name}`
+    removed: &'static str,
+    /// The summary of an endpoint to confirm, without and with `{values}`
+    confirm: &'static str,
+    confirm_with_values: &'static str,
+    /// Added to the summary when the endpoint's risk is high
+    irreversible: &'static str,
+    /// Ends the summary
+    confirm_reply: &'static str,
+    confirmed: &'static str,
+    declined: &'static str,
+    /// Whether the list takes a comma before `and` as well ("a, b, and c")
+    serial_comma: bool,
+    /// Whether names from the catalog are lowercased inside the sentence
+    lowercase: bool,
+    plural: fn(usize) -> Plural,
+}
+
+fn one_is_singular(n: usize) -> Plural {
+    if n == 1 {
+        Plural::One
+    } else {
+        Plural::Other
+    }
+}
+
+/// French and Portuguese also treat zero as singular
+fn zero_and_one_are_singular(n: usize) -> Plural {
+    if n <= 1 {
+        Plural::One
+    } else {
+        Plural::Other
+    }
+}
+
+const TEMPLATES: [Templates; 7] = [
+    Templates {
+        code: "en",
+        with_endpoint: [
+            "To proceed with {endpoint}, I need one more piece of information: {fields}. Could you please provide that?",
+            "To complete your {endpoint} request, I need {count} more details: {fields}. Could you provide this information?",
+        ],
+        without_endpoint: [
+            "I need one more piece of information: {fields}. Could you please provide it?",
+            "I need {count} more pieces of information: {fields}. Could you provide them?",
+        ],
+        field: "the {name}",
+        examples: "{field} (for example {examples})",
+        and: "and",
+        or: "or",
+        cancelled: "Okay, I cancelled your request to {endpoint}.",
+        back_to_earlier: "Back to your earlier request to {endpoint}:",
+        resumed: "Back to your request to {endpoint}:",
+        still_pending: "Your earlier request to {endpoint} is still waiting for more information.",
+        updated: "Updated {name}: {previous} → {value}.",
+        removed: "Removed {name}.",
+        confirm: "Please confirm: {endpoint}.",
+        confirm_with_values: "Please confirm: {endpoint} with {values}.",
+        irreversible: "This cannot be undone.",
+        confirm_reply: "Reply yes to go ahead, no to cancel, or tell me what to change.",
+        confirmed: "Confirmed: {endpoint}.",
+        declined: "Okay, I will not go ahead with {endpoint}.",
+        serial_comma: true,
+        lowercase: true,
+        plural: one_is_singular,
+    },
+    Templates {
+        code: "fr",
+        with_endpoint: [
+            "Pour {endpoint}, il me manque une information : {fields}. Pouvez-vous me l'indiquer ?",
+            "Pour {endpoint}, il me manque {count} informations : {fields}. Pouvez-vous me les indiquer ?",
+        ],
+        without_endpoint: [
+            "Il me manque une information : {fields}. Pouvez-vous me l'indiquer ?",
+            "Il me manque {count} informations : {fields}. Pouvez-vous me les indiquer ?",
+        ],
+        field: "{name}",
+        examples: "{field} (par exemple {examples})",
+        and: "et",
+        or: "ou",
+        cancelled: "D'accord, j'ai annulé votre demande pour {endpoint}.",
+        back_to_earlier: "Revenons à votre demande précédente pour {endpoint} :",
+        resumed: "Revenons à votre demande pour {endpoint} :",
+        still_pending: "Votre demande précédente pour {endpoint} attend toujours des informations.",
+        updated: "{name} modifié : {previous} → {value}.",
+        removed: "{name} supprimé.",
+        confirm: "Veuillez confirmer : {endpoint}.",
+        confirm_with_values: "Veuillez confirmer : {endpoint} avec {values}.",
+        irreversible: "Cette action est irréversible.",
+        confirm_reply: "Répondez oui pour continuer, non pour annuler, ou dites-moi ce qu'il faut modifier.",
+        confirmed: "Confirmé : {endpoint}.",
+        declined: "D'accord, je ne donne pas suite à {endpoint}.",
+        serial_comma: false,
+        lowercase: true,
+        plural: zero_and_one_are_singular,
+    },
+    Templates {
+        code: "es",
+        with_endpoint: [
+            "Para {endpoint}, necesito un dato más: {fields}. ¿Podría indicármelo?",
+            "Para {endpoint}, necesito {count} datos más: {fields}. ¿Podría indicármelos?",
+        ],
+        without_endpoint: [
+            "Necesito un dato más: {fields}. ¿Podría indicármelo?",
+            "Necesito {count} datos más: {fields}. ¿Podría indicármelos?",
+        ],
+        field: "{name}",
+        examples: "{field} (por ejemplo {examples})",
+        and: "y",
+        or: "o",
+        cancelled: "De acuerdo, he cancelado su solicitud para {endpoint}.",
+        back_to_earlier: "Volvamos a su solicitud anterior para {endpoint}:",
+        resumed: "Volvamos a su solicitud para {endpoint}:",
+        still_pending: "Su solicitud anterior para {endpoint} sigue esperando más información.",
+        updated: "{name} actualizado: {previous} → {value}.",
+        removed: "{name} eliminado.",
+        confirm: "Confirme, por favor: {endpoint}.",
+        confirm_with_values: "Confirme, por favor: {endpoint} con {values}.",
+        irreversible: "Esta acción no se puede deshacer.",
+        confirm_reply: "Responda sí para continuar, no para cancelar, o dígame qué cambiar.",
+        confirmed: "Confirmado: {endpoint}.",
+        declined: "De acuerdo, no seguiré adelante con {endpoint}.",
+        serial_comma: false,
+        lowercase: true,
+        plural: one_is_singular,
+    },
+    Templates {
+        code: "de",
+        with_endpoint: [
+            "Für {endpoint} fehlt mir noch eine Angabe: {fields}. Können Sie sie mir nennen?",
+            "Für {endpoint} fehlen mir noch {count} Angaben: {fields}. Können Sie sie mir nennen?",
+        ],
+        without_endpoint: [
+            "Mir fehlt noch eine Angabe: {fields}. Können Sie sie mir nennen?",
+            "Mir fehlen noch {count} Angaben: {fields}. Können Sie sie mir nennen?",
+        ],
+        field: "{name}",
+        examples: "{field} (zum Beispiel {examples})",
+        and: "und",
+        or: "oder",
+        cancelled: "In Ordnung, ich habe Ihre Anfrage für {endpoint} abgebrochen.",
+        back_to_earlier: "Zurück zu Ihrer früheren Anfrage für {endpoint}:",
+        resumed: "Zurück zu Ihrer Anfrage für {endpoint}:",
+        still_pending: "Ihre frühere Anfrage für {endpoint} wartet noch auf weitere Angaben.",
+        updated: "{name} geändert: {previous} → {value}.",
+        removed: "{name} entfernt.",
+        confirm: "Bitte bestätigen Sie: {endpoint}.",
+        confirm_with_values: "Bitte bestätigen Sie: {endpoint} mit {values}.",
+        irreversible: "Dies kann nicht rückgängig gemacht werden.",
+        confirm_reply: "Antworten Sie mit ja, um fortzufahren, mit nein, um abzubrechen, oder sagen Sie mir, was ich ändern soll.",
+        confirmed: "Bestätigt: {endpoint}.",
+        declined: "In Ordnung, ich führe {endpoint} nicht aus.",
+        serial_comma: false,
+        lowercase: false,
+        plural: one_is_singular,
+    },
+    Templates {
+        code: "it",
+        with_endpoint: [
+            "Per {endpoint} mi serve ancora un'informazione: {fields}. Può indicarmela?",
+            "Per {endpoint} mi servono ancora {count} informazioni: {fields}. Può indicarmele?",
+        ],
+        without_endpoint: [
+            "Mi serve ancora un'informazione: {fields}. Può indicarmela?",
+            "Mi servono ancora {count} informazioni: {fields}. Può indicarmele?",
+        ],
+        field: "{name}",
+        examples: "{field} (per esempio {examples})",
+        and: "e",
+        or: "o",
+        cancelled: "D'accordo, ho annullato la sua richiesta per {endpoint}.",
+        back_to_earlier: "Torniamo alla sua richiesta precedente per {endpoint}:",
+        resumed: "Torniamo alla sua richiesta per {endpoint}:",
+        still_pending: "La sua richiesta precedente per {endpoint} attende ancora altre informazioni.",
+        updated: "{name} aggiornato: {previous} → {value}.",
+        removed: "{name} rimosso.",
+        confirm: "Confermi, per favore: {endpoint}.",
+        confirm_with_values: "Confermi, per favore: {endpoint} con {values}.",
+        irreversible: "L'operazione non può essere annullata.",
+        confirm_reply: "Risponda sì per procedere, no per annullare, oppure mi dica cosa cambiare.",
+        confirmed: "Confermato: {endpoint}.",
+        declined: "D'accordo, non procedo con {endpoint}.",
+        serial_comma: false,
+        lowercase: true,
+        plural: one_is_singular,
+    },
+    Templates {
+        code: "pt",
+        with_endpoint: [
+            "Para {endpoint}, preciso de mais uma informação: {fields}. Pode me informar?",
+            "Para {endpoint}, preciso de mais {count} informações: {fields}. Pode me informar?",
+        ],
+        without_endpoint: [
+            "Preciso de mais uma informação: {fields}. Pode me informar?",
+            "Preciso de mais {count} informações: {fields}. Pode me informar?",
+        ],
+        field: "{name}",
+        examples: "{field} (por exemplo {examples})",
+        and: "e",
+        or: "ou",
+        cancelled: "Certo, cancelei sua solicitação de {endpoint}.",
+        back_to_earlier: "De volta à sua solicitação anterior de {endpoint}:",
+        resumed: "De volta à sua solicitação de {endpoint}:",
+        still_pending: "Sua solicitação anterior de {endpoint} ainda aguarda mais informações.",
+        updated: "{name} atualizado: {previous} → {value}.",
+        removed: "{name} removido.",
+        confirm: "Por favor, confirme: {endpoint}.",
+        confirm_with_values: "Por favor, confirme: {endpoint} com {values}.",
+        irreversible: "Isso não pode ser desfeito.",
+        confirm_reply: "Responda sim para continuar, não para cancelar, ou diga o que devo mudar.",
+        confirmed: "Confirmado: {endpoint}.",
+        declined: "Certo, não vou prosseguir com {endpoint}.",
+        serial_comma: false,
+        lowercase: true,
+        plural: zero_and_one_are_singular,
+    },
+    Templates {
+        code: "nl",
+        with_endpoint: [
+            "Voor {endpoint} heb ik nog één gegeven nodig: {fields}. Kunt u dat doorgeven?",
+            "Voor {endpoint} heb ik nog {count} gegevens nodig: {fields}. Kunt u die doorgeven?",
+        ],
+        without_endpoint: [
+            "Ik heb nog één gegeven nodig: {fields}. Kunt u dat doorgeven?",
+            "Ik heb nog {count} gegevens nodig: {fields}. Kunt u die doorgeven?",
+        ],
+        field: "{name}",
+        examples: "{field} (bijvoorbeeld {examples})",
+        and: "en",
+        or: "of",
+        cancelled: "Goed, ik heb uw verzoek voor {endpoint} geannuleerd.",
+        back_to_earlier: "Terug naar uw eerdere verzoek voor {endpoint}:",
+        resumed: "Terug naar uw verzoek voor {endpoint}:",
+        still_pending: "Uw eerdere verzoek voor {endpoint} wacht nog op meer gegevens.",
+        updated: "{name} gewijzigd: {previous} → {value}.",
+        removed: "{name} verwijderd.",
+        confirm: "Bevestig alstublieft: {endpoint}.",
+        confirm_with_values: "Bevestig alstublieft: {endpoint} met {values}.",
+        irreversible: "Dit kan niet ongedaan worden gemaakt.",
+        confirm_reply: "Antwoord ja om door te gaan, nee om te annuleren, of vertel me wat ik moet wijzigen.",
+        confirmed: "Bevestigd: {endpoint}.",
+        declined: "Goed, ik voer {endpoint} niet uit.",
+        serial_comma: false,
+        lowercase: true,
+        plural: one_is_singular,
+    },
+];
+
+fn templates(language: &str) -> &'static Templates {
+    TEMPLATES
+        .iter()
+        .find(|t| t.code == language)
+        .unwrap_or(&TEMPLATES[0])
+}
+
+/// Replace the placeholders of `template` in one pass, so braces in the values (a
+/// path like `/invoices/{id}`, a value the user typed) are left as they are
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let placeholder = after.find('}').and_then(|end| {
+            values
+                .iter()
+                .find(|(name, _)| *name == &after[..end])
+                .map(|(_, value)| (end, value))
+        });
+        match placeholder {
+            Some((end, value)) => {
+                text.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = after;
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// An endpoint name from the catalog, as it reads inside a sentence
+fn in_sentence(name: &str, templates: &Templates) -> String {
+    if templates.lowercase {
+        name.to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+/// "a", "a and b", "a, b and c" (or "a, b, and c" in English)
+fn join(items: &[String], conjunction: &str, serial_comma: bool, language: &str) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [initial @ .., last] => {
+            // Spanish "y" and "o" become "e" and "u" before the same sound
+            let lowered = last.to_lowercase();
+            let conjunction = match (language, conjunction) {
+                ("es", "y") if lowered.starts_with('i') || lowered.starts_with("hi") => "e",
+                ("es", "o") if lowered.starts_with('o') || lowered.starts_with("ho") => "u",
+                _ => conjunction,
+            };
+            let comma = if serial_comma && initial.len() > 1 {
+                ","
+            } else {
+                ""
+            };
+            format!("{}{comma} {conjunction} {last}", initial.join(", "))
+        }
+    }
+}
+
+/// The field's description when it says more than its name, else its name
+fn describe_field(field: &MissingField, templates: &Templates, language: &str) -> String {
+    let natural_name = field.name.replace(['_', '-'], " ");
+    let described = if field.description.len() > natural_name.len() + 5
+        && !field
+            .description
+            .to_lowercase()
+            .starts_with("missing parameter")
+    {
+        if templates.lowercase {
+            field.description.to_lowercase()
+        } else {
+            field.description.clone()
+        }
+    } else {
+        fill(templates.field, &[("name", &natural_name)])
+    };
+
+    let examples: Vec<String> = field
+        .examples
+        .iter()
+        .map(|example| example.trim())
+        .filter(|example| !example.is_empty())
+        .take(MAX_EXAMPLES)
+        .map(str::to_string)
+        .collect();
+    if examples.is_empty() {
+        return described;
+    }
+    fill(
+        templates.examples,
+        &[
+            ("field", &described),
+            ("examples", &join(&examples, templates.or, false, language)),
+        ],
+    )
+}
+
+/// Ask for `fields` in `language`, for the request to `endpoint_name` when given.
+/// `None` when nothing is missing.
+pub fn question(
+    language: &str,
+    endpoint_name: Option<&str>,
+    fields: &[MissingField],
+) -> Option<String> {
+    if fields.is_empty() {
+        return None;
+    }
+    let templates = templates(language);
+    let described: Vec<String> = fields
+        .iter()
+        .map(|field| describe_field(field, templates, language))
+        .collect();
+    let forms = match endpoint_name {
+        Some(_) => &templates.with_endpoint,
+        None => &templates.without_endpoint,
+    };
+    let form = match (templates.plural)(fields.len()) {
+        Plural::One => forms[0],
+        Plural::Other => forms[1],
+    };
+    let endpoint = in_sentence(endpoint_name.unwrap_or_default(), templates);
+    Some(fill(
+        form,
+        &[
+            ("endpoint", &endpoint),
+            ("count", &fields.len().to_string()),
+            (
+                "fields",
+                &join(
+                    &described,
+                    templates.and,
+                    templates.serial_comma,
+                    templates.code,
+                ),
+            ),
+        ],
+    ))
+}
+
+/// The request to `endpoint_name` was cancelled; `back_to` is pending again
+pub fn cancelled(language: &str, endpoint_name: &str, back_to: Option<&str>) -> String {
+    let templates = templates(language);
+    let cancelled = fill(
+        templates.cancelled,
+        &[("endpoint", &in_sentence(endpoint_name, templates))],
+    );
+    match back_to {
+        Some(previous) => format!(
+            "{cancelled} {}",
+            fill(
+                templates.back_to_earlier,
+                &[("endpoint", &in_sentence(previous, templates))]
+            )
+        ),
+        None => cancelled,
+    }
+}
+
+/// The user went back to the pending request to `endpoint_name`
+pub fn resumed(language: &str, endpoint_name: &str) -> String {
+    let templates = templates(language);
+    fill(
+        templates.resumed,
+        &[("endpoint", &in_sentence(endpoint_name, templates))],
+    )
+}
+
+/// The request to `endpoint_name` is still pending after another one completed
+pub fn still_pending(language: &str, endpoint_name: &str) -> String {
+    let templates = templates(language);
+    fill(
+        templates.still_pending,
+        &[("endpoint", &in_sentence(endpoint_name, templates))],
+    )
+}
+
+/// "Updated date: 10 → 12. Removed subject." for the values a follow-up changed
+pub fn changes(language: &str, changes: &[ParameterChange]) -> String {
+    let templates = templates(language);
+    changes
+        .iter()
+        .map(|change| match &change.value {
+            Some(value) => fill(
+                templates.updated,
+                &[
+                    ("name", &change.name),
+                    ("previous", &change.previous),
+                    ("value", value),
+                ],
+            ),
+            None => fill(templates.removed, &[("name", &change.name)]),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// "Please confirm: Delete invoice (DELETE /invoices/{id}) with id = 42. ..."
+pub fn confirmation_summary(language: &str, result: &EnhancedAnalysisResult) -> String {
+    let templates = templates(language);
+    let values: Vec<String> = result
+        .parameters
+        .iter()
+        .filter_map(|p| {
+            p.value
+                .as_ref()
+                .map(|value| format!("{} = {}", p.name.replace('_', " "), value))
+        })
+        .collect();
+    let endpoint = format!(
+        "{} ({} {})",
+        in_sentence(&result.endpoint_name, templates),
+        result.verb,
+        result.path
+    );
+    let mut summary = if values.is_empty() {
+        fill(templates.confirm, &[("endpoint", &endpoint)])
+    } else {
+        let values = join(&values, templates.and, templates.serial_comma, language);
+        fill(
+            templates.confirm_with_values,
+            &[("endpoint", &endpoint), ("values", &values)],
+        )
+    };
+    if result.risk_level == RiskLevel::High {
+        summary.push(' ');
+        summary.push_str(templates.irreversible);
+    }
+    summary.push(' ');
+    summary.push_str(templates.confirm_reply);
+    summary
+}
+
+/// The user confirmed the request to `endpoint_name`
+pub fn confirmed(language: &str, endpoint_name: &str) -> String {
+    let templates = templates(language);
+    fill(
+        templates.confirmed,
+        &[("endpoint", &in_sentence(endpoint_name, templates))],
+    )
+}
+
+/// The user declined the request to `endpoint_name`
+pub fn declined(language: &str, endpoint_name: &str) -> String {
+    let templates = templates(language);
+    fill(
+        templates.declined,
+        &[("endpoint", &in_sentence(endpoint_name, templates))],
+    )
+}
+
+/// Language of the current request, see `UsageScope::language`; English when unknown
+pub fn request_language() -> String {
+    usage::current_scope()
+        .and_then(|scope| scope.language)
+        .unwrap_or_else(|| "en".to_string())
+}
+
+/// Have the LLM rewrite `question` so it reads naturally in `language`, when
+/// `missing_fields.llm_polish` is on. The templated question stays when it is off
+/// or the LLM fails.
+pub async fn polish(
+    question: String,
+    language: &str,
+    provider: Arc<dyn ModelProvider>,
+) -> (String, TokenUsage) {
+    if !load_missing_fields_config()
+        .await
+        .unwrap_or_default()
+        .llm_polish
+    {
+        return (question, TokenUsage::default());
+    }
+    match trace::step(
+        "polish_missing_fields",
+        rewrite(&question, language, provider),
+    )
+    .await
+    {
+        Ok((polished, usage)) if !polished.is_empty() => (polished, usage),
+        Ok((_, usage)) => (question, usage),
+        Err(e) => {
+            app_log!(warn, "Failed to polish the missing fields question: {}", e);
+            (question, TokenUsage::default())
+        }
+    }
+}
+
+async fn rewrite(
+    question: &str,
+    language: &str,
+    provider: Arc<dyn ModelProvider>,
+) -> Result<(String, TokenUsage), Box<dyn Error + Send + Sync>> {
+    let prompt_manager = PromptManager::new().await?;
+    let models_config = load_models_config().await?;
+    let template = prompt_manager
+        .get_prompt("polish_missing_fields", None)
+        .ok_or("polish_missing_fields prompt not found")?;
+    let prompt = budget::fit(
+        template,
+        vec![
+            PromptSection::fixed("question", question),
+            PromptSection::fixed("language", language),
+        ],
+        provider.get_model_name(),
+        &models_config.default,
+    )
+    .await?;
+
+    let response = provider
+        .generate(&prompt.prompt, &prompt.model_config)
+        .await?;
+    let polished = response.content.trim().trim_matches('"').trim().to_string();
+    app_log!(debug, "Polished missing fields question: {}", polished);
+    Ok((polished, response.usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, examples: &[&str]) -> MissingField {
+        MissingField {
+            name: name.to_string(),
+            description: String::new(),
+            examples: examples.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_questions_follow_plural_rules_and_list_joining() {
+        let one = [field(
+            "due_date",
+            &["2024-05-01", "next friday", "tomorrow"],
+        )];
+        assert_eq!(
+            question("en", Some("Send Invoice"), &one).unwrap(),
+            "To proceed with send invoice, I need one more piece of information: the due date \
+             (for example 2024-05-01 or next friday). Could you please provide that?"
+        );
+        let three = [field("amount", &[]), field("city", &[]), field("item", &[])];
+        assert_eq!(
+            question("en", None, &three).unwrap(),
+            "I need 3 more pieces of information: the amount, the city, and the item. \
+             Could you provide them?"
+        );
+        assert_eq!(
+            question("fr", None, &three[..2]).unwrap(),
+            "Il me manque 2 informations : amount et city. Pouvez-vous me les indiquer ?"
+        );
+        assert_eq!(
+            question("es", None, &[field("amount", &[]), field("id", &[])]).unwrap(),
+            "Necesito 2 datos más: amount e id. ¿Podría indicármelos?"
+        );
+        assert_eq!(
+            question("de", Some("Rechnung senden"), &one).unwrap(),
+            "Für Rechnung senden fehlt mir noch eine Angabe: due date (zum Beispiel 2024-05-01 \
+             oder next friday). Können Sie sie mir nennen?"
+        );
+        // Languages without templates get English
+        assert!(question("ja", None, &one)
+            .unwrap()
+            .starts_with("I need one more"));
+        assert_eq!(question("en", None, &[]), None);
+        assert_eq!(zero_and_one_are_singular(0), Plural::One);
+        assert_eq!(one_is_singular(0), Plural::Other);
+    }
+
+    #[test]
+    fn test_messages_around_the_question_are_localized() {
+        assert_eq!(
+            cancelled("en", "Send Invoice", Some("List Orders")),
+            "Okay, I cancelled your request to send invoice. Back to your earlier request \
+             to list orders:"
+        );
+        assert_eq!(
+            cancelled("de", "Rechnung senden", None),
+            "In Ordnung, ich habe Ihre Anfrage für Rechnung senden abgebrochen."
+        );
+        let change = |value: Option<&str>| ParameterChange {
+            name: "path".to_string(),
+            previous: "/a/{value}".to_string(),
+            value: value.map(str::to_string),
+        };
+        // Braces in the values are not placeholders
+        assert_eq!(
+            changes("fr", &[change(Some("/b")), change(None)]),
+            "path modifié : /a/{value} → /b. path supprimé."
+        );
+        assert_eq!(
+            resumed("ja", "List Orders"),
+            "Back to your request to list orders:"
+        );
+    }
+}
@@ -1,4 +1,7 @@
 pub mod analyze_sentence_enhanced;
+pub mod confirmation;
+pub mod followup_intent;
+pub mod missing_fields;
 pub mod parameter_extraction;
 pub mod progressive_handler;
 pub mod response_builders;
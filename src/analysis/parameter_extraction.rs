@@ -1,18 +1,38 @@
 use crate::app_log;
 use crate::json_helper::sanitize_json;
 use crate::models::config::load_models_config;
+use crate::models::providers::token_counter::TokenUsage;
 use crate::models::providers::ModelProvider;
 use crate::models::EndpointParameter;
 use crate::progressive_matching::ParameterValue;
+use crate::prompts::budget::{self, PromptSection};
 use crate::prompts::PromptManager;
 use std::sync::Arc;
 
+/// What a follow-up message says about the parameters of the pending request
+#[derive(Debug, Default)]
+pub struct FollowupParameters {
+    /// New values, and corrections of values given earlier
+    pub values: Vec<ParameterValue>,
+    /// Parameters whose earlier value the user withdrew
+    pub removed: Vec<String>,
+    /// Of the extraction call
+    pub usage: TokenUsage,
+}
+
+impl FollowupParameters {
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.removed.is_empty()
+    }
+}
+
 // Extract parameters from follow-up using the existing function from sentence_analysis.rs
 pub async fn extract_parameters_from_followup(
     sentence: &str,
     provider: Arc<dyn ModelProvider>,
     endpoint_parameters: &[EndpointParameter],
-) -> Result<Vec<ParameterValue>, Box<dyn std::error::Error + Send + Sync>> {
+    current_values: &[ParameterValue],
+) -> Result<FollowupParameters, Box<dyn std::error::Error + Send + Sync>> {
     app_log!(info, "Extracting parameters from follow-up: '{}'", sentence);
 
     let prompt_manager = PromptManager::new().await?;
@@ -20,21 +40,48 @@ pub async fn extract_parameters_from_followup(
         .iter()
         .map(|p| format!("{}: {}", p.name, p.description))
         .collect();
-    let available_params_str = available_params.join("\n");
+    let mut current: Vec<String> = current_values
+        .iter()
+        .map(|p| format!("{}: {}", p.name, p.value))
+        .collect();
+    if current.is_empty() {
+        current.push("(none)".to_string());
+    }
+    let current_count = current.len();
 
-    let prompt = prompt_manager.format_extract_followup_parameters_with_mapping(
-        sentence,
-        &available_params_str,
-        Some("v1"),
-    )?;
+    let template = prompt_manager
+        .get_prompt("extract_followup_parameters_mapping", None)
+        .ok_or("extract_followup_parameters_mapping prompt not found in prompts.yaml")?;
 
     let models_config = load_models_config().await?;
     let model_config = &models_config.default;
 
-    let result = provider.generate(&prompt, model_config).await?;
+    let param_count = available_params.len();
+    let prompt = budget::fit(
+        template,
+        vec![
+            PromptSection::fixed("sentence", sentence),
+            PromptSection::list("available_parameters", available_params, "\n")
+                .keep_at_least(param_count)
+                .shorten_items_to(200),
+            PromptSection::list("current_values", current, "\n")
+                .keep_at_least(current_count)
+                .shorten_items_to(200),
+        ],
+        provider.get_model_name(),
+        model_config,
+    )
+    .await?;
+
+    let result = provider
+        .generate(&prompt.prompt, &prompt.model_config)
+        .await?;
     let json_result = sanitize_json(&result.content)?;
 
-    let mut parameters = Vec::new();
+    let mut parameters = FollowupParameters {
+        usage: result.usage.clone(),
+        ..Default::default()
+    };
     let valid_param_names: Vec<&str> = endpoint_parameters
         .iter()
         .map(|p| p.name.as_str())
@@ -42,9 +89,17 @@ pub async fn extract_parameters_from_followup(
 
     if let Some(obj) = json_result.as_object() {
         for (key, value) in obj {
-            if let Some(str_value) = value.as_str() {
-                if !str_value.trim().is_empty() && valid_param_names.contains(&key.as_str()) {
-                    parameters.push(ParameterValue {
+            if !valid_param_names.contains(&key.as_str()) {
+                continue;
+            }
+            if value.is_null() {
+                // Only a value given earlier can be withdrawn
+                if current_values.iter().any(|p| &p.name == key) {
+                    parameters.removed.push(key.clone());
+                }
+            } else if let Some(str_value) = value.as_str() {
+                if !str_value.trim().is_empty() {
+                    parameters.values.push(ParameterValue {
                         name: key.clone(),
                         value: str_value.trim().to_string(),
                         description: format!("User provided value for {key}"),
@@ -1,16 +1,64 @@
+use crate::analysis::followup_intent::{classify_followup, FollowupIntent};
+use crate::analysis::missing_fields;
 use crate::analysis::parameter_extraction::extract_parameters_from_followup;
 use crate::analysis::response_builders::{
-    create_complete_progressive_response, create_partial_progressive_response,
+    create_cancelled_progressive_response, create_complete_progressive_response,
+    create_partial_progressive_response,
 };
 use crate::app_log;
 use crate::endpoint_client::get_enhanced_endpoints;
+use crate::metrics;
+use crate::models::config::load_progressive_matching_config;
 use crate::models::providers::ModelProvider;
-use crate::models::EnhancedAnalysisResult;
+use crate::models::{EnhancedAnalysisResult, EnhancedEndpoint, ParameterChange};
 use crate::progressive_matching::{OngoingMatch, ProgressiveMatchingManager};
 use std::error::Error;
 use std::sync::Arc;
 
-// Dedicated progressive matching handler
+fn required_parameter_names(endpoint: &EnhancedEndpoint) -> Vec<String> {
+    endpoint
+        .parameters
+        .iter()
+        .filter(|p| p.required.unwrap_or(false))
+        .map(|p| p.name.clone())
+        .collect()
+}
+
+/// The values a follow-up changed, in the request's language
+pub fn describe_changes(changes: &[ParameterChange]) -> String {
+    missing_fields::changes(&missing_fields::request_language(), changes)
+}
+
+/// Ask again for what a pending request still misses, after `preamble`
+async fn pending_response(
+    preamble: String,
+    conversation_id: &str,
+    endpoint: &EnhancedEndpoint,
+    progressive_manager: &ProgressiveMatchingManager,
+) -> Result<EnhancedAnalysisResult, Box<dyn Error + Send + Sync>> {
+    let completion_result = progressive_manager
+        .check_completion(
+            conversation_id,
+            &endpoint.id,
+            required_parameter_names(endpoint),
+            &endpoint.parameters,
+        )
+        .await?;
+    let mut response = create_partial_progressive_response(
+        endpoint,
+        completion_result,
+        &Some(conversation_id.to_string()),
+    )
+    .await?;
+    response.user_prompt = Some(match response.user_prompt {
+        Some(prompt) => format!("{preamble} {prompt}"),
+        None => preamble,
+    });
+    Ok(response)
+}
+
+/// Handle a message of a conversation with a pending request. `Ok(None)` means the
+/// message is not a follow-up (a switch to another request) and is analyzed normally.
 pub async fn handle_progressive_followup(
     sentence: &str,
     conversation_id: &str,
@@ -19,7 +67,7 @@ pub async fn handle_progressive_followup(
     progressive_manager: &ProgressiveMatchingManager,
     api_url: &str,
     email: &str,
-) -> Result<EnhancedAnalysisResult, Box<dyn Error + Send + Sync>> {
+) -> Result<Option<EnhancedAnalysisResult>, Box<dyn Error + Send + Sync>> {
     app_log!(
         info,
         "Processing progressive follow-up for endpoint: {}",
@@ -28,10 +76,19 @@ pub async fn handle_progressive_followup(
 
     // Get the endpoint definition to understand its parameters
     let enhanced_endpoints = get_enhanced_endpoints(api_url, email).await?;
-    let endpoint = enhanced_endpoints
-        .iter()
-        .find(|e| e.id == ongoing_match.endpoint_id)
-        .ok_or_else(|| format!("Endpoint {} not found", ongoing_match.endpoint_id))?;
+    let find_endpoint = |id: &str| enhanced_endpoints.iter().find(|e| e.id == id);
+    let Some(endpoint) = find_endpoint(&ongoing_match.endpoint_id) else {
+        // The endpoint is gone, so the request can never be completed
+        app_log!(
+            warn,
+            "Endpoint {} of the pending request no longer exists, dropping it",
+            ongoing_match.endpoint_id
+        );
+        progressive_manager
+            .abandon_match(conversation_id, &ongoing_match.endpoint_id, email)
+            .await?;
+        return Ok(None);
+    };
 
     app_log!(
         info,
@@ -40,41 +97,131 @@ pub async fn handle_progressive_followup(
         endpoint.parameters.len()
     );
 
-    // Extract new parameters from the follow-up message
-    let new_parameters =
-        extract_parameters_from_followup(sentence, provider.clone(), &endpoint.parameters).await?;
+    let required_param_names = required_parameter_names(endpoint);
+    let pending = progressive_manager
+        .pending_matches(conversation_id, email)
+        .await?;
+    let others: Vec<&EnhancedEndpoint> = pending
+        .iter()
+        .filter(|m| m.endpoint_id != endpoint.id)
+        .filter_map(|m| find_endpoint(&m.endpoint_id))
+        .collect();
+    let current = progressive_manager
+        .check_completion(
+            conversation_id,
+            &endpoint.id,
+            required_param_names.clone(),
+            &endpoint.parameters,
+        )
+        .await?;
+    let missing = current.missing_parameters;
+
+    let config = load_progressive_matching_config().await.unwrap_or_default();
+    let (intent, classification_usage) = classify_followup(
+        sentence,
+        endpoint,
+        &missing,
+        &others,
+        provider.clone(),
+        config.llm_topic_detection,
+    )
+    .await?;
+
+    match intent {
+        FollowupIntent::Continue => {}
+        FollowupIntent::Cancel => {
+            progressive_manager
+                .abandon_match(conversation_id, &endpoint.id, email)
+                .await?;
+            metrics::record_progressive_match("abandoned");
+            let mut response = match others.first() {
+                // The request below on the stack becomes the pending one again
+                Some(previous) => {
+                    let preamble = missing_fields::cancelled(
+                        &missing_fields::request_language(),
+                        &endpoint.name,
+                        Some(&previous.name),
+                    );
+                    pending_response(preamble, conversation_id, previous, progressive_manager)
+                        .await?
+                }
+                None => create_cancelled_progressive_response(
+                    endpoint,
+                    &Some(conversation_id.to_string()),
+                ),
+            };
+            response.add_usage(&classification_usage);
+            return Ok(Some(response));
+        }
+        FollowupIntent::Replace => {
+            progressive_manager
+                .abandon_match(conversation_id, &endpoint.id, email)
+                .await?;
+            metrics::record_progressive_match("abandoned");
+            return Ok(None);
+        }
+        FollowupIntent::Switch => {
+            // Kept below the new request, or on top if the new one completes at once
+            metrics::record_progressive_match("switched");
+            return Ok(None);
+        }
+        FollowupIntent::Resume(endpoint_id) => {
+            let resumed = find_endpoint(&endpoint_id).ok_or("Resumed endpoint not found")?;
+            progressive_manager
+                .resume_match(conversation_id, &endpoint_id, email)
+                .await?;
+            metrics::record_progressive_match("resumed");
+            let preamble =
+                missing_fields::resumed(&missing_fields::request_language(), &resumed.name);
+            let mut response =
+                pending_response(preamble, conversation_id, resumed, progressive_manager).await?;
+            response.add_usage(&classification_usage);
+            return Ok(Some(response));
+        }
+    }
+
+    // Extract new, corrected and withdrawn parameters from the follow-up message
+    let followup_parameters = extract_parameters_from_followup(
+        sentence,
+        provider.clone(),
+        &endpoint.parameters,
+        &current.matched_parameters,
+    )
+    .await?;
 
     app_log!(
         info,
-        "Extracted {} new parameters from follow-up",
-        new_parameters.len()
+        "Extracted {} parameters and {} removals from follow-up",
+        followup_parameters.values.len(),
+        followup_parameters.removed.len()
     );
 
-    if new_parameters.is_empty() {
-        return Err("No parameters could be extracted from the follow-up message".into());
+    if followup_parameters.is_empty() {
+        app_log!(
+            info,
+            "No parameters could be extracted from the follow-up message, analyzing it as a new request"
+        );
+        return Ok(None);
     }
+    let extraction_usage = followup_parameters.usage;
 
     // Update the progressive match with new parameters
-    progressive_manager
+    let changes = progressive_manager
         .update_match(
             conversation_id,
-            &ongoing_match.endpoint_id,
-            new_parameters.clone(),
+            &endpoint.id,
+            email,
+            followup_parameters.values,
+            &followup_parameters.removed,
+            sentence,
         )
         .await?;
 
     // Check if we're now complete
-    let required_param_names: Vec<String> = endpoint
-        .parameters
-        .iter()
-        .filter(|p| p.required.unwrap_or(false))
-        .map(|p| p.name.clone())
-        .collect();
-
     let completion_result = progressive_manager
         .check_completion(
             conversation_id,
-            &ongoing_match.endpoint_id,
+            &endpoint.id,
             required_param_names,
             &endpoint.parameters,
         )
@@ -87,29 +234,49 @@ pub async fn handle_progressive_followup(
         completion_result.is_complete
     );
 
-    if completion_result.is_complete {
+    let mut response = if completion_result.is_complete {
         // Clean up the progressive match
         progressive_manager
-            .complete_match(conversation_id, &ongoing_match.endpoint_id)
+            .complete_match(conversation_id, &endpoint.id)
             .await?;
 
+        metrics::record_progressive_match("completed");
         app_log!(info, "Progressive matching completed successfully");
-        create_complete_progressive_response(
+        let mut response = create_complete_progressive_response(
             endpoint,
             completion_result,
             &Some(conversation_id.to_string()),
         )
-        .await
+        .await?;
+        if let Some(previous) = others.first() {
+            response.user_prompt = Some(missing_fields::still_pending(
+                &missing_fields::request_language(),
+                &previous.name,
+            ));
+        }
+        response
     } else {
         app_log!(
             info,
             "Progressive matching still incomplete, prompting for more parameters"
         );
+        metrics::record_progressive_match("continued");
         create_partial_progressive_response(
             endpoint,
             completion_result,
             &Some(conversation_id.to_string()),
         )
-        .await
+        .await?
+    };
+    if !changes.is_empty() {
+        let summary = describe_changes(&changes);
+        response.user_prompt = Some(match response.user_prompt {
+            Some(prompt) => format!("{summary} {prompt}"),
+            None => summary,
+        });
+        response.changed_parameters = changes;
     }
+    response.add_usage(&classification_usage);
+    response.add_usage(&extraction_usage);
+    Ok(Some(response))
 }
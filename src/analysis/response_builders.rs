@@ -1,8 +1,12 @@
+use crate::analysis::missing_fields;
 use crate::general_question_handler::handle_general_question;
 use crate::help_response_handler::handle_help_request;
 use crate::models::providers::ModelProvider;
+use crate::models::{
+    Confirmation, ConfirmationStatus, MatchingInfo, MatchingStatus, MissingField, ParameterMatch,
+    RiskLevel, UsageInfo,
+};
 use crate::models::{EnhancedAnalysisResult, EnhancedEndpoint};
-use crate::models::{MatchingInfo, MatchingStatus, MissingField, ParameterMatch, UsageInfo};
 use crate::progressive_matching::ProgressiveMatchResult;
 use crate::utils::path_params::add_path_parameters_to_list;
 use crate::workflow::classify_intent::IntentType;
@@ -22,6 +26,7 @@ pub async fn create_complete_progressive_response(
             name: param.name,
             description: param.description,
             value: Some(param.value),
+            default_source: None,
         })
         .collect();
 
@@ -61,6 +66,9 @@ pub async fn create_complete_progressive_response(
         total_output_tokens: usage_info.output_tokens,
         usage: usage_info,
         intent: IntentType::ActionableRequest,
+        changed_parameters: vec![],
+        risk_level: endpoint.risk_level,
+        confirmation: None,
     })
 }
 
@@ -76,6 +84,7 @@ pub async fn create_partial_progressive_response(
             name: param.name,
             description: param.description,
             value: Some(param.value),
+            default_source: None,
         })
         .collect();
 
@@ -85,9 +94,17 @@ pub async fn create_partial_progressive_response(
     let missing_fields: Vec<MissingField> = result
         .missing_parameters
         .iter()
-        .map(|param| MissingField {
-            name: param.clone(),
-            description: format!("Missing required parameter: {param}"),
+        .map(|param| {
+            let definition = all_endpoint_parameters.iter().find(|p| p.name == *param);
+            MissingField {
+                name: param.clone(),
+                description: definition
+                    .map(|p| p.description.clone())
+                    .unwrap_or_else(|| format!("Missing required parameter: {param}")),
+                examples: definition
+                    .and_then(|p| p.examples.clone())
+                    .unwrap_or_default(),
+            }
         })
         .collect();
 
@@ -102,7 +119,7 @@ pub async fn create_partial_progressive_response(
         missing_optional_fields: vec![],
     };
 
-    let user_prompt = generate_missing_fields_prompt(&result.missing_parameters);
+    let user_prompt = generate_missing_fields_prompt(&matching_info.missing_required_fields);
 
     let usage_info = UsageInfo {
         input_tokens: 30,
@@ -137,36 +154,127 @@ pub async fn create_partial_progressive_response(
         total_output_tokens: usage_info.output_tokens,
         usage: usage_info,
         intent: IntentType::ActionableRequest,
+        changed_parameters: vec![],
+        risk_level: endpoint.risk_level,
+        confirmation: None,
     })
 }
 
-pub fn generate_missing_fields_prompt(missing_params: &[String]) -> String {
-    match missing_params.len() {
-        0 => "All required information has been provided.".to_string(),
-        1 => format!(
-            "I need one more piece of information: {}. Could you please provide it?",
-            missing_params[0].replace('_', " ")
-        ),
-        2 => format!(
-            "I need two more pieces of information: {} and {}. Could you provide them?",
-            missing_params[0].replace('_', " "),
-            missing_params[1].replace('_', " ")
-        ),
-        _ => {
-            let (initial, last) = missing_params.split_at(missing_params.len() - 1);
-            format!(
-                "I need a few more details: {}, and {}. Could you provide this information?",
-                initial
-                    .iter()
-                    .map(|p| p.replace('_', " "))
-                    .collect::<Vec<_>>()
-                    .join(", "),
-                last[0].replace('_', " ")
-            )
-        }
+/// Acknowledge a cancelled request when no other request is pending
+pub fn create_cancelled_progressive_response(
+    endpoint: &EnhancedEndpoint,
+    conversation_id: &Option<String>,
+) -> EnhancedAnalysisResult {
+    let message =
+        missing_fields::cancelled(&missing_fields::request_language(), &endpoint.name, None);
+    let usage_info = UsageInfo {
+        input_tokens: 0,
+        output_tokens: 0,
+        total_tokens: 0,
+        model: "progressive_matching".to_string(),
+        estimated: true,
+    };
+
+    EnhancedAnalysisResult {
+        endpoint_id: "request_cancelled".to_string(),
+        endpoint_name: "Request Cancelled".to_string(),
+        endpoint_description: "The pending request was dropped at the user's request".to_string(),
+        verb: "GET".to_string(),
+        base: "conversation".to_string(),
+        path: "/general".to_string(),
+        essential_path: "/general".to_string(),
+        api_group_id: "conversation".to_string(),
+        api_group_name: "Conversation API".to_string(),
+        parameters: vec![],
+        raw_json: serde_json::json!({
+            "type": "progressive_cancelled",
+            "endpoint_id": endpoint.id,
+            "response": message
+        }),
+        conversation_id: conversation_id.clone(),
+        matching_info: MatchingInfo {
+            status: MatchingStatus::Complete,
+            total_required_fields: 0,
+            mapped_required_fields: 0,
+            total_optional_fields: 0,
+            mapped_optional_fields: 0,
+            completion_percentage: 100.0,
+            missing_required_fields: vec![],
+            missing_optional_fields: vec![],
+        },
+        user_prompt: Some(message),
+        total_input_tokens: 0,
+        total_output_tokens: 0,
+        usage: usage_info,
+        intent: IntentType::GeneralQuestion,
+        changed_parameters: vec![],
+        risk_level: RiskLevel::Low,
+        confirmation: None,
     }
 }
 
+/// Acknowledge a request the user declined to confirm
+pub fn create_declined_confirmation_response(
+    pending: &EnhancedAnalysisResult,
+    summary: String,
+) -> EnhancedAnalysisResult {
+    let message =
+        missing_fields::declined(&missing_fields::request_language(), &pending.endpoint_name);
+    let usage_info = UsageInfo {
+        input_tokens: 0,
+        output_tokens: 0,
+        total_tokens: 0,
+        model: "confirmation".to_string(),
+        estimated: true,
+    };
+
+    EnhancedAnalysisResult {
+        endpoint_id: "request_declined".to_string(),
+        endpoint_name: "Request Declined".to_string(),
+        endpoint_description: "The user did not confirm the request".to_string(),
+        verb: "GET".to_string(),
+        base: "conversation".to_string(),
+        path: "/general".to_string(),
+        essential_path: "/general".to_string(),
+        api_group_id: "conversation".to_string(),
+        api_group_name: "Conversation API".to_string(),
+        parameters: vec![],
+        raw_json: serde_json::json!({
+            "type": "confirmation_declined",
+            "endpoint_id": pending.endpoint_id,
+            "response": message
+        }),
+        conversation_id: pending.conversation_id.clone(),
+        matching_info: MatchingInfo {
+            status: MatchingStatus::Complete,
+            total_required_fields: 0,
+            mapped_required_fields: 0,
+            total_optional_fields: 0,
+            mapped_optional_fields: 0,
+            completion_percentage: 100.0,
+            missing_required_fields: vec![],
+            missing_optional_fields: vec![],
+        },
+        user_prompt: Some(message),
+        total_input_tokens: 0,
+        total_output_tokens: 0,
+        usage: usage_info,
+        intent: IntentType::GeneralQuestion,
+        changed_parameters: vec![],
+        risk_level: RiskLevel::Low,
+        confirmation: Some(Confirmation {
+            status: ConfirmationStatus::Declined,
+            summary,
+        }),
+    }
+}
+
+/// Ask for `fields` in the request's language
+pub fn generate_missing_fields_prompt(fields: &[MissingField]) -> String {
+    missing_fields::question(&missing_fields::request_language(), None, fields)
+        .unwrap_or_else(|| "All required information has been provided.".to_string())
+}
+
 pub async fn create_fallback_response(
     sentence: &str,
     provider: Arc<dyn ModelProvider>,
@@ -219,6 +327,9 @@ pub async fn create_fallback_response(
         total_output_tokens: conversational_result.usage.output_tokens,
         usage: usage_info,
         intent: IntentType::GeneralQuestion,
+        changed_parameters: vec![],
+        risk_level: RiskLevel::Low,
+        confirmation: None,
     })
 }
 
@@ -274,6 +385,9 @@ pub async fn create_help_response(
         total_output_tokens: usage_info.output_tokens,
         usage: usage_info,
         intent: IntentType::HelpRequest,
+        changed_parameters: vec![],
+        risk_level: RiskLevel::Low,
+        confirmation: None,
     })
 }
 
@@ -327,5 +441,8 @@ pub async fn create_general_response(
         total_output_tokens: usage_info.output_tokens,
         usage: usage_info,
         intent: IntentType::GeneralQuestion,
+        changed_parameters: vec![],
+        risk_level: RiskLevel::Low,
+        confirmation: None,
     })
 }
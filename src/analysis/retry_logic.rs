@@ -1,16 +1,16 @@
 use crate::app_log;
+use crate::auth::tenant_from_email;
+use crate::error::SemanticError;
+use crate::metrics;
+use crate::models::config::load_workflows_config;
+use crate::models::providers::token_counter::TokenUsage;
 use crate::models::providers::ModelProvider;
 use crate::models::EnhancedAnalysisResult;
 use crate::models::{MatchingInfo, ParameterMatch, UsageInfo};
-use crate::utils::token_calculator::EnhancedTokenCalculator;
+use crate::parameter_memory::ParameterDefaults;
+use crate::usage;
 use crate::workflow::classify_intent::IntentType;
-use crate::workflow::steps::endpoint_matching::EndpointMatchingStep;
-use crate::workflow::steps::enhanced_config_loading::EnhancedConfigurationLoadingStep;
-use crate::workflow::steps::field_matching::FieldMatchingStep;
-use crate::workflow::steps::json_generation::JsonGenerationStep;
-use crate::workflow::steps::path_parameter_extraction::PathParameterExtractionStep;
-use crate::workflow::{WorkflowConfig, WorkflowEngine};
-use std::error::Error;
+use crate::workflow::registry::{StepBuildContext, StepRegistry};
 use std::sync::Arc;
 
 // Retry logic for actionable analysis
@@ -21,10 +21,14 @@ pub async fn analyze_with_retry(
     email: &str,
     conversation_id: Option<String>,
     retry_attempts: u32,
-) -> Result<EnhancedAnalysisResult, Box<dyn Error + Send + Sync>> {
+    defaults: &ParameterDefaults,
+) -> Result<EnhancedAnalysisResult, SemanticError> {
     let mut last_error = None;
+    // Failed attempts were paid for as well, so their calls count toward the result
+    let mut failed_usage = TokenUsage::default();
 
     for attempt in 1..=retry_attempts {
+        let before = usage::request_usage();
         app_log!(
             info,
             "Analysis attempt {}/{} for: {}",
@@ -39,36 +43,33 @@ pub async fn analyze_with_retry(
             api_url.clone(),
             email,
             conversation_id.clone(),
+            defaults,
         )
         .await
         {
-            Ok(result) => {
+            Ok(mut result) => {
                 app_log!(info, "Analysis succeeded on attempt {}", attempt);
+                result.add_usage(&failed_usage);
                 return Ok(result);
             }
-            Err(e) => {
-                let error_msg = e.to_string();
-                if error_msg.contains("No suitable endpoint found")
-                    || error_msg.contains("not found in available endpoints")
-                {
-                    app_log!(
-                        warn,
-                        "Endpoint matching failed on attempt {}: {}",
-                        attempt,
-                        error_msg
-                    );
-                    last_error = Some(e);
+            Err(e) if e.is_match_failure() => {
+                app_log!(
+                    warn,
+                    "Endpoint matching failed on attempt {}: {}",
+                    attempt,
+                    e
+                );
+                last_error = Some(e);
+                failed_usage.add(&usage::request_usage().since(&before));
 
-                    if attempt < retry_attempts {
-                        // Add small delay between retries
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                        continue;
-                    }
-                } else {
-                    // For other errors, don't retry
-                    return Err(e);
+                if attempt < retry_attempts {
+                    metrics::record_analysis_retry("endpoint_matching");
+                    // Add small delay between retries
+                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                 }
             }
+            // For other errors, don't retry
+            Err(e) => return Err(e),
         }
     }
 
@@ -83,74 +84,23 @@ async fn try_actionable_analysis(
     api_url: Option<String>,
     email: &str,
     conversation_id: Option<String>,
-) -> Result<EnhancedAnalysisResult, Box<dyn Error + Send + Sync>> {
-    // Run the full workflow for actionable requests
-    const ENHANCED_WORKFLOW_CONFIG: &str = r#"
-steps:
-  - name: enhanced_configuration_loading
-    enabled: true
-    retry:
-      max_attempts: 3
-      delay_ms: 1000
-  - name: endpoint_matching  # Do endpoint matching FIRST
-    enabled: true
-    retry:
-      max_attempts: 2
-      delay_ms: 500
-  - name: path_parameter_extraction  # NEW: Extract path parameters
-    enabled: true
-    retry:
-      max_attempts: 1
-      delay_ms: 0
-  - name: json_generation    # Then extract parameters for the specific endpoint
-    enabled: true
-    retry:
-      max_attempts: 3
-      delay_ms: 1000
-  - name: field_matching     # Finally do field matching as cleanup
-    enabled: true
-    retry:
-      max_attempts: 2
-      delay_ms: 500
-"#;
-
-    let config: WorkflowConfig = serde_yaml::from_str(ENHANCED_WORKFLOW_CONFIG)?;
-    let mut engine = WorkflowEngine::new();
-
-    // Register all workflow steps
-    for step_config in config.steps {
-        match step_config.name.as_str() {
-            "enhanced_configuration_loading" => {
-                engine.register_step(
-                    step_config,
-                    Arc::new(EnhancedConfigurationLoadingStep {
-                        api_url: api_url.clone(),
-                        email: email.to_string(),
-                    }),
-                );
-            }
-            "path_parameter_extraction" => {
-                // NEW
-                engine.register_step(step_config, Arc::new(PathParameterExtractionStep));
-            }
-            "json_generation" => {
-                engine.register_step(step_config, Arc::new(JsonGenerationStep));
-            }
-            "endpoint_matching" => {
-                engine.register_step(
-                    step_config,
-                    Arc::new(EndpointMatchingStep), // Uses the updated implementation
-                );
-            }
-            "field_matching" => {
-                engine.register_step(step_config, Arc::new(FieldMatchingStep));
-            }
-            _ => {
-                app_log!(error, "Unknown step: {}", step_config.name);
-                return Err(format!("Unknown step: {}", step_config.name).into());
-            }
-        }
-    }
+    defaults: &ParameterDefaults,
+) -> Result<EnhancedAnalysisResult, SemanticError> {
+    // The workflow definition comes from config.yaml, so steps can be added, reordered
+    // or disabled without recompiling
+    let workflows = load_workflows_config().await?;
+    // Prefer the tenant of the caller's verified credentials over the email domain
+    let tenant = usage::current_scope()
+        .and_then(|scope| scope.tenant)
+        .unwrap_or_else(|| tenant_from_email(email));
+    let workflow = workflows.resolve(Some(&tenant), "actionable_request");
+    let engine = StepRegistry::with_builtin_steps().build(
+        workflow,
+        &StepBuildContext {
+            api_url: api_url.clone(),
+            email: email.to_string(),
+        },
+    )?;
 
     // Execute the workflow
     let context = engine
@@ -169,7 +119,7 @@ steps:
         .ok_or("Enhanced endpoint data not found")?;
 
     // Build parameter matches from workflow results
-    let parameter_matches: Vec<ParameterMatch> = context
+    let mut parameter_matches: Vec<ParameterMatch> = context
         .parameters
         .clone()
         .into_iter()
@@ -177,94 +127,39 @@ steps:
             name: param.name,
             description: param.description,
             value: param.semantic_value,
+            default_source: None,
         })
         .collect();
-    let matching_info = MatchingInfo::compute(&parameter_matches, &context.parameters);
+    // Missing required values the user gave before are filled in instead of asked for
+    let matching_info = MatchingInfo::compute_with_defaults(
+        &mut parameter_matches,
+        &context.parameters,
+        enhanced_endpoint.risk_level,
+        defaults,
+    );
 
     // let matching_info = MatchingInfo::compute(&parameter_matches, &enhanced_endpoint.parameters);
     let user_prompt = matching_info.generate_user_prompt(&enhanced_endpoint.name);
 
-    // If workflow didn't track tokens properly, estimate them based on the sentence and response
-    let (final_input_tokens, final_output_tokens) = if context.total_output_tokens == 0 {
-        app_log!(
-            debug,
-            "Workflow reported 0 output tokens, estimating output tokens"
-        );
-
-        let enhanced_calculator = EnhancedTokenCalculator::new();
-
-        // Use existing input tokens if available, otherwise estimate
-        let estimated_input = if context.total_input_tokens > 0 {
-            context.total_input_tokens
-        } else {
-            let sentence_tokens = enhanced_calculator.estimate_tokens_enhanced(
-                sentence,
-                provider.get_model_name(),
-                None,
-            );
-            sentence_tokens * 3 // Used in ~3 different LLM calls
-        };
-
-        // Estimate output tokens based on all the content generated by the workflow
-        let mut total_output_content = String::new();
-
-        // Add JSON output content
-        if let Some(json_output) = &context.json_output {
-            let json_str = serde_json::to_string(json_output).unwrap_or_default();
-            total_output_content.push_str(&json_str);
-            total_output_content.push(' ');
-        }
-
-        // Add endpoint matching result (endpoint ID and description)
-        if let Some(endpoint_id) = &context.endpoint_id {
-            total_output_content.push_str(endpoint_id);
-            total_output_content.push(' ');
-        }
-        if let Some(desc) = &context.endpoint_description {
-            total_output_content.push_str(desc);
-            total_output_content.push(' ');
-        }
-
-        // Add parameter processing results
-        for param in &parameter_matches {
-            total_output_content.push_str(&param.name);
-            total_output_content.push(' ');
-            if let Some(value) = &param.value {
-                total_output_content.push_str(value);
-                total_output_content.push(' ');
-            }
-        }
-
-        // Add estimated tokens for LLM reasoning/processing overhead
-        let sentence_tokens =
-            enhanced_calculator.estimate_tokens_enhanced(sentence, provider.get_model_name(), None);
-        let reasoning_overhead = sentence_tokens * 2; // Assume 2x input tokens for reasoning
-
-        let content_tokens = enhanced_calculator.estimate_tokens_enhanced(
-            &total_output_content,
-            provider.get_model_name(),
-            None,
-        );
-        let estimated_output = content_tokens + reasoning_overhead;
-
-        app_log!(debug, "Output estimation breakdown: content='{}' ({} tokens), reasoning overhead ({} tokens), total output: {}", 
-               total_output_content.chars().take(100).collect::<String>(),
-               content_tokens, reasoning_overhead, estimated_output);
-
-        (estimated_input, estimated_output)
-    } else {
-        (context.total_input_tokens, context.total_output_tokens)
-    };
-
-    // Create usage info from final token counts
+    // Every step accounts the usage its provider reported (or estimated, when none)
     let usage_info = UsageInfo {
-        input_tokens: final_input_tokens,
-        output_tokens: final_output_tokens,
-        total_tokens: final_input_tokens + final_output_tokens,
+        input_tokens: context.usage.input_tokens,
+        output_tokens: context.usage.output_tokens,
+        total_tokens: context.usage.total_tokens,
         model: provider.get_model_name().to_string(),
-        estimated: true, // Workflow aggregates multiple calls, so mark as estimated
+        estimated: context.usage.estimated,
     };
 
+    for (step, usage) in &context.step_usage {
+        app_log!(
+            debug,
+            "Step {} token usage: input={}, output={}, estimated={}",
+            step,
+            usage.input_tokens,
+            usage.output_tokens,
+            usage.estimated
+        );
+    }
     app_log!(
         debug,
         "Final workflow token usage: input={}, output={}, total={}",
@@ -289,9 +184,12 @@ steps:
         raw_json: context.json_output.ok_or("JSON output not available")?,
         matching_info,
         user_prompt,
-        total_input_tokens: final_input_tokens,
-        total_output_tokens: final_output_tokens,
+        total_input_tokens: usage_info.input_tokens,
+        total_output_tokens: usage_info.output_tokens,
         usage: usage_info,
         intent: IntentType::ActionableRequest,
+        changed_parameters: vec![],
+        risk_level: enhanced_endpoint.risk_level,
+        confirmation: None,
     })
 }
@@ -0,0 +1,279 @@
+// src/auth/jwt.rs
+use crate::app_log;
+use crate::models::config::JwtConfig;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+use std::env;
+use std::error::Error;
+
+/// Verified JWT claims relevant to identity
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedClaims {
+    pub email: String,
+    pub tenant: Option<String>,
+}
+
+/// RS256 keys with their optional key id (`kid`)
+type KeyedDecodingKeys = Vec<(Option<String>, DecodingKey)>;
+
+#[derive(Clone)]
+pub struct JwtVerifier {
+    hs256_key: Option<DecodingKey>,
+    rs256_keys: KeyedDecodingKeys,
+    issuer: Option<String>,
+    audience: Option<String>,
+    email_claim: String,
+    tenant_claim: String,
+    leeway_secs: u64,
+}
+
+impl JwtVerifier {
+    pub fn from_config(config: &JwtConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let hs256_key = match &config.hs256_secret_env {
+            Some(var) => {
+                let secret = env::var(var).map_err(|_| {
+                    format!("{var} environment variable not found for HS256 secret")
+                })?;
+                if secret.is_empty() {
+                    return Err(format!("{var} is empty, refusing an empty HS256 secret").into());
+                }
+                Some(DecodingKey::from_secret(secret.as_bytes()))
+            }
+            None => None,
+        };
+
+        let rs256_keys = match &config.jwks_path {
+            Some(path) => {
+                let jwks_str = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read JWKS file {path}: {e}"))?;
+                Self::load_jwks(&jwks_str)?
+            }
+            None => Vec::new(),
+        };
+
+        if hs256_key.is_none() && rs256_keys.is_empty() {
+            return Err("JWT auth configured without an HS256 secret or a JWKS file".into());
+        }
+
+        app_log!(
+            info,
+            "JWT verifier ready: hs256={}, rs256 keys={}",
+            hs256_key.is_some(),
+            rs256_keys.len()
+        );
+
+        Ok(Self {
+            hs256_key,
+            rs256_keys,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            email_claim: config.email_claim.clone(),
+            tenant_claim: config.tenant_claim.clone(),
+            leeway_secs: config.leeway_secs,
+        })
+    }
+
+    fn load_jwks(jwks_str: &str) -> Result<KeyedDecodingKeys, Box<dyn Error + Send + Sync>> {
+        let jwks: JwkSet = serde_json::from_str(jwks_str)?;
+        let mut keys = Vec::new();
+
+        for jwk in &jwks.keys {
+            match DecodingKey::from_jwk(jwk) {
+                Ok(key) => keys.push((jwk.common.key_id.clone(), key)),
+                Err(e) => {
+                    app_log!(warn, "Skipping unusable JWK {:?}: {}", jwk.common.key_id, e);
+                }
+            }
+        }
+
+        if keys.is_empty() {
+            return Err("JWKS file contains no usable keys".into());
+        }
+
+        Ok(keys)
+    }
+
+    pub fn verify(&self, token: &str) -> Result<VerifiedClaims, Box<dyn Error + Send + Sync>> {
+        let header = decode_header(token)?;
+
+        let key = match header.alg {
+            Algorithm::HS256 => self
+                .hs256_key
+                .as_ref()
+                .ok_or("HS256 tokens are not accepted")?,
+            Algorithm::RS256 => self.select_rs256_key(header.kid.as_deref())?,
+            other => return Err(format!("Unsupported JWT algorithm: {other:?}").into()),
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.leeway_secs;
+        match &self.issuer {
+            Some(issuer) => validation.set_issuer(&[issuer]),
+            None => validation.iss = None,
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let token_data = decode::<Map<String, Value>>(token, key, &validation)?;
+        let claims = token_data.claims;
+
+        let email = claims
+            .get(&self.email_claim)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("Token has no '{}' claim", self.email_claim))?
+            .to_string();
+
+        let tenant = claims
+            .get(&self.tenant_claim)
+            .and_then(|v| v.as_str())
+            .map(|t| t.to_string());
+
+        Ok(VerifiedClaims { email, tenant })
+    }
+
+    fn select_rs256_key(
+        &self,
+        kid: Option<&str>,
+    ) -> Result<&DecodingKey, Box<dyn Error + Send + Sync>> {
+        if self.rs256_keys.is_empty() {
+            return Err("RS256 tokens are not accepted".into());
+        }
+
+        match kid {
+            Some(kid) => self
+                .rs256_keys
+                .iter()
+                .find(|(key_id, _)| key_id.as_deref() == Some(kid))
+                .map(|(_, key)| key)
+                .ok_or_else(|| format!("Unknown JWT key id: {kid}").into()),
+            // Without a kid we only accept a single unambiguous key
+            None if self.rs256_keys.len() == 1 => Ok(&self.rs256_keys[0].1),
+            None => Err("JWT has no key id and the JWKS holds several keys".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    pub const TEST_SECRET: &str = "test-hs256-secret";
+    pub const TEST_SECRET_ENV: &str = "SEMANTIC_TEST_JWT_SECRET";
+    pub const JWKS_PATH: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/test/fixtures/auth/jwks.json");
+    const RS256_PRIVATE_KEY: &str = include_str!("../../test/fixtures/auth/rs256_private.pem");
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+
+    pub fn mint_hs256(claims: Value) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(TEST_SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    pub fn mint_rs256(claims: Value, kid: Option<&str>) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = kid.map(|k| k.to_string());
+        encode(
+            &header,
+            &claims,
+            &EncodingKey::from_rsa_pem(RS256_PRIVATE_KEY.as_bytes()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    pub fn test_config() -> JwtConfig {
+        env::set_var(TEST_SECRET_ENV, TEST_SECRET);
+        JwtConfig {
+            hs256_secret_env: Some(TEST_SECRET_ENV.to_string()),
+            jwks_path: Some(JWKS_PATH.to_string()),
+            issuer: Some("https://auth.example.com".to_string()),
+            audience: Some("semantic".to_string()),
+            email_claim: "email".to_string(),
+            tenant_claim: "tenant".to_string(),
+            leeway_secs: 0,
+        }
+    }
+
+    pub fn valid_claims(email: &str) -> Value {
+        json!({
+            "email": email,
+            "tenant": "acme",
+            "iss": "https://auth.example.com",
+            "aud": "semantic",
+            "exp": now() + 300,
+        })
+    }
+
+    #[test]
+    fn test_hs256_token_verified() {
+        let verifier = JwtVerifier::from_config(&test_config()).unwrap();
+        let token = mint_hs256(valid_claims("alice@example.com"));
+
+        let claims = verifier.verify(&token).unwrap();
+        assert_eq!(claims.email, "alice@example.com");
+        assert_eq!(claims.tenant.as_deref(), Some("acme"));
+    }
+
+    #[test]
+    fn test_rs256_token_verified_with_local_jwks() {
+        let verifier = JwtVerifier::from_config(&test_config()).unwrap();
+        let token = mint_rs256(valid_claims("bob@example.com"), Some("test-key-1"));
+
+        let claims = verifier.verify(&token).unwrap();
+        assert_eq!(claims.email, "bob@example.com");
+    }
+
+    #[test]
+    fn test_rs256_unknown_kid_rejected() {
+        let verifier = JwtVerifier::from_config(&test_config()).unwrap();
+        let token = mint_rs256(valid_claims("bob@example.com"), Some("other-key"));
+
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn test_expired_token_rejected() {
+        let verifier = JwtVerifier::from_config(&test_config()).unwrap();
+        let mut claims = valid_claims("alice@example.com");
+        claims["exp"] = json!(now() - 10);
+
+        assert!(verifier.verify(&mint_hs256(claims)).is_err());
+    }
+
+    #[test]
+    fn test_wrong_secret_and_audience_rejected() {
+        let verifier = JwtVerifier::from_config(&test_config()).unwrap();
+
+        let forged = encode(
+            &Header::new(Algorithm::HS256),
+            &valid_claims("alice@example.com"),
+            &EncodingKey::from_secret(b"not-the-secret"),
+        )
+        .unwrap();
+        assert!(verifier.verify(&forged).is_err());
+
+        let mut claims = valid_claims("alice@example.com");
+        claims["aud"] = json!("another-service");
+        assert!(verifier.verify(&mint_hs256(claims)).is_err());
+    }
+
+    #[test]
+    fn test_missing_email_claim_rejected() {
+        let verifier = JwtVerifier::from_config(&test_config()).unwrap();
+        let mut claims = valid_claims("alice@example.com");
+        claims.as_object_mut().unwrap().remove("email");
+
+        assert!(verifier.verify(&mint_hs256(claims)).is_err());
+    }
+}
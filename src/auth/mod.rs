@@ -0,0 +1,317 @@
+// src/auth/mod.rs - Caller authentication for the gRPC API
+pub mod jwt;
+
+use crate::app_log;
+use crate::models::config::AuthConfig;
+use crate::utils::email::validate_email;
+use jwt::JwtVerifier;
+use std::env;
+use std::error::Error;
+use std::sync::Arc;
+use tonic::metadata::MetadataMap;
+use tonic::{Request, Status};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthMethod {
+    Jwt,
+    ApiKey,
+}
+
+/// Identity derived from verified credentials, attached to request extensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthenticatedIdentity {
+    pub email: String,
+    pub tenant: String,
+    pub method: AuthMethod,
+}
+
+#[derive(Clone)]
+struct ApiKeyIdentity {
+    key: String,
+    email: String,
+    tenant: String,
+}
+
+#[derive(Clone)]
+pub struct Authenticator {
+    enabled: bool,
+    jwt: Option<JwtVerifier>,
+    api_keys: Arc<Vec<ApiKeyIdentity>>,
+}
+
+impl Authenticator {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            jwt: None,
+            api_keys: Arc::new(Vec::new()),
+        }
+    }
+
+    pub fn from_config(config: &AuthConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if !config.enabled {
+            app_log!(
+                warn,
+                "Authentication is disabled: caller identity comes from the unauthenticated 'email' header"
+            );
+            return Ok(Self::disabled());
+        }
+
+        let jwt = match &config.jwt {
+            Some(jwt_config) => Some(JwtVerifier::from_config(jwt_config)?),
+            None => None,
+        };
+
+        let mut api_keys = Vec::new();
+        for key_config in &config.api_keys {
+            let key = env::var(&key_config.key_env).map_err(|_| {
+                format!(
+                    "{} environment variable not found for API key",
+                    key_config.key_env
+                )
+            })?;
+            if key.is_empty() {
+                return Err(
+                    format!("{} is empty, refusing an empty API key", key_config.key_env).into(),
+                );
+            }
+            validate_email(&key_config.email)?;
+            api_keys.push(ApiKeyIdentity {
+                key,
+                email: key_config.email.clone(),
+                tenant: key_config
+                    .tenant
+                    .clone()
+                    .unwrap_or_else(|| tenant_from_email(&key_config.email)),
+            });
+        }
+
+        if jwt.is_none() && api_keys.is_empty() {
+            return Err(
+                "Authentication enabled but neither JWT nor API keys are configured".into(),
+            );
+        }
+
+        app_log!(
+            info,
+            "Authentication enabled: jwt={}, api_keys={}",
+            jwt.is_some(),
+            api_keys.len()
+        );
+
+        Ok(Self {
+            enabled: true,
+            jwt,
+            api_keys: Arc::new(api_keys),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Resolve the caller identity from request metadata.
+    /// Returns `Ok(None)` when authentication is disabled.
+    pub fn authenticate(
+        &self,
+        metadata: &MetadataMap,
+    ) -> Result<Option<AuthenticatedIdentity>, Status> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        let identity = if let Some(token) = bearer_token(metadata) {
+            let verifier = self
+                .jwt
+                .as_ref()
+                .ok_or_else(|| Status::unauthenticated("Bearer tokens are not accepted"))?;
+            let claims = verifier.verify(token).map_err(|e| {
+                app_log!(warn, "JWT verification failed: {}", e);
+                Status::unauthenticated("Invalid or expired token")
+            })?;
+            validate_email(&claims.email)
+                .map_err(|_| Status::unauthenticated("Token email claim is not a valid email"))?;
+
+            AuthenticatedIdentity {
+                tenant: claims
+                    .tenant
+                    .unwrap_or_else(|| tenant_from_email(&claims.email)),
+                email: claims.email,
+                method: AuthMethod::Jwt,
+            }
+        } else if let Some(key) = metadata.get("x-api-key").and_then(|v| v.to_str().ok()) {
+            let entry = self
+                .api_keys
+                .iter()
+                .find(|entry| constant_time_eq(entry.key.as_bytes(), key.as_bytes()))
+                .ok_or_else(|| {
+                    app_log!(warn, "Rejected unknown API key");
+                    Status::unauthenticated("Invalid API key")
+                })?;
+
+            AuthenticatedIdentity {
+                email: entry.email.clone(),
+                tenant: entry.tenant.clone(),
+                method: AuthMethod::ApiKey,
+            }
+        } else {
+            return Err(Status::unauthenticated(
+                "Missing credentials. Send 'authorization: Bearer <token>' or 'x-api-key' metadata.",
+            ));
+        };
+
+        // The legacy email header may still be sent, but it cannot override the verified identity
+        if let Some(header_email) = metadata.get("email").and_then(|v| v.to_str().ok()) {
+            if !header_email.eq_ignore_ascii_case(&identity.email) {
+                app_log!(
+                    warn,
+                    "Email header '{}' does not match authenticated identity '{}'",
+                    header_email,
+                    identity.email
+                );
+                return Err(Status::permission_denied(
+                    "Email header does not match the authenticated identity",
+                ));
+            }
+        }
+
+        Ok(Some(identity))
+    }
+
+    /// Build a tonic interceptor that stores the verified identity in request extensions
+    pub fn interceptor(
+        self,
+    ) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone + Send + Sync + 'static
+    {
+        move |mut request: Request<()>| {
+            if let Some(identity) = self.authenticate(request.metadata())? {
+                request.extensions_mut().insert(identity);
+            }
+            Ok(request)
+        }
+    }
+}
+
+fn bearer_token(metadata: &MetadataMap) -> Option<&str> {
+    let value = metadata.get("authorization")?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() {
+        Some(token.trim())
+    } else {
+        None
+    }
+}
+
+/// Default tenant when credentials carry none: the email domain
+pub fn tenant_from_email(email: &str) -> String {
+    email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.to_lowercase())
+        .unwrap_or_default()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::jwt::tests::{mint_hs256, mint_rs256, test_config, valid_claims};
+    use super::*;
+    use crate::models::config::ApiKeyConfig;
+
+    const API_KEY_ENV: &str = "SEMANTIC_TEST_API_KEY";
+
+    fn authenticator() -> Authenticator {
+        env::set_var(API_KEY_ENV, "partner-secret-key");
+        Authenticator::from_config(&AuthConfig {
+            enabled: true,
+            jwt: Some(test_config()),
+            api_keys: vec![ApiKeyConfig {
+                key_env: API_KEY_ENV.to_string(),
+                email: "partner@example.org".to_string(),
+                tenant: None,
+            }],
+        })
+        .unwrap()
+    }
+
+    fn metadata(entries: &[(&'static str, &str)]) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        for (key, value) in entries {
+            metadata.insert(*key, value.parse().unwrap());
+        }
+        metadata
+    }
+
+    #[test]
+    fn test_jwt_identity_from_claims() {
+        let token = mint_rs256(valid_claims("alice@example.com"), Some("test-key-1"));
+        let bearer = format!("Bearer {token}");
+        let identity = authenticator()
+            .authenticate(&metadata(&[("authorization", &bearer)]))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(identity.email, "alice@example.com");
+        assert_eq!(identity.tenant, "acme");
+        assert_eq!(identity.method, AuthMethod::Jwt);
+    }
+
+    #[test]
+    fn test_api_key_identity() {
+        let identity = authenticator()
+            .authenticate(&metadata(&[("x-api-key", "partner-secret-key")]))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(identity.email, "partner@example.org");
+        assert_eq!(identity.tenant, "example.org");
+        assert_eq!(identity.method, AuthMethod::ApiKey);
+
+        let err = authenticator()
+            .authenticate(&metadata(&[("x-api-key", "guessed-key")]))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn test_missing_credentials_rejected() {
+        let err = authenticator()
+            .authenticate(&metadata(&[("email", "alice@example.com")]))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn test_mismatched_email_header_rejected() {
+        let bearer = format!("Bearer {}", mint_hs256(valid_claims("alice@example.com")));
+
+        let err = authenticator()
+            .authenticate(&metadata(&[
+                ("authorization", &bearer),
+                ("email", "mallory@example.com"),
+            ]))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+
+        let identity = authenticator()
+            .authenticate(&metadata(&[
+                ("authorization", &bearer),
+                ("email", "Alice@Example.com"),
+            ]))
+            .unwrap();
+        assert!(identity.is_some());
+    }
+
+    #[test]
+    fn test_disabled_passes_through() {
+        let identity = Authenticator::disabled()
+            .authenticate(&metadata(&[("email", "alice@example.com")]))
+            .unwrap();
+        assert!(identity.is_none());
+    }
+}
@@ -1,13 +1,21 @@
 use crate::analysis::analyze_sentence_enhanced::analyze_sentence_enhanced;
+use crate::parameter_memory::ParameterDefaults;
 // src/cli.rs - Updated to use only Cohere
 use crate::app_log;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use std::{error::Error, sync::Arc};
 
 use crate::comparison_test::run_model_comparison;
 use crate::endpoint_client::get_default_api_url;
+use crate::health::StoreSlot;
+use crate::migrations;
+use crate::models::config::{load_language_detection_config, load_usage_config};
 use crate::models::providers::ModelProvider;
+use crate::prompts;
+use crate::usage::ledger::UsageLedger;
+use crate::usage::{self, MeteredProvider, UsageScope};
 use crate::utils::email::validate_email;
+use crate::utils::language;
 use crate::workflow::classify_intent::IntentType;
 
 pub fn display_custom_help() {
@@ -53,6 +61,22 @@ USAGE EXAMPLES:
   6. List available endpoints:
      semantic --list-endpoints --email user@example.com
 
+  7. Usage report per day, model and endpoint (optionally as CSV):
+     semantic usage --email user@example.com --since 2025-01-01
+     semantic usage --email user@example.com --since 2025-01-01 --csv usage.csv
+
+  8. Match rate and token cost per prompt experiment variant:
+     semantic experiments --since 2025-01-01
+
+  9. Check prompts.yaml templates against the variables the code supplies:
+     semantic prompts check
+     semantic prompts check --file prompts.staging.yaml
+
+ 10. Database schema migrations (uses DATABASE_URL):
+     semantic db status
+     semantic db migrate --dry-run
+     semantic db migrate
+
 INTENT TYPES SUPPORTED:
   📋 Actionable Request: \"Send email to john@example.com\"
   💬 General Question: \"What is machine learning?\"
@@ -79,6 +103,9 @@ For more information, use the standard help:
 {all-args}{after-help}
 ")]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(long, value_name = "PROVIDER", default_value = "cohere")]
     pub provider: String,
     /// The sentence to analyze (if not provided, starts gRPC server)
@@ -118,6 +145,280 @@ pub struct Cli {
     pub iterations: u32,
 }
 
+#[derive(Subcommand)]
+pub enum Command {
+    /// Report LLM usage and cost from the usage ledger
+    Usage(UsageArgs),
+    /// Report match rate and token cost per prompt experiment variant
+    Experiments(ExperimentsArgs),
+    /// Manage prompt templates
+    Prompts {
+        #[command(subcommand)]
+        command: PromptsCommand,
+    },
+    /// Manage the database schema
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// Apply pending schema migrations
+    Migrate {
+        /// Show the pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the schema migrations and whether they are applied
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum PromptsCommand {
+    /// Parse every template and check its variables, failing on errors
+    Check {
+        /// Prompts file (default: PROMPTS_PATH or prompts.yaml)
+        #[arg(long, value_name = "FILE")]
+        file: Option<String>,
+    },
+}
+
+#[derive(Args)]
+pub struct UsageArgs {
+    /// Email whose usage is reported
+    #[arg(long, value_name = "EMAIL")]
+    pub email: String,
+
+    /// Start of the report, YYYY-MM-DD or RFC 3339 (default: 30 days ago)
+    #[arg(long, value_name = "DATE")]
+    pub since: Option<String>,
+
+    /// End of the report, exclusive (default: now)
+    #[arg(long, value_name = "DATE")]
+    pub until: Option<String>,
+
+    /// Write the report as CSV to this file ('-' for stdout)
+    #[arg(long, value_name = "FILE")]
+    pub csv: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ExperimentsArgs {
+    /// Start of the report, YYYY-MM-DD or RFC 3339 (default: 30 days ago)
+    #[arg(long, value_name = "DATE")]
+    pub since: Option<String>,
+
+    /// End of the report, exclusive (default: now)
+    #[arg(long, value_name = "DATE")]
+    pub until: Option<String>,
+}
+
+/// Run a subcommand; these do not need a model provider
+pub async fn handle_command(command: Command) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match command {
+        Command::Usage(args) => report_usage(args).await,
+        Command::Experiments(args) => report_experiments(args).await,
+        Command::Prompts {
+            command: PromptsCommand::Check { file },
+        } => check_prompts(file.as_deref()).await,
+        Command::Db { command } => handle_db(command).await,
+    }
+}
+
+async fn handle_db(command: DbCommand) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let database_url = crate::progressive_matching::get_postgres_url()?;
+    let (mut client, connection) =
+        tokio_postgres::connect(&database_url, tokio_postgres::NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            app_log!(error, "Database connection error: {}", e);
+        }
+    });
+
+    match command {
+        DbCommand::Migrate { dry_run } => {
+            let migrations = migrations::migrate(&mut client, dry_run).await?;
+            if migrations.is_empty() {
+                println!("Database schema is up to date");
+                return Ok(());
+            }
+            for migration in &migrations {
+                if dry_run {
+                    println!("-- {:04} {}", migration.version, migration.name);
+                    println!("{}", migration.sql.trim_end());
+                } else {
+                    println!("Applied {:04} {}", migration.version, migration.name);
+                }
+            }
+            if dry_run {
+                println!("{} pending migration(s), nothing applied", migrations.len());
+            }
+        }
+        DbCommand::Status => {
+            println!("{:<8} {:<28} Applied", "Version", "Name");
+            for migration in migrations::status(&mut client).await? {
+                let applied = migration
+                    .applied_at
+                    .map(|at| at.to_rfc3339())
+                    .unwrap_or_else(|| "pending".to_string());
+                println!(
+                    "{:<8} {:<28} {}",
+                    format!("{:04}", migration.version),
+                    migration.name,
+                    applied
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn check_prompts(file: Option<&str>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let issues = prompts::check(file).await?;
+    for issue in &issues {
+        let level = if issue.fatal { "error" } else { "warning" };
+        println!("{level}: {issue}");
+    }
+
+    let errors = issues.iter().filter(|issue| issue.fatal).count();
+    if errors > 0 {
+        return Err(format!("{errors} prompt error(s)").into());
+    }
+    println!("Prompts OK ({} warning(s))", issues.len());
+    Ok(())
+}
+
+/// Open the usage ledger for reading, even when this instance does not record to it
+async fn open_ledger() -> Result<UsageLedger, Box<dyn Error + Send + Sync>> {
+    let mut usage_config = load_usage_config().await?;
+    usage_config.enabled = true;
+    Ok(UsageLedger::from_config(&usage_config)
+        .await?
+        .ok_or("Usage ledger unavailable")?)
+}
+
+fn report_bounds(
+    since: Option<&str>,
+    until: Option<&str>,
+) -> Result<
+    (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>),
+    Box<dyn Error + Send + Sync>,
+> {
+    let until = match until {
+        Some(until) => usage::parse_bound(until)?,
+        None => chrono::Utc::now(),
+    };
+    let since = match since {
+        Some(since) => usage::parse_bound(since)?,
+        None => until - chrono::Duration::days(30),
+    };
+    Ok((since, until))
+}
+
+async fn report_experiments(args: ExperimentsArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (since, until) = report_bounds(args.since.as_deref(), args.until.as_deref())?;
+    let summaries = open_ledger()
+        .await?
+        .summarize_experiments(since, until)
+        .await?;
+
+    println!(
+        "\nPrompt experiments from {} to {}",
+        since.to_rfc3339(),
+        until.to_rfc3339()
+    );
+    if summaries.is_empty() {
+        println!("No experiment traffic recorded in this period.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<28} {:<8} {:>9} {:>8} {:>7} {:>12} {:>12} {:>12}",
+        "Experiment", "Variant", "Requests", "Matched", "Calls", "Input", "Output", "Cost (USD)"
+    );
+    for s in &summaries {
+        let match_rate = if s.requests > 0 {
+            format!("{:.1}%", s.matched as f64 * 100.0 / s.requests as f64)
+        } else {
+            "-".to_string()
+        };
+        println!(
+            "{:<28} {:<8} {:>9} {:>8} {:>7} {:>12} {:>12} {:>12.6}",
+            s.experiment,
+            s.variant,
+            s.requests,
+            match_rate,
+            s.calls,
+            s.input_tokens,
+            s.output_tokens,
+            s.cost
+        );
+    }
+
+    Ok(())
+}
+
+async fn report_usage(args: UsageArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    validate_email(&args.email)?;
+
+    let (since, until) = report_bounds(args.since.as_deref(), args.until.as_deref())?;
+    let summaries = open_ledger()
+        .await?
+        .summarize(&args.email, since, until)
+        .await?;
+
+    if let Some(path) = &args.csv {
+        let csv = usage::summaries_to_csv(&summaries);
+        if path == "-" {
+            print!("{csv}");
+        } else {
+            tokio::fs::write(path, csv).await?;
+            println!("Wrote {} rows to {}", summaries.len(), path);
+        }
+        return Ok(());
+    }
+
+    println!(
+        "\nUsage for {} from {} to {}",
+        args.email,
+        since.to_rfc3339(),
+        until.to_rfc3339()
+    );
+    if summaries.is_empty() {
+        println!("No usage recorded in this period.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<12} {:<28} {:<18} {:>7} {:>12} {:>12} {:>12}",
+        "Day", "Model", "RPC", "Calls", "Input", "Output", "Cost (USD)"
+    );
+    for s in &summaries {
+        println!(
+            "{:<12} {:<28} {:<18} {:>7} {:>12} {:>12} {:>12.6}",
+            s.day.to_string(),
+            s.model,
+            s.rpc,
+            s.calls,
+            s.input_tokens,
+            s.output_tokens,
+            s.cost
+        );
+    }
+    println!(
+        "Total: {} tokens, {:.6} USD",
+        summaries
+            .iter()
+            .map(|s| s.input_tokens + s.output_tokens)
+            .sum::<u64>(),
+        summaries.iter().map(|s| s.cost).sum::<f64>()
+    );
+
+    Ok(())
+}
+
 // Update handle_cli function to handle enhanced intent testing:
 pub async fn handle_cli(
     mut cli: Cli,
@@ -216,8 +517,43 @@ pub async fn handle_cli(
         app_log!(info, "Using endpoints from {}", endpoint_source);
         app_log!(info, "Analyzing prompt via CLI: {}", prompt);
 
+        let usage_config = load_usage_config().await.unwrap_or_default();
+        // Calls are counted for the reported usage even when they are not metered
+        let ledger = match UsageLedger::from_config(&usage_config).await? {
+            Some(ledger) => StoreSlot::new(Arc::new(ledger)),
+            None => StoreSlot::empty(),
+        };
+        let provider: Arc<dyn ModelProvider> =
+            Arc::new(MeteredProvider::new(provider, Arc::new(ledger)));
+        let scope = UsageScope {
+            email: Some(email.clone()),
+            conversation_id: None,
+            rpc: "cli".to_string(),
+            language: language::detect_confident(
+                &prompt,
+                load_language_detection_config()
+                    .await
+                    .unwrap_or_default()
+                    .min_confidence,
+            )
+            .map(str::to_string),
+            tenant: None,
+        };
+
         // Pass the API URL and email to analyze_sentence
-        let result = analyze_sentence_enhanced(&prompt, provider, cli.api, &email, None).await?;
+        let result = usage::scoped(
+            scope,
+            analyze_sentence_enhanced(
+                &prompt,
+                provider,
+                cli.api,
+                &email,
+                None,
+                &ParameterDefaults::default(),
+                None,
+            ),
+        )
+        .await?;
 
         println!("\nAnalysis Results:");
         println!(
@@ -1,4 +1,5 @@
 use crate::analysis::analyze_sentence_enhanced::analyze_sentence_enhanced;
+use crate::parameter_memory::ParameterDefaults;
 // src/comparison_test.rs
 use crate::app_log;
 use crate::models::providers::{create_provider, ModelProvider, ProviderConfig};
@@ -148,6 +149,8 @@ impl ModelComparisonTester {
                 Some(self.config.api_url.clone()),
                 &self.config.email,
                 Some(self.config.conversation_id.clone()),
+                &ParameterDefaults::default(),
+                None,
             )
             .await
             {
@@ -1,5 +1,6 @@
 // src/conversation.rs
 use crate::app_log;
+use crate::models::EnhancedAnalysisResult;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -26,9 +27,27 @@ pub struct ConversationMessage {
     pub parameters: Option<serde_json::Value>,
 }
 
+/// A matched request waiting for the user to confirm it
+#[derive(Debug, Clone)]
+pub struct PendingConfirmation {
+    pub result: EnhancedAnalysisResult,
+    pub requested_at: chrono::DateTime<chrono::Utc>,
+    /// The user who made the request; only their reply confirms it
+    pub email: String,
+}
+
+impl PendingConfirmation {
+    pub fn belongs_to(&self, email: &str) -> bool {
+        self.email.eq_ignore_ascii_case(email)
+    }
+}
+
 pub struct ConversationManager {
     conversations: Arc<RwLock<HashMap<String, ConversationMetadata>>>,
     messages: Arc<RwLock<HashMap<String, Vec<ConversationMessage>>>>,
+    confirmations: Arc<RwLock<HashMap<String, PendingConfirmation>>>,
+    /// Last language identified with confidence in each conversation
+    languages: Arc<RwLock<HashMap<String, String>>>,
 }
 
 impl ConversationManager {
@@ -36,6 +55,8 @@ impl ConversationManager {
         Self {
             conversations: Arc::new(RwLock::new(HashMap::new())),
             messages: Arc::new(RwLock::new(HashMap::new())),
+            confirmations: Arc::new(RwLock::new(HashMap::new())),
+            languages: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -113,6 +134,71 @@ impl ConversationManager {
         app_log!(debug, "Added message to conversation: {}", conversation_id);
         Ok(())
     }
+
+    /// Hold `result` until the next message of `email` in the conversation confirms or
+    /// declines it, replacing any earlier one
+    pub async fn request_confirmation(
+        &self,
+        conversation_id: &str,
+        email: &str,
+        result: EnhancedAnalysisResult,
+    ) {
+        let pending = PendingConfirmation {
+            result,
+            requested_at: chrono::Utc::now(),
+            email: email.to_lowercase(),
+        };
+        self.confirmations
+            .write()
+            .await
+            .insert(conversation_id.to_string(), pending);
+    }
+
+    /// Remove and return the confirmation the conversation waits for from `email`,
+    /// unless it is older than `ttl`. Messages of other users leave it in place.
+    pub async fn take_confirmation(
+        &self,
+        conversation_id: &str,
+        email: &str,
+        ttl: chrono::Duration,
+    ) -> Option<PendingConfirmation> {
+        let mut confirmations = self.confirmations.write().await;
+        if !confirmations.get(conversation_id)?.belongs_to(email) {
+            return None;
+        }
+        let pending = confirmations.remove(conversation_id)?;
+        drop(confirmations);
+        if chrono::Utc::now() - pending.requested_at > ttl {
+            app_log!(
+                info,
+                "Confirmation of {} in conversation {} expired",
+                pending.result.endpoint_id,
+                conversation_id
+            );
+            return None;
+        }
+        Some(pending)
+    }
+
+    /// The language of a message: `detected` when the message was identified with
+    /// confidence, which the conversation then keeps, else the conversation's last
+    /// one. Short follow-ups ("42", "ok") stay in the language of the conversation.
+    pub async fn language(
+        &self,
+        conversation_id: &str,
+        detected: Option<String>,
+    ) -> Option<String> {
+        match detected {
+            Some(language) => {
+                self.languages
+                    .write()
+                    .await
+                    .insert(conversation_id.to_string(), language.clone());
+                Some(language)
+            }
+            None => self.languages.read().await.get(conversation_id).cloned(),
+        }
+    }
 }
 
 impl Default for ConversationManager {
@@ -120,3 +206,56 @@ impl Default for ConversationManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::response_builders::create_complete_progressive_response;
+    use crate::models::EnhancedEndpoint;
+    use crate::progressive_matching::ProgressiveMatchResult;
+
+    #[tokio::test]
+    async fn test_only_the_requester_takes_a_confirmation() {
+        let endpoint = EnhancedEndpoint {
+            id: "delete_invoice".to_string(),
+            ..Default::default()
+        };
+        let result = create_complete_progressive_response(
+            &endpoint,
+            ProgressiveMatchResult {
+                conversation_id: "conv".to_string(),
+                endpoint_id: endpoint.id.clone(),
+                endpoint_description: String::new(),
+                matched_parameters: vec![],
+                missing_parameters: vec![],
+                is_complete: true,
+                completion_percentage: 100.0,
+                ready_for_execution: true,
+            },
+            &None,
+        )
+        .await
+        .unwrap();
+        let manager = ConversationManager::new();
+        let ttl = chrono::Duration::minutes(5);
+        manager
+            .request_confirmation("conv", "Ann@example.com", result)
+            .await;
+
+        // Another user replying "yes" on the same conversation id
+        assert!(manager
+            .take_confirmation("conv", "mallory@example.com", ttl)
+            .await
+            .is_none());
+
+        let pending = manager
+            .take_confirmation("conv", "ann@example.com", ttl)
+            .await
+            .unwrap();
+        assert_eq!(pending.result.endpoint_id, "delete_invoice");
+        assert!(manager
+            .take_confirmation("conv", "ann@example.com", ttl)
+            .await
+            .is_none());
+    }
+}
@@ -2,7 +2,9 @@ pub mod endpoint {
     tonic::include_proto!("endpoint");
 }
 use crate::app_log;
+use crate::error::SemanticError;
 use crate::models::config::load_endpoint_client_config;
+use crate::models::RiskLevel;
 use endpoint::endpoint_service_client::EndpointServiceClient;
 use endpoint::{Endpoint, GetApiGroupsRequest};
 use std::error::Error;
@@ -142,6 +144,8 @@ pub fn convert_remote_endpoints_enhanced(
                     name: re.text.clone(),
                     text: re.text,
                     description: re.description,
+                    risk_level: RiskLevel::parse(&re.risk_level)
+                        .unwrap_or_else(|| RiskLevel::from_verb(&re.verb)),
                     verb: re.verb,
                     base: re.base,
                     path: re.path.clone(),
@@ -156,6 +160,7 @@ pub fn convert_remote_endpoints_enhanced(
                             description: rp.description,
                             required: Some(rp.required == "true"),
                             alternatives: Some(rp.alternatives),
+                            examples: Some(rp.examples),
                             semantic_value: None,
                         })
                         .collect(),
@@ -181,30 +186,42 @@ fn extract_essential_path(path: &str) -> String {
 pub async fn get_enhanced_endpoints(
     addr: &str,
     email: &str,
-) -> Result<Vec<crate::models::EnhancedEndpoint>, Box<dyn Error + Send + Sync>> {
-    let channel = Channel::from_shared(addr.to_string())?
+) -> Result<Vec<crate::models::EnhancedEndpoint>, SemanticError> {
+    let unavailable = |e: &dyn std::fmt::Display| SemanticError::EndpointService(e.to_string());
+    let channel = Channel::from_shared(addr.to_string())
+        .map_err(|e| unavailable(&e))?
         .connect_timeout(std::time::Duration::from_secs(5))
         .timeout(std::time::Duration::from_secs(10))
         .connect()
-        .await?;
+        .await
+        .map_err(|e| unavailable(&e))?;
 
     let mut client = EndpointServiceClient::new(channel);
     let request = tonic::Request::new(GetApiGroupsRequest {
         email: email.to_string(),
     });
 
-    let response = client.get_api_groups(request).await?;
+    let response = client
+        .get_api_groups(request)
+        .await
+        .map_err(|e| SemanticError::from_endpoint_service(&e))?;
     let mut stream = response.into_inner();
     let mut api_groups = Vec::new();
 
-    while let Some(response) = stream.message().await? {
+    while let Some(response) = stream
+        .message()
+        .await
+        .map_err(|e| SemanticError::from_endpoint_service(&e))?
+    {
         api_groups.extend(response.api_groups);
     }
 
     let enhanced_endpoints = convert_remote_endpoints_enhanced(api_groups);
 
     if enhanced_endpoints.is_empty() {
-        return Err(format!("No endpoints available for user '{email}'").into());
+        return Err(SemanticError::NoEndpoints {
+            email: email.to_string(),
+        });
     }
 
     Ok(enhanced_endpoints)
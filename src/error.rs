@@ -0,0 +1,231 @@
+// src/error.rs - Failures of an analysis and how they reach gRPC clients
+use crate::models::providers::error::ProviderError;
+use crate::sentence_service::sentence::ErrorDetail;
+use prost::Message;
+use std::error::Error;
+use std::time::Duration;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+/// Why an analysis failed. Each variant maps to one gRPC code and a stable `reason`.
+#[derive(Debug, thiserror::Error)]
+pub enum SemanticError {
+    /// The caller sent something unusable, e.g. a malformed email
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error(
+        "No endpoints configured for your account ({email}). Please contact your administrator."
+    )]
+    NoEndpoints { email: String },
+    #[error("No suitable endpoint found for the given input")]
+    NoMatch,
+    /// The LLM answered with an endpoint id that is not in the list it was given
+    #[error("Endpoint ID '{id}' not found in available endpoints. Available IDs: [{available}]")]
+    UnknownEndpoint { id: String, available: String },
+    /// The endpoint service cannot be reached or did not answer in time
+    #[error("Endpoint service is unavailable: {0}")]
+    EndpointService(String),
+    /// The endpoint service answered with an error, passed on with its code
+    #[error("Endpoint service failed ({code:?}): {message}")]
+    EndpointServiceFailed { code: Code, message: String },
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+    #[error("Step {step} timed out after {after:?}")]
+    StepTimeout { step: String, after: Duration },
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl SemanticError {
+    pub fn code(&self) -> Code {
+        match self {
+            SemanticError::InvalidRequest(_) => Code::InvalidArgument,
+            SemanticError::NoEndpoints { .. }
+            | SemanticError::NoMatch
+            | SemanticError::UnknownEndpoint { .. } => Code::NotFound,
+            SemanticError::EndpointService(_) => Code::Unavailable,
+            SemanticError::EndpointServiceFailed { code, .. } => *code,
+            SemanticError::Provider(e) => match e {
+                ProviderError::RateLimited { .. } => Code::ResourceExhausted,
+                ProviderError::Timeout { .. } => Code::DeadlineExceeded,
+                ProviderError::Overloaded { .. } | ProviderError::Connection { .. } => {
+                    Code::Unavailable
+                }
+                ProviderError::ContentFiltered { .. } => Code::InvalidArgument,
+                ProviderError::Auth { .. }
+                | ProviderError::InvalidResponse { .. }
+                | ProviderError::Http { .. } => Code::Internal,
+            },
+            SemanticError::StepTimeout { .. } => Code::DeadlineExceeded,
+            SemanticError::Internal(_) => Code::Internal,
+        }
+    }
+
+    /// Machine-readable cause, stable across releases
+    pub fn reason(&self) -> &'static str {
+        match self {
+            SemanticError::InvalidRequest(_) => "INVALID_REQUEST",
+            SemanticError::NoEndpoints { .. } => "NO_ENDPOINTS",
+            SemanticError::NoMatch => "NO_ENDPOINT_MATCH",
+            SemanticError::UnknownEndpoint { .. } => "UNKNOWN_ENDPOINT",
+            SemanticError::EndpointService(_) => "ENDPOINT_SERVICE_UNAVAILABLE",
+            SemanticError::EndpointServiceFailed { .. } => "ENDPOINT_SERVICE_ERROR",
+            SemanticError::Provider(e) => match e {
+                ProviderError::RateLimited { .. } => "PROVIDER_RATE_LIMITED",
+                ProviderError::Auth { .. } => "PROVIDER_AUTH",
+                ProviderError::Timeout { .. } => "PROVIDER_TIMEOUT",
+                ProviderError::Overloaded { .. } => "PROVIDER_OVERLOADED",
+                ProviderError::InvalidResponse { .. } => "PROVIDER_INVALID_RESPONSE",
+                ProviderError::ContentFiltered { .. } => "CONTENT_FILTERED",
+                ProviderError::Http { .. } => "PROVIDER_ERROR",
+                ProviderError::Connection { .. } => "PROVIDER_UNREACHABLE",
+            },
+            SemanticError::StepTimeout { .. } => "STEP_TIMEOUT",
+            SemanticError::Internal(_) => "INTERNAL",
+        }
+    }
+
+    /// The LLM picked no endpoint, or one that does not exist; asking again may help
+    pub fn is_match_failure(&self) -> bool {
+        matches!(
+            self,
+            SemanticError::NoMatch | SemanticError::UnknownEndpoint { .. }
+        )
+    }
+
+    /// The same request may succeed when sent again later
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SemanticError::EndpointService(_) | SemanticError::StepTimeout { .. } => true,
+            SemanticError::Provider(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// An unreachable or slow endpoint service is unavailable; any other status it
+    /// answered with keeps its code
+    pub fn from_endpoint_service(status: &Status) -> Self {
+        match status.code() {
+            Code::Unavailable | Code::DeadlineExceeded => {
+                SemanticError::EndpointService(status.message().to_string())
+            }
+            code => SemanticError::EndpointServiceFailed {
+                code,
+                message: status.message().to_string(),
+            },
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            SemanticError::Provider(e) => e.retry_after(),
+            _ => None,
+        }
+    }
+
+    /// Status with an `ErrorDetail` as binary details, plus `retry-after` (seconds)
+    /// and `request-id` metadata
+    pub fn to_status(&self, request_id: Option<&str>) -> Status {
+        let provider = match self {
+            SemanticError::Provider(e) => Some(e.provider().to_string()),
+            _ => None,
+        };
+        let detail = ErrorDetail {
+            reason: self.reason().to_string(),
+            retryable: self.is_retryable(),
+            retry_after_ms: self.retry_after().map(|d| d.as_millis() as u64),
+            provider,
+            request_id: request_id.map(str::to_string),
+        };
+        let mut status =
+            Status::with_details(self.code(), self.to_string(), detail.encode_to_vec().into());
+        if let Some(retry_after) = self.retry_after() {
+            status.metadata_mut().insert(
+                "retry-after",
+                MetadataValue::from(retry_after.as_secs_f64().ceil() as u64),
+            );
+        }
+        if let Some(request_id) = request_id.and_then(|id| id.parse().ok()) {
+            status.metadata_mut().insert("request-id", request_id);
+        }
+        status
+    }
+}
+
+/// Recover the typed error from a boxed one; anything untyped is internal
+impl From<Box<dyn Error + Send + Sync>> for SemanticError {
+    fn from(error: Box<dyn Error + Send + Sync>) -> Self {
+        match error.downcast::<SemanticError>() {
+            Ok(error) => *error,
+            Err(error) => match error.downcast::<ProviderError>() {
+                Ok(error) => SemanticError::Provider(*error),
+                Err(error) => SemanticError::Internal(error.to_string()),
+            },
+        }
+    }
+}
+
+impl From<String> for SemanticError {
+    fn from(message: String) -> Self {
+        SemanticError::Internal(message)
+    }
+}
+
+impl From<&str> for SemanticError {
+    fn from(message: &str) -> Self {
+        SemanticError::Internal(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_carries_code_and_details() {
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(ProviderError::RateLimited {
+            provider: "Claude",
+            retry_after: Some(Duration::from_millis(1500)),
+            message: "slow down".to_string(),
+        });
+        let status = SemanticError::from(boxed).to_status(Some("req-1"));
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "2");
+        assert_eq!(status.metadata().get("request-id").unwrap(), "req-1");
+
+        let detail = ErrorDetail::decode(status.details()).unwrap();
+        assert_eq!(detail.reason, "PROVIDER_RATE_LIMITED");
+        assert!(detail.retryable);
+        assert_eq!(detail.retry_after_ms, Some(1500));
+        assert_eq!(detail.provider.as_deref(), Some("Claude"));
+
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(SemanticError::NoMatch);
+        let status = SemanticError::from(boxed).to_status(None);
+        assert_eq!(status.code(), Code::NotFound);
+        let detail = ErrorDetail::decode(status.details()).unwrap();
+        assert_eq!(detail.reason, "NO_ENDPOINT_MATCH");
+        assert!(!detail.retryable);
+
+        let untyped: Box<dyn Error + Send + Sync> = "Enhanced endpoint data not found".into();
+        assert_eq!(SemanticError::from(untyped).code(), Code::Internal);
+    }
+
+    #[test]
+    fn test_endpoint_service_codes_pass_through() {
+        let denied =
+            SemanticError::from_endpoint_service(&Status::permission_denied("not your tenant"));
+        assert_eq!(denied.code(), Code::PermissionDenied);
+        assert_eq!(denied.reason(), "ENDPOINT_SERVICE_ERROR");
+        assert!(!denied.is_retryable());
+
+        for status in [
+            Status::unavailable("down"),
+            Status::deadline_exceeded("slow"),
+        ] {
+            let error = SemanticError::from_endpoint_service(&status);
+            assert_eq!(error.code(), Code::Unavailable);
+            assert_eq!(error.reason(), "ENDPOINT_SERVICE_UNAVAILABLE");
+            assert!(error.is_retryable());
+        }
+    }
+}
@@ -1,6 +1,8 @@
 // src/general_question_handler.rs
 use crate::models::config::load_models_config;
 use crate::models::providers::{GenerationResult, ModelProvider};
+use crate::prompts::budget::{self, PromptSection};
+use crate::prompts::template::Template;
 use std::error::Error;
 use std::sync::Arc;
 
@@ -9,13 +11,22 @@ pub async fn handle_general_question(
     provider: Arc<dyn ModelProvider>,
 ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>> {
     // Return GenerationResult instead of String
-    let prompt = format!(
-        "You are a helpful assistant. Answer this question naturally and conversationally: {question}"
-    );
-
     let models_config = load_models_config().await?;
     let model_config = &models_config.default;
 
-    let result = provider.generate(&prompt, model_config).await?;
+    let template = Template::parse(
+        "You are a helpful assistant. Answer this question naturally and conversationally: {question}",
+    )?;
+    let prompt = budget::fit(
+        &template,
+        vec![PromptSection::fixed("question", question)],
+        provider.get_model_name(),
+        model_config,
+    )
+    .await?;
+
+    let result = provider
+        .generate(&prompt.prompt, &prompt.model_config)
+        .await?;
     Ok(result) // Return the full result with token usage
 }
@@ -1,9 +1,24 @@
+use crate::auth::Authenticator;
 use crate::endpoint_client::verify_endpoints_configuration;
-use crate::models::config::load_server_config;
+use crate::health::{
+    DatabaseProbe, EndpointServiceProbe, HealthMonitor, HealthProbe, ProviderProbe, StoreSlot,
+};
+use crate::metrics;
+use crate::models::config::{
+    load_auth_config, load_health_config, load_metrics_config, load_parameter_memory_config,
+    load_rate_limit_config, load_server_config, load_trace_config, load_usage_config,
+    load_workflows_config,
+};
 use crate::models::providers::ModelProvider;
-use crate::progressive_matching::get_database_url;
+use crate::parameter_memory::ParameterMemory;
+use crate::progressive_matching::{get_database_url, get_postgres_url};
+use crate::rate_limit::RateLimiter;
 use crate::sentence_service::sentence::sentence_service_server::SentenceServiceServer;
 use crate::sentence_service::SentenceAnalyzeService;
+use crate::trace::{TraceStore, TracingProvider};
+use crate::usage::ledger::UsageLedger;
+use crate::usage::MeteredProvider;
+use crate::workflow::registry::StepRegistry;
 use std::sync::Arc;
 use tonic::transport::Server;
 use tonic_reflection::server::Builder;
@@ -30,29 +45,45 @@ pub async fn start_sentence_grpc_server(
 
     app_log!(info, "Starting sentence analysis gRPC server on {}", addr);
 
-    // Check if endpoints are available - REQUIRED for startup
+    // An unavailable endpoint service no longer prevents startup: the server starts
+    // degraded, reports NOT_SERVING and recovers once the background probe succeeds
     match verify_endpoints_configuration(api_url.clone()).await {
-        Ok(true) => {
-            app_log!(info, "Endpoint configuration verified - either remote service or local file is available");
-        }
-        Ok(false) => {
-            app_log!(error, "FATAL: No endpoint configuration available! The server cannot start without endpoints.");
-            return Err("No endpoint configuration available".into());
+        Ok(_) => {
+            app_log!(info, "Endpoint configuration verified - remote endpoint service is available");
         }
         Err(e) => {
-            app_log!(error, "Error checking endpoint configuration: {}", e);
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("Failed to verify endpoint configuration: {e}"),
-            )));
+            app_log!(warn, "Starting in degraded mode, endpoint service unavailable: {}", e);
         }
     }
 
-    app_log!(info, "Email is required for each request - no defaults will be used");
+    // Reject workflow definitions that reference unknown steps before serving anything
+    let workflows = load_workflows_config().await?;
+    if let Err(e) = StepRegistry::with_builtin_steps().validate_all(&workflows) {
+        app_log!(error, "Invalid workflow configuration: {}", e);
+        return Err(e.into());
+    }
+
+    let auth_config = load_auth_config().await?;
+    let authenticator = Authenticator::from_config(&auth_config)?;
+    if authenticator.is_enabled() {
+        app_log!(info, "Caller identity is derived from verified JWT or API key credentials");
+    } else {
+        app_log!(info, "Email is required for each request - no defaults will be used");
+    }
+
+    let metrics_config = load_metrics_config().await?;
+    if metrics_config.enabled {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(&metrics_config).await {
+                app_log!(error, "Metrics endpoint failed: {}", e);
+            }
+        });
+    }
 
     let descriptor_set = include_bytes!(concat!(env!("OUT_DIR"), "/sentence_descriptor.bin"));
     let reflection_service = Builder::configure()
         .register_encoded_file_descriptor_set(descriptor_set)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
         .build_v1()?;
 
     // Create CORS layer
@@ -64,6 +95,21 @@ pub async fn start_sentence_grpc_server(
 
     app_log!(info, "Starting semantic gRPC server on {}", addr);
 
+    // Stores that cannot reach PostgreSQL at startup are retried by the database probe
+    let mut pending_stores = Vec::new();
+
+    // Every provider call counts toward its request's token quota, and is written to
+    // the usage ledger when metering is enabled
+    let usage_config = load_usage_config().await?;
+    let usage_ledger = UsageLedger::from_config_deferred(&usage_config, &mut pending_stores).await;
+    let ledger_slot = usage_ledger
+        .clone()
+        .unwrap_or_else(|| Arc::new(StoreSlot::empty()));
+    let provider: Arc<dyn ModelProvider> = Arc::new(MeteredProvider::new(provider, ledger_slot));
+    // Prompts and raw responses are captured only for traced requests
+    let provider: Arc<dyn ModelProvider> = Arc::new(TracingProvider::new(provider));
+    let trace_store = Arc::new(TraceStore::new(load_trace_config().await?));
+
     // Use the provider that was passed in from main.rs
     // In src/grpc_server.rs, change the initialization to:
 
@@ -74,6 +120,7 @@ pub async fn start_sentence_grpc_server(
                 provider.clone(),
                 api_url.clone(),
                 &db_url,
+                &mut pending_stores,
             )
             .await
             {
@@ -81,17 +128,60 @@ pub async fn start_sentence_grpc_server(
                 Err(e) => {
                     app_log!(error, "Failed to initialize with progressive matching: {}", e);
                     app_log!(info, "Falling back to service without progressive matching");
-                    SentenceAnalyzeService::new(provider, api_url)
+                    SentenceAnalyzeService::new(provider.clone(), api_url.clone())
                 }
             }
         }
         Err(e) => {
             app_log!(error, "Failed to resolve database path: {}", e);
             app_log!(info, "Falling back to service without progressive matching");
-            SentenceAnalyzeService::new(provider, api_url)
+            SentenceAnalyzeService::new(provider.clone(), api_url.clone())
         }
     };
-    let service = SentenceServiceServer::new(sentence_service);
+    let rate_limit_config = load_rate_limit_config().await?;
+    let rate_limiter =
+        Arc::new(RateLimiter::from_config(&rate_limit_config, &mut pending_stores).await);
+    let parameter_memory_config = load_parameter_memory_config().await?;
+    let parameter_memory = Arc::new(
+        ParameterMemory::from_config(&parameter_memory_config, &mut pending_stores).await,
+    );
+    let sentence_service = sentence_service
+        .with_rate_limiter(rate_limiter)
+        .with_usage_ledger(usage_ledger)
+        .with_parameter_memory(parameter_memory)
+        .with_trace_store(trace_store);
+
+    let service =
+        SentenceServiceServer::with_interceptor(sentence_service, authenticator.interceptor());
+
+    // grpc.health.v1, updated by periodic probes of everything requests depend on
+    let health_config = load_health_config().await?;
+    let (health_reporter, health_service) = tonic_health::server::health_reporter();
+    let mut probes: Vec<Arc<dyn HealthProbe>> = Vec::new();
+    if let Some(url) = &api_url {
+        probes.push(Arc::new(EndpointServiceProbe {
+            api_url: url.clone(),
+        }));
+    }
+    let database_url = get_postgres_url().ok();
+    if database_url.is_some() || !pending_stores.is_empty() {
+        probes.push(Arc::new(DatabaseProbe {
+            database_url,
+            pending: pending_stores,
+        }));
+    }
+    if health_config.probe_provider {
+        probes.push(Arc::new(ProviderProbe {
+            provider: provider.clone(),
+        }));
+    }
+    let health_monitor = HealthMonitor::new(health_reporter, probes, &health_config);
+    if !health_monitor.probe_once().await {
+        app_log!(warn, "Dependencies unhealthy at startup, reporting NOT_SERVING until they recover");
+    }
+    health_monitor.spawn(std::time::Duration::from_secs(
+        health_config.probe_interval_secs.max(1),
+    ));
 
     match Server::builder()
         .accept_http1(true)
@@ -101,6 +191,7 @@ pub async fn start_sentence_grpc_server(
         .layer(cors) // Add CORS layer
         .layer(GrpcWebLayer::new())
         .add_service(service)
+        .add_service(health_service)
         .add_service(reflection_service) // Add reflection service
         .serve_with_shutdown(addr, async {
             tokio::signal::ctrl_c().await.ok();
@@ -0,0 +1,357 @@
+// src/health.rs - grpc.health.v1 status driven by background dependency probes
+use crate::app_log;
+use crate::endpoint_client::check_endpoint_service_health;
+use crate::models::config::HealthConfig;
+use crate::models::providers::ModelProvider;
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::error::Error;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
+/// Service name reported for the sentence API, in addition to the overall ("") status
+pub const SENTENCE_SERVICE: &str = "sentence.SentenceService";
+
+/// A dependency the server needs to answer requests
+#[async_trait]
+pub trait HealthProbe: Send + Sync {
+    /// Also reported as its own health service, e.g. `semantic.database`
+    fn name(&self) -> &'static str;
+
+    async fn check(&self) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+pub struct EndpointServiceProbe {
+    pub api_url: String,
+}
+
+#[async_trait]
+impl HealthProbe for EndpointServiceProbe {
+    fn name(&self) -> &'static str {
+        "semantic.endpoint_service"
+    }
+
+    async fn check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match check_endpoint_service_health(&self.api_url).await? {
+            true => Ok(()),
+            false => Err(format!("Endpoint service is not available at {}", self.api_url).into()),
+        }
+    }
+}
+
+/// A store behind `DATABASE_URL` that may connect after startup; `None` until then
+pub struct StoreSlot<T: ?Sized> {
+    value: RwLock<Option<Arc<T>>>,
+}
+
+impl<T: ?Sized> StoreSlot<T> {
+    pub fn new(value: Arc<T>) -> Self {
+        Self {
+            value: RwLock::new(Some(value)),
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            value: RwLock::new(None),
+        }
+    }
+
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.value.read().unwrap().clone()
+    }
+
+    pub fn set(&self, value: Arc<T>) {
+        *self.value.write().unwrap() = Some(value);
+    }
+}
+
+type Connect =
+    Box<dyn Fn() -> BoxFuture<'static, Result<(), Box<dyn Error + Send + Sync>>> + Send + Sync>;
+
+/// A store that could not connect at startup, retried by `DatabaseProbe`
+pub struct PendingStore {
+    name: &'static str,
+    connected: AtomicBool,
+    connect: Connect,
+}
+
+impl PendingStore {
+    /// Fill `slot` with `connect()` now. When that fails the server still starts:
+    /// the store is added to `pending` and connected once the database is reachable.
+    pub async fn connect_or_retry<T, F, Fut>(
+        name: &'static str,
+        slot: &Arc<StoreSlot<T>>,
+        pending: &mut Vec<PendingStore>,
+        connect: F,
+    ) where
+        T: ?Sized + Send + Sync + 'static,
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Arc<T>, Box<dyn Error + Send + Sync>>> + Send + 'static,
+    {
+        let slot = slot.clone();
+        let store = PendingStore {
+            name,
+            connected: AtomicBool::new(false),
+            connect: Box::new(move || {
+                let slot = slot.clone();
+                let connecting = connect();
+                Box::pin(async move {
+                    slot.set(connecting.await?);
+                    Ok(())
+                })
+            }),
+        };
+        match (store.connect)().await {
+            Ok(()) => store.connected.store(true, Ordering::SeqCst),
+            Err(e) => {
+                app_log!(
+                    warn,
+                    "Starting without the {}, retrying in the background: {}",
+                    name,
+                    e
+                );
+                pending.push(store);
+            }
+        }
+    }
+
+    /// Connect unless already connected
+    async fn retry(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.connected.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        (self.connect)()
+            .await
+            .map_err(|e| format!("{} is not connected: {e}", self.name))?;
+        self.connected.store(true, Ordering::SeqCst);
+        app_log!(info, "Connected the {}", self.name);
+        Ok(())
+    }
+}
+
+/// Reachability of PostgreSQL, and of the stores that could not connect at startup
+pub struct DatabaseProbe {
+    /// Set when `DATABASE_URL` points at PostgreSQL
+    pub database_url: Option<String>,
+    pub pending: Vec<PendingStore>,
+}
+
+#[async_trait]
+impl HealthProbe for DatabaseProbe {
+    fn name(&self) -> &'static str {
+        "semantic.database"
+    }
+
+    async fn check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(database_url) = &self.database_url {
+            let (client, connection) =
+                tokio_postgres::connect(database_url, tokio_postgres::NoTls).await?;
+            let connection = tokio::spawn(connection);
+            let result = client.simple_query("SELECT 1").await;
+            drop(client);
+            let _ = connection.await;
+            result?;
+        }
+        for store in &self.pending {
+            store.retry().await?;
+        }
+        Ok(())
+    }
+}
+
+pub struct ProviderProbe {
+    pub provider: Arc<dyn ModelProvider>,
+}
+
+#[async_trait]
+impl HealthProbe for ProviderProbe {
+    fn name(&self) -> &'static str {
+        "semantic.provider"
+    }
+
+    async fn check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.provider.health_check().await
+    }
+}
+
+/// Runs the probes and publishes their results through the health reporter.
+/// The overall and sentence service statuses are SERVING only when every probe passes.
+pub struct HealthMonitor {
+    reporter: HealthReporter,
+    probes: Vec<Arc<dyn HealthProbe>>,
+    timeout: Duration,
+    /// Last result per probe, used to log transitions only
+    last: Mutex<Vec<Option<bool>>>,
+}
+
+impl HealthMonitor {
+    pub fn new(
+        reporter: HealthReporter,
+        probes: Vec<Arc<dyn HealthProbe>>,
+        config: &HealthConfig,
+    ) -> Self {
+        let last = Mutex::new(vec![None; probes.len()]);
+        Self {
+            reporter,
+            probes,
+            timeout: Duration::from_secs(config.probe_timeout_secs),
+            last,
+        }
+    }
+
+    /// Probe every dependency once and publish the result; returns overall health
+    pub async fn probe_once(&self) -> bool {
+        let mut reporter = self.reporter.clone();
+        let mut last = self.last.lock().await;
+        let mut all_healthy = true;
+
+        for (index, probe) in self.probes.iter().enumerate() {
+            let healthy = match tokio::time::timeout(self.timeout, probe.check()).await {
+                Ok(Ok(())) => true,
+                Ok(Err(e)) => {
+                    if last[index] != Some(false) {
+                        app_log!(warn, "Health probe {} failed: {}", probe.name(), e);
+                    }
+                    false
+                }
+                Err(_) => {
+                    if last[index] != Some(false) {
+                        app_log!(
+                            warn,
+                            "Health probe {} timed out after {:?}",
+                            probe.name(),
+                            self.timeout
+                        );
+                    }
+                    false
+                }
+            };
+
+            if healthy && last[index] == Some(false) {
+                app_log!(info, "Health probe {} recovered", probe.name());
+            }
+            last[index] = Some(healthy);
+            all_healthy &= healthy;
+
+            reporter
+                .set_service_status(probe.name(), serving_status(healthy))
+                .await;
+        }
+
+        reporter
+            .set_service_status("", serving_status(all_healthy))
+            .await;
+        reporter
+            .set_service_status(SENTENCE_SERVICE, serving_status(all_healthy))
+            .await;
+
+        all_healthy
+    }
+
+    /// Probe immediately, then every `interval` in the background
+    pub fn spawn(self, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.probe_once().await;
+            }
+        });
+    }
+}
+
+fn serving_status(healthy: bool) -> ServingStatus {
+    if healthy {
+        ServingStatus::Serving
+    } else {
+        ServingStatus::NotServing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct FakeProbe {
+        name: &'static str,
+        healthy: AtomicBool,
+    }
+
+    #[async_trait]
+    impl HealthProbe for FakeProbe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            if self.healthy.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err("down".into())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overall_status_follows_probes() {
+        let (reporter, _server) = tonic_health::server::health_reporter();
+        let database = Arc::new(FakeProbe {
+            name: "semantic.database",
+            healthy: AtomicBool::new(true),
+        });
+        let endpoints = Arc::new(FakeProbe {
+            name: "semantic.endpoint_service",
+            healthy: AtomicBool::new(false),
+        });
+        let monitor = HealthMonitor::new(
+            reporter,
+            vec![database.clone(), endpoints.clone()],
+            &HealthConfig::default(),
+        );
+
+        assert!(!monitor.probe_once().await);
+
+        endpoints.healthy.store(true, Ordering::SeqCst);
+        assert!(monitor.probe_once().await);
+
+        database.healthy.store(false, Ordering::SeqCst);
+        assert!(!monitor.probe_once().await);
+    }
+
+    #[tokio::test]
+    async fn test_database_probe_connects_pending_stores() {
+        let reachable = Arc::new(AtomicBool::new(false));
+        let slot: Arc<StoreSlot<String>> = Arc::new(StoreSlot::empty());
+        let mut pending = Vec::new();
+        let connect = reachable.clone();
+        PendingStore::connect_or_retry("test store", &slot, &mut pending, move || {
+            let result: Result<Arc<String>, Box<dyn Error + Send + Sync>> =
+                if connect.load(Ordering::SeqCst) {
+                    Ok(Arc::new("connected".to_string()))
+                } else {
+                    Err("connection refused".into())
+                };
+            async move { result }
+        })
+        .await;
+        assert!(slot.get().is_none());
+        assert_eq!(pending.len(), 1);
+
+        let probe = DatabaseProbe {
+            database_url: None,
+            pending,
+        };
+        assert!(probe.check().await.is_err());
+
+        reachable.store(true, Ordering::SeqCst);
+        assert!(probe.check().await.is_ok());
+        assert_eq!(slot.get().as_deref().map(String::as_str), Some("connected"));
+    }
+}
@@ -1,8 +1,11 @@
 // src/help_response_handler.rs - Using prompts.yaml with minimal transformation
-use crate::models::config::load_models_config;
+use crate::metrics;
+use crate::models::config::{load_language_detection_config, load_models_config};
 use crate::models::providers::{GenerationResult, ModelProvider};
 use crate::models::EnhancedEndpoint;
+use crate::prompts::budget::{self, PromptSection};
 use crate::prompts::PromptManager;
+use crate::utils::language;
 use std::error::Error;
 use std::sync::Arc;
 use crate::app_log;
@@ -14,12 +17,13 @@ pub async fn handle_help_request(
 ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>> {
     app_log!(info, "Handling help request for: {}", sentence);
 
-    // First, detect the language using LLM
-    let detected_language = detect_language_with_llm(sentence, provider.clone()).await?;
+    // First, detect the language, locally unless the identifier is unsure
+    let detected_language = detect_language(sentence, provider.clone()).await?;
     app_log!(debug, "Detected language: {}", detected_language);
 
     // Create the exact endpoints list
-    let endpoints_list = create_exact_endpoints_list(available_endpoints);
+    let capabilities = create_exact_endpoints_list(available_endpoints);
+    let endpoints_list = capabilities.join("\n\n");
     app_log!(debug, 
         "Generated exact endpoints list with {} endpoints",
         available_endpoints.len()
@@ -44,30 +48,74 @@ pub async fn handle_help_request(
 
     // For non-English, use the prompt from prompts.yaml
     let prompt_manager = PromptManager::new().await?;
-    let full_prompt = prompt_manager.format_help_response_with_language(
-        sentence,
-        &endpoints_list,
-        &detected_language,
-        Some("v3"), // Use v3 for minimal transformation
-    );
-
-    app_log!(debug, "Generated help prompt using prompts.yaml");
-
     let models_config = load_models_config().await?;
     let model_config = &models_config.default;
 
-    let result = provider.generate(&full_prompt, model_config).await?;
+    // The default version (v3) keeps the transformation minimal
+    let template = prompt_manager
+        .get_prompt("help_response", None)
+        .ok_or("help_response prompt not found")?;
+    let full_prompt = budget::fit(
+        template,
+        vec![
+            PromptSection::fixed("sentence", sentence),
+            PromptSection::fixed("detected_language", detected_language.as_str()),
+            PromptSection::list("endpoints_list", capabilities, "\n\n").shorten_items_to(200),
+        ],
+        provider.get_model_name(),
+        model_config,
+    )
+    .await?;
+
+    app_log!(debug, "Generated help prompt using prompts.yaml");
+
+    let result = provider
+        .generate(&full_prompt.prompt, &full_prompt.model_config)
+        .await?;
 
     app_log!(info, "Successfully generated help response");
     Ok(result)
 }
 
+async fn detect_language(
+    sentence: &str,
+    provider: Arc<dyn ModelProvider>,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let config = load_language_detection_config().await?;
+    let detected = language::detect(sentence);
+    match detected {
+        Some(detected) if detected.confidence >= config.min_confidence => {
+            app_log!(
+                debug,
+                "Detected language {} locally (confidence {:.2})",
+                detected.code,
+                detected.confidence
+            );
+            metrics::record_language_detection("local");
+            Ok(detected.code.to_string())
+        }
+        _ if config.llm_fallback => {
+            app_log!(
+                debug,
+                "Local language detection unsure ({:?}), asking the LLM",
+                detected
+            );
+            metrics::record_language_detection("llm");
+            detect_language_with_llm(sentence, provider).await
+        }
+        _ => {
+            metrics::record_language_detection("local");
+            Ok(detected.map_or("en", |d| d.code).to_string())
+        }
+    }
+}
+
 async fn detect_language_with_llm(
     sentence: &str,
     provider: Arc<dyn ModelProvider>,
 ) -> Result<String, Box<dyn Error + Send + Sync>> {
     let prompt_manager = PromptManager::new().await?;
-    let language_detection_prompt = prompt_manager.language_detection(sentence, Some("v1"));
+    let language_detection_prompt = prompt_manager.language_detection(sentence, None)?;
 
     let models_config = load_models_config().await?;
     let model_config = &models_config.default;
@@ -79,10 +127,7 @@ async fn detect_language_with_llm(
     let detected_language = result.content.trim().to_lowercase();
 
     // Validate the response is a known language code
-    let valid_languages = [
-        "en", "fr", "es", "de", "it", "pt", "nl", "ru", "ja", "zh", "ko", "ar",
-    ];
-    if valid_languages.contains(&detected_language.as_str()) {
+    if language::LANGUAGES.contains(&detected_language.as_str()) {
         app_log!(debug, "LLM detected language: {}", detected_language);
         Ok(detected_language)
     } else {
@@ -94,9 +139,9 @@ async fn detect_language_with_llm(
     }
 }
 
-fn create_exact_endpoints_list(endpoints: &[EnhancedEndpoint]) -> String {
+fn create_exact_endpoints_list(endpoints: &[EnhancedEndpoint]) -> Vec<String> {
     if endpoints.is_empty() {
-        return "No capabilities currently available.".to_string();
+        return vec!["No capabilities currently available.".to_string()];
     }
 
     let mut capabilities: Vec<String> = Vec::new();
@@ -113,6 +158,5 @@ fn create_exact_endpoints_list(endpoints: &[EnhancedEndpoint]) -> String {
         capabilities.push(endpoint_info);
     }
 
-    capabilities.join("\n\n")
+    capabilities
 }
-
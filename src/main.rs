@@ -1,26 +1,38 @@
 // src/main.rs - Updated with helpers and dead code removed
+// tonic::Status is large by design and is the error type of every gRPC-facing helper
+#![allow(clippy::result_large_err)]
+
+mod auth;
 mod cli;
 mod comparison_test;
 mod conversation;
 mod endpoint_client;
+mod error;
 mod general_question_handler;
 mod grpc_server;
+mod health;
 mod help_response_handler;
 mod json_helper;
+mod metrics;
+mod migrations;
 mod models;
+mod parameter_memory;
 mod progressive_matching;
 mod prompts;
+mod rate_limit;
 mod sentence_analysis;
 mod sentence_service;
+mod trace;
+mod usage;
 mod utils;
 
 pub mod analysis;
 pub mod workflow;
 
-use crate::models::config::load_models_config;
+use crate::models::config::{load_http_config, load_models_config, load_tokenizers_config};
 use crate::models::providers::{create_provider, ModelProvider, ProviderConfig};
 use clap::Parser;
-use cli::{display_custom_help, handle_cli, Cli};
+use cli::{display_custom_help, handle_cli, handle_command, Cli};
 use endpoint_client::get_default_api_url;
 use graflog::app_log;
 use graflog::init_logging;
@@ -130,7 +142,13 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         }
     };
 
+    if let Some(command) = cli.command {
+        return handle_command(command).await;
+    }
+
     let _models_config = load_models_config().await?;
+    utils::tokenizer::load_configured(&load_tokenizers_config().await?);
+    models::providers::http::configure(&load_http_config().await?);
 
     let provider: Box<dyn ModelProvider> = match create_provider_with_key(&cli.provider) {
         Ok(provider) => provider,
@@ -140,7 +158,8 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
         }
     };
 
-    let provider_arc: Arc<dyn ModelProvider> = Arc::from(provider);
+    let provider_arc: Arc<dyn ModelProvider> =
+        Arc::new(metrics::InstrumentedProvider::new(Arc::from(provider)));
 
     // Get API URL from CLI or config
     let api_url = if let Some(url) = cli.api.clone() {
@@ -0,0 +1,362 @@
+// src/metrics.rs - Prometheus metrics and the /metrics HTTP endpoint
+use crate::app_log;
+use crate::models::config::MetricsConfig;
+use crate::models::providers::error::ProviderError;
+use crate::models::providers::{GenerationResult, ModelConfig, ModelProvider};
+use crate::usage;
+use crate::workflow::classify_intent::IntentType;
+use async_trait::async_trait;
+use axum::http::header::CONTENT_TYPE;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    register_counter_vec_with_registry, register_histogram_vec_with_registry, CounterVec, Encoder,
+    HistogramVec, Registry, TextEncoder,
+};
+use std::error::Error;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+struct Metrics {
+    registry: Registry,
+    requests: CounterVec,
+    endpoint_matches: CounterVec,
+    analysis_retries: CounterVec,
+    step_duration: HistogramVec,
+    provider_duration: HistogramVec,
+    provider_errors: CounterVec,
+    provider_retries: CounterVec,
+    tokens: CounterVec,
+    progressive_matches: CounterVec,
+    confirmations: CounterVec,
+    experiment_requests: CounterVec,
+    experiment_tokens: CounterVec,
+    language_detections: CounterVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry =
+        Registry::new_custom(Some("semantic".to_string()), None).expect("valid metrics prefix");
+
+    Metrics {
+        requests: register_counter_vec_with_registry!(
+            "requests_total",
+            "Analyzed sentences by classified intent ('error' when analysis failed)",
+            &["intent"],
+            registry
+        )
+        .unwrap(),
+        endpoint_matches: register_counter_vec_with_registry!(
+            "endpoint_matches_total",
+            "Endpoint selection outcomes: matched, fallback, no_match, unknown_id",
+            &["result"],
+            registry
+        )
+        .unwrap(),
+        analysis_retries: register_counter_vec_with_registry!(
+            "analysis_retries_total",
+            "Actionable analysis attempts retried by analyze_with_retry",
+            &["reason"],
+            registry
+        )
+        .unwrap(),
+        step_duration: register_histogram_vec_with_registry!(
+            "workflow_step_duration_seconds",
+            "Workflow step latency including retries",
+            &["step", "outcome"],
+            vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0],
+            registry
+        )
+        .unwrap(),
+        provider_duration: register_histogram_vec_with_registry!(
+            "provider_request_duration_seconds",
+            "LLM provider call latency",
+            &["provider", "model"],
+            vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0],
+            registry
+        )
+        .unwrap(),
+        provider_errors: register_counter_vec_with_registry!(
+            "provider_errors_total",
+            "Failed LLM provider calls by error type",
+            &["provider", "kind"],
+            registry
+        )
+        .unwrap(),
+        provider_retries: register_counter_vec_with_registry!(
+            "provider_retries_total",
+            "LLM provider requests sent again after a retryable error",
+            &["provider", "kind"],
+            registry
+        )
+        .unwrap(),
+        tokens: register_counter_vec_with_registry!(
+            "tokens_total",
+            "Tokens consumed by model and direction (input/output)",
+            &["model", "direction"],
+            registry
+        )
+        .unwrap(),
+        progressive_matches: register_counter_vec_with_registry!(
+            "progressive_matches_total",
+            "Progressive matching lifecycle: started, continued, completed, switched, resumed, abandoned, expired",
+            &["outcome"],
+            registry
+        )
+        .unwrap(),
+        confirmations: register_counter_vec_with_registry!(
+            "confirmations_total",
+            "Confirmations of risky endpoints: requested, confirmed, declined, edited, dropped",
+            &["outcome"],
+            registry
+        )
+        .unwrap(),
+        experiment_requests: register_counter_vec_with_registry!(
+            "prompt_experiment_requests_total",
+            "Requests per prompt experiment variant by outcome: matched, answered, failed",
+            &["experiment", "variant", "outcome"],
+            registry
+        )
+        .unwrap(),
+        experiment_tokens: register_counter_vec_with_registry!(
+            "prompt_experiment_tokens_total",
+            "Tokens consumed by prompts of an experiment variant (input/output)",
+            &["experiment", "variant", "direction"],
+            registry
+        )
+        .unwrap(),
+        language_detections: register_counter_vec_with_registry!(
+            "language_detections_total",
+            "Help request language detections by method: local, llm",
+            &["method"],
+            registry
+        )
+        .unwrap(),
+        registry,
+    }
+});
+
+pub fn record_request(intent: Option<&IntentType>) {
+    let label = match intent {
+        Some(IntentType::ActionableRequest) => "actionable_request",
+        Some(IntentType::GeneralQuestion) => "general_question",
+        Some(IntentType::HelpRequest) => "help_request",
+        None => "error",
+    };
+    METRICS.requests.with_label_values(&[label]).inc();
+}
+
+/// `result` is one of `matched`, `fallback`, `no_match` or `unknown_id`
+pub fn record_endpoint_match(result: &str) {
+    METRICS.endpoint_matches.with_label_values(&[result]).inc();
+}
+
+pub fn record_analysis_retry(reason: &str) {
+    METRICS.analysis_retries.with_label_values(&[reason]).inc();
+}
+
+pub fn record_step(step: &str, ok: bool, elapsed: Duration) {
+    METRICS
+        .step_duration
+        .with_label_values(&[step, if ok { "ok" } else { "error" }])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// `outcome` is one of `started`, `continued`, `completed`, `switched`, `resumed`,
+/// `abandoned` or `expired`
+pub fn record_progressive_match(outcome: &str) {
+    METRICS
+        .progressive_matches
+        .with_label_values(&[outcome])
+        .inc();
+}
+
+pub fn record_progressive_matches_expired(count: u64) {
+    METRICS
+        .progressive_matches
+        .with_label_values(&["expired"])
+        .inc_by(count as f64);
+}
+
+/// `outcome` is one of `requested`, `confirmed`, `declined`, `edited` or `dropped`
+/// (the next message was not a reply)
+pub fn record_confirmation(outcome: &str) {
+    METRICS.confirmations.with_label_values(&[outcome]).inc();
+}
+
+pub fn record_experiment_outcome(experiment: &str, variant: &str, outcome: &str) {
+    METRICS
+        .experiment_requests
+        .with_label_values(&[experiment, variant, outcome])
+        .inc();
+}
+
+/// `method` is `local` or `llm` (the local identifier was not confident enough)
+pub fn record_language_detection(method: &str) {
+    METRICS
+        .language_detections
+        .with_label_values(&[method])
+        .inc();
+}
+
+/// Coarse error type for the provider error counter
+fn provider_error_kind(error: &(dyn Error + Send + Sync + 'static)) -> &'static str {
+    if let Some(e) = error.downcast_ref::<ProviderError>() {
+        return e.kind();
+    }
+    if error.is::<serde_json::Error>() {
+        return "parse";
+    }
+    "other"
+}
+
+/// A provider call failed with a retryable error and is sent again
+pub fn record_provider_retry(provider: &str, kind: &str) {
+    METRICS
+        .provider_retries
+        .with_label_values(&[provider, kind])
+        .inc();
+}
+
+/// Render all metrics in the Prometheus text format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&METRICS.registry.gather(), &mut buffer) {
+        app_log!(error, "Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Serve `GET /metrics` until the process stops
+pub async fn serve(config: &MetricsConfig) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let addr = format!("{}:{}", config.address, config.port);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    app_log!(
+        info,
+        "Serving Prometheus metrics on http://{}/metrics",
+        addr
+    );
+
+    let app = Router::new().route(
+        "/metrics",
+        get(|| async {
+            (
+                [(CONTENT_TYPE, TextEncoder::new().format_type().to_string())],
+                render(),
+            )
+        }),
+    );
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+/// Provider decorator recording latency, errors and token usage
+pub struct InstrumentedProvider {
+    inner: Arc<dyn ModelProvider>,
+}
+
+impl InstrumentedProvider {
+    pub fn new(inner: Arc<dyn ModelProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for InstrumentedProvider {
+    async fn generate(
+        &self,
+        prompt: &str,
+        config: &ModelConfig,
+    ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>> {
+        let provider = self.inner.get_model_name();
+        let model = config.model_for(provider);
+
+        let started = Instant::now();
+        let result = self.inner.generate(prompt, config).await;
+        METRICS
+            .provider_duration
+            .with_label_values(&[provider, model])
+            .observe(started.elapsed().as_secs_f64());
+
+        match &result {
+            Ok(generation) => {
+                METRICS
+                    .tokens
+                    .with_label_values(&[model, "input"])
+                    .inc_by(generation.usage.input_tokens as f64);
+                METRICS
+                    .tokens
+                    .with_label_values(&[model, "output"])
+                    .inc_by(generation.usage.output_tokens as f64);
+                if let Some((experiment, variant)) = usage::current_variant() {
+                    METRICS
+                        .experiment_tokens
+                        .with_label_values(&[&experiment, &variant, "input"])
+                        .inc_by(generation.usage.input_tokens as f64);
+                    METRICS
+                        .experiment_tokens
+                        .with_label_values(&[&experiment, &variant, "output"])
+                        .inc_by(generation.usage.output_tokens as f64);
+                }
+            }
+            Err(e) => {
+                METRICS
+                    .provider_errors
+                    .with_label_values(&[provider, provider_error_kind(e.as_ref())])
+                    .inc();
+            }
+        }
+
+        result
+    }
+
+    fn get_model_name(&self) -> &str {
+        self.inner.get_model_name()
+    }
+
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_recorded_series() {
+        record_request(Some(&IntentType::HelpRequest));
+        record_endpoint_match("no_match");
+        record_step("endpoint_matching", true, Duration::from_millis(120));
+
+        let text = render();
+        assert!(text.contains("semantic_requests_total{intent=\"help_request\"}"));
+        assert!(text.contains("semantic_endpoint_matches_total{result=\"no_match\"}"));
+        assert!(text.contains(
+            "semantic_workflow_step_duration_seconds_bucket{outcome=\"ok\",step=\"endpoint_matching\""
+        ));
+    }
+
+    #[test]
+    fn test_provider_error_kind() {
+        let parse_error: Box<dyn Error + Send + Sync> =
+            serde_json::from_str::<serde_json::Value>("{")
+                .unwrap_err()
+                .into();
+        assert_eq!(provider_error_kind(parse_error.as_ref()), "parse");
+
+        let status_error: Box<dyn Error + Send + Sync> = Box::new(ProviderError::RateLimited {
+            provider: "Claude",
+            retry_after: None,
+            message: "slow down".to_string(),
+        });
+        assert_eq!(provider_error_kind(status_error.as_ref()), "rate_limited");
+
+        let empty: Box<dyn Error + Send + Sync> =
+            Box::new(ProviderError::invalid_response("Cohere", "empty content"));
+        assert_eq!(provider_error_kind(empty.as_ref()), "invalid_response");
+
+        let other: Box<dyn Error + Send + Sync> = "Unexpected response shape".into();
+        assert_eq!(provider_error_kind(other.as_ref()), "other");
+    }
+}
@@ -0,0 +1,205 @@
+// src/migrations.rs - Versioned schema migrations of the PostgreSQL stores
+use crate::app_log;
+use crate::models::config::load_database_config;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::error::Error;
+use tokio_postgres::{Client, Transaction};
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration, in the order they are applied. Applied migrations are never
+/// edited; a schema change is a new file in `migrations/` and a new entry here.
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "progressive_matching",
+        sql: include_str!("../migrations/0001_progressive_matching.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "usage_ledger",
+        sql: include_str!("../migrations/0002_usage_ledger.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "rate_limit_counters",
+        sql: include_str!("../migrations/0003_rate_limit_counters.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "parameter_history",
+        sql: include_str!("../migrations/0004_parameter_history.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "parameter_memory",
+        sql: include_str!("../migrations/0005_parameter_memory.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "match_owner",
+        sql: include_str!("../migrations/0006_match_owner.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "usage_ledger_rpc",
+        sql: include_str!("../migrations/0007_usage_ledger_rpc.sql"),
+    },
+];
+
+/// Held for the duration of a run, so instances starting together migrate once
+const ADVISORY_LOCK_ID: i64 = 0x7365_6d61_6e74_6963;
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: &'static str,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+async fn applied_versions(
+    transaction: &Transaction<'_>,
+) -> Result<HashMap<i64, DateTime<Utc>>, Box<dyn Error + Send + Sync>> {
+    transaction
+        .batch_execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )
+            "#,
+        )
+        .await?;
+
+    let applied: HashMap<i64, DateTime<Utc>> = transaction
+        .query("SELECT version, applied_at FROM schema_migrations", &[])
+        .await?
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+
+    for version in applied.keys() {
+        if !MIGRATIONS.iter().any(|m| m.version == *version) {
+            app_log!(
+                warn,
+                "Database has migration {} that this build does not know about",
+                version
+            );
+        }
+    }
+    Ok(applied)
+}
+
+fn pending(applied: &HashMap<i64, DateTime<Utc>>) -> Vec<&'static Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(|m| !applied.contains_key(&m.version))
+        .collect()
+}
+
+/// Every known migration and when it was applied, if it was
+pub async fn status(
+    client: &mut Client,
+) -> Result<Vec<MigrationStatus>, Box<dyn Error + Send + Sync>> {
+    // Rolled back on drop: reading the status leaves the database untouched
+    let transaction = client.transaction().await?;
+    let applied = applied_versions(&transaction).await?;
+    Ok(MIGRATIONS
+        .iter()
+        .map(|m| MigrationStatus {
+            version: m.version,
+            name: m.name,
+            applied_at: applied.get(&m.version).copied(),
+        })
+        .collect())
+}
+
+/// Apply the pending migrations in one transaction and return them. With `dry_run`,
+/// only return what would be applied.
+pub async fn migrate(
+    client: &mut Client,
+    dry_run: bool,
+) -> Result<Vec<&'static Migration>, Box<dyn Error + Send + Sync>> {
+    let transaction = client.transaction().await?;
+    transaction
+        .execute("SELECT pg_advisory_xact_lock($1)", &[&ADVISORY_LOCK_ID])
+        .await?;
+    let pending = pending(&applied_versions(&transaction).await?);
+    if dry_run || pending.is_empty() {
+        return Ok(pending);
+    }
+
+    for migration in &pending {
+        app_log!(
+            info,
+            "Applying migration {} ({})",
+            migration.version,
+            migration.name
+        );
+        transaction
+            .batch_execute(migration.sql)
+            .await
+            .map_err(|e| {
+                format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version, migration.name, e
+                )
+            })?;
+        transaction
+            .execute(
+                "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+    }
+    transaction.commit().await?;
+    Ok(pending)
+}
+
+/// Called by each store before use: applies pending migrations when
+/// `database.migrate_on_startup` is set, and otherwise refuses an outdated schema
+pub async fn prepare(client: &mut Client) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config = load_database_config().await.unwrap_or_default();
+    if config.migrate_on_startup {
+        let applied = migrate(client, false).await?;
+        if !applied.is_empty() {
+            app_log!(info, "Applied {} database migration(s)", applied.len());
+        }
+        return Ok(());
+    }
+
+    let outdated = status(client)
+        .await?
+        .iter()
+        .filter(|m| m.applied_at.is_none())
+        .count();
+    if outdated > 0 {
+        return Err(format!(
+            "Database schema is out of date ({outdated} pending migration(s)), run `semantic db migrate`"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered_and_pending_skips_applied() {
+        assert!(MIGRATIONS.windows(2).all(|w| w[0].version < w[1].version));
+
+        let applied = HashMap::from([(1, Utc::now())]);
+        let versions: Vec<i64> = pending(&applied).iter().map(|m| m.version).collect();
+        let expected: Vec<i64> = MIGRATIONS[1..].iter().map(|m| m.version).collect();
+        assert_eq!(versions, expected);
+        assert_eq!(versions.first(), Some(&2));
+        assert_eq!(pending(&HashMap::new()).len(), MIGRATIONS.len());
+    }
+}
@@ -1,7 +1,9 @@
 // src/models/config.rs
 use crate::app_log;
-use crate::models::ModelsConfig;
+use crate::models::{ModelsConfig, RiskLevel};
+use crate::workflow::WorkflowsConfig;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::error::Error;
 
 use std::env;
@@ -36,12 +38,516 @@ impl Default for AnalysisConfig {
     }
 }
 
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AuthConfig {
+    /// When false, the caller identity is taken from the `email` metadata header as before
+    #[serde(default)]
+    pub enabled: bool,
+    pub jwt: Option<JwtConfig>,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct JwtConfig {
+    /// Name of the environment variable holding the HS256 shared secret
+    pub hs256_secret_env: Option<String>,
+    /// Local JWKS file with the RS256 public keys
+    pub jwks_path: Option<String>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    #[serde(default = "default_email_claim")]
+    pub email_claim: String,
+    #[serde(default = "default_tenant_claim")]
+    pub tenant_claim: String,
+    #[serde(default = "default_leeway_secs")]
+    pub leeway_secs: u64,
+}
+
+fn default_email_claim() -> String {
+    "email".to_string()
+}
+
+fn default_tenant_claim() -> String {
+    "tenant".to_string()
+}
+
+fn default_leeway_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ApiKeyConfig {
+    /// Name of the environment variable holding the key value
+    pub key_env: String,
+    pub email: String,
+    pub tenant: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CounterBackend {
+    #[default]
+    Memory,
+    Postgres,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Where counters live: `memory` (per process) or `postgres` (shared, uses DATABASE_URL)
+    #[serde(default)]
+    pub backend: CounterBackend,
+    pub per_email: Option<LimitRule>,
+    pub per_client: Option<LimitRule>,
+}
+
+/// Limits applied to one subject (an email or a client id); unset values are unlimited
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct LimitRule {
+    pub requests_per_minute: Option<u64>,
+    /// Input + output tokens per UTC day
+    pub daily_tokens: Option<u64>,
+    /// Input + output tokens per UTC calendar month
+    pub monthly_tokens: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct UsageConfig {
+    /// Record every LLM call to the `usage_ledger` table (uses DATABASE_URL)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Prices keyed by model name, e.g. `command-r7b-12-2024`
+    #[serde(default)]
+    pub prices: HashMap<String, ModelPrice>,
+}
+
+/// Price in USD per million tokens
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_metrics_address")]
+    pub address: String,
+    #[serde(default = "default_metrics_port")]
+    pub port: u16,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: default_metrics_address(),
+            port: default_metrics_port(),
+        }
+    }
+}
+
+fn default_metrics_address() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_metrics_port() -> u16 {
+    9464
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct HealthConfig {
+    /// Seconds between background probes of the endpoint service, database and provider
+    #[serde(default = "default_probe_interval_secs")]
+    pub probe_interval_secs: u64,
+    #[serde(default = "default_probe_timeout_secs")]
+    pub probe_timeout_secs: u64,
+    /// Probe the LLM provider API (a model listing call, no tokens consumed)
+    #[serde(default = "default_true")]
+    pub probe_provider: bool,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval_secs: default_probe_interval_secs(),
+            probe_timeout_secs: default_probe_timeout_secs(),
+            probe_provider: true,
+        }
+    }
+}
+
+fn default_probe_interval_secs() -> u64 {
+    30
+}
+
+fn default_probe_timeout_secs() -> u64 {
+    5
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct HttpConfig {
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: u64,
+    /// Whole request, including reading the generated response
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    #[serde(default = "default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    #[serde(default)]
+    pub retry: ProviderRetryConfig,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: default_connect_timeout_ms(),
+            request_timeout_ms: default_request_timeout_ms(),
+            pool_idle_timeout_secs: default_pool_idle_timeout_secs(),
+            pool_max_idle_per_host: default_pool_max_idle_per_host(),
+            retry: ProviderRetryConfig::default(),
+        }
+    }
+}
+
+/// Retries of rate limited, overloaded, timed out or unreachable provider calls
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProviderRetryConfig {
+    /// Attempts including the first one
+    #[serde(default = "default_provider_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Upper bound of one wait; a longer `Retry-After` fails the call instead
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl Default for ProviderRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_provider_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+fn default_connect_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_request_timeout_ms() -> u64 {
+    60000
+}
+
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_pool_max_idle_per_host() -> usize {
+    16
+}
+
+fn default_provider_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    10000
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TraceConfig {
+    /// Trace every request, not only those sent with `debug: true`
+    #[serde(default)]
+    pub record_all: bool,
+    /// Traces kept in memory for `GetTrace`, oldest evicted first
+    #[serde(default = "default_trace_capacity")]
+    pub capacity: usize,
+    #[serde(default = "default_trace_retention_secs")]
+    pub retention_secs: u64,
+    /// Prompts and raw responses longer than this are truncated
+    #[serde(default = "default_trace_max_text_chars")]
+    pub max_text_chars: usize,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            record_all: false,
+            capacity: default_trace_capacity(),
+            retention_secs: default_trace_retention_secs(),
+            max_text_chars: default_trace_max_text_chars(),
+        }
+    }
+}
+
+fn default_trace_capacity() -> usize {
+    500
+}
+
+fn default_trace_retention_secs() -> u64 {
+    3600
+}
+
+fn default_trace_max_text_chars() -> usize {
+    20_000
+}
+
+/// Vocabulary used to count tokens exactly for one provider
+#[derive(Debug, Deserialize, Clone)]
+pub struct TokenizerConfig {
+    pub format: TokenizerFormat,
+    pub path: String,
+    /// Pre-tokenization regex, defaults to the usual one for the format
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenizerFormat {
+    /// `.tiktoken` rank file (OpenAI-style models)
+    Tiktoken,
+    /// `tokenizer.json` with a byte-level BPE model (Claude, Cohere)
+    Huggingface,
+}
+
+/// Context windows used to fit prompts before they are sent
+#[derive(Debug, Deserialize, Clone)]
+pub struct PromptBudgetConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Context window in tokens, keyed by model name (e.g. `deepseek-chat`) or provider
+    #[serde(default)]
+    pub context_windows: HashMap<String, u32>,
+    #[serde(default = "default_context_window")]
+    pub default_context_window: u32,
+    /// Tokens kept free to absorb counting error when no tokenizer is loaded
+    #[serde(default = "default_safety_margin")]
+    pub safety_margin: u32,
+    /// Below this many output tokens a prompt is refused instead of sent
+    #[serde(default = "default_min_output_tokens")]
+    pub min_output_tokens: u32,
+}
+
+impl Default for PromptBudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            context_windows: HashMap::new(),
+            default_context_window: default_context_window(),
+            safety_margin: default_safety_margin(),
+            min_output_tokens: default_min_output_tokens(),
+        }
+    }
+}
+
+impl PromptBudgetConfig {
+    pub fn context_window(&self, model: &str, provider: &str) -> u32 {
+        self.context_windows
+            .get(model)
+            .or_else(|| self.context_windows.get(provider))
+            .copied()
+            .unwrap_or(self.default_context_window)
+    }
+}
+
+/// Local language identification, see `utils/language.rs`
+#[derive(Debug, Deserialize, Clone)]
+pub struct LanguageDetectionConfig {
+    /// Below this confidence the language is treated as unknown
+    #[serde(default = "default_min_confidence")]
+    pub min_confidence: f32,
+    /// Ask the LLM for help requests the local identifier is unsure about
+    #[serde(default = "default_true")]
+    pub llm_fallback: bool,
+}
+
+impl Default for LanguageDetectionConfig {
+    fn default() -> Self {
+        Self {
+            min_confidence: default_min_confidence(),
+            llm_fallback: true,
+        }
+    }
+}
+
+/// Follow-ups of incomplete requests, see `progressive_matching.rs`
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProgressiveMatchingConfig {
+    /// Ask the LLM whether a follow-up continues the pending request, drops it or
+    /// switches to another one. Without it only explicit cancellations are detected.
+    #[serde(default = "default_true")]
+    pub llm_topic_detection: bool,
+    /// Seconds without an update after which a pending request expires
+    #[serde(default = "default_progressive_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Seconds between background sweeps of expired requests
+    #[serde(default = "default_sweep_interval_secs")]
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for ProgressiveMatchingConfig {
+    fn default() -> Self {
+        Self {
+            llm_topic_detection: true,
+            ttl_secs: default_progressive_ttl_secs(),
+            sweep_interval_secs: default_sweep_interval_secs(),
+        }
+    }
+}
+
+/// Confirmation of endpoints that change or delete data, see `analysis/confirmation.rs`
+#[derive(Debug, Deserialize, Clone)]
+pub struct ConfirmationConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Endpoints at this risk level or above are confirmed before use
+    #[serde(default = "default_confirmation_risk_level")]
+    pub min_risk_level: RiskLevel,
+    /// Seconds after which an unanswered confirmation is dropped
+    #[serde(default = "default_confirmation_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Default for ConfirmationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_risk_level: default_confirmation_risk_level(),
+            ttl_secs: default_confirmation_ttl_secs(),
+        }
+    }
+}
+
+fn default_confirmation_risk_level() -> RiskLevel {
+    RiskLevel::Medium
+}
+
+fn default_confirmation_ttl_secs() -> u64 {
+    600
+}
+
+/// Parameter values remembered per user, see `parameter_memory/mod.rs`
+#[derive(Debug, Deserialize, Clone)]
+pub struct ParameterMemoryConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Where values live: `memory` (per process) or `postgres` (shared, uses DATABASE_URL)
+    #[serde(default)]
+    pub backend: CounterBackend,
+    /// Learn values from completed matches; without it only profile values are used
+    #[serde(default = "default_true")]
+    pub learn: bool,
+    /// Completed matches in a row that must give a value before it fills in others
+    #[serde(default = "default_min_uses")]
+    pub min_uses: u32,
+}
+
+impl Default for ParameterMemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            backend: CounterBackend::default(),
+            learn: true,
+            min_uses: default_min_uses(),
+        }
+    }
+}
+
+fn default_min_uses() -> u32 {
+    2
+}
+
+/// Questions for missing parameters, see `analysis/missing_fields.rs`
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MissingFieldsConfig {
+    /// Have the LLM rewrite the question so it reads naturally in the user's
+    /// language, also for languages without templates
+    #[serde(default)]
+    pub llm_polish: bool,
+}
+
+/// Schema of the PostgreSQL stores, see `migrations.rs`
+#[derive(Debug, Deserialize, Clone)]
+pub struct DatabaseConfig {
+    /// Apply pending migrations when a store opens the database. Without it a store
+    /// refuses an outdated schema and `semantic db migrate` must be run first.
+    #[serde(default = "default_true")]
+    pub migrate_on_startup: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            migrate_on_startup: true,
+        }
+    }
+}
+
+fn default_progressive_ttl_secs() -> u64 {
+    86_400
+}
+
+fn default_sweep_interval_secs() -> u64 {
+    300
+}
+
+fn default_min_confidence() -> f32 {
+    0.9
+}
+
+fn default_context_window() -> u32 {
+    32_000
+}
+
+fn default_safety_margin() -> u32 {
+    256
+}
+
+fn default_min_output_tokens() -> u32 {
+    256
+}
+
+fn default_true() -> bool {
+    true
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub models: ModelsConfig,
     pub server: ServerConfig,
     pub endpoint_client: EndpointClientConfig,
     pub analysis: Option<AnalysisConfig>, // Optional for backward compatibility
+    pub auth: Option<AuthConfig>,
+    pub rate_limits: Option<RateLimitConfig>,
+    pub usage: Option<UsageConfig>,
+    pub metrics: Option<MetricsConfig>,
+    pub health: Option<HealthConfig>,
+    pub workflows: Option<WorkflowsConfig>,
+    pub tracing: Option<TraceConfig>,
+    /// Keyed by provider: `claude`, `cohere` or `deepseek`
+    pub tokenizers: Option<HashMap<String, TokenizerConfig>>,
+    pub prompt_budget: Option<PromptBudgetConfig>,
+    pub language_detection: Option<LanguageDetectionConfig>,
+    pub http: Option<HttpConfig>,
+    pub progressive_matching: Option<ProgressiveMatchingConfig>,
+    pub database: Option<DatabaseConfig>,
+    pub confirmation: Option<ConfirmationConfig>,
+    pub parameter_memory: Option<ParameterMemoryConfig>,
+    pub missing_fields: Option<MissingFieldsConfig>,
 }
 
 pub async fn load_models_config() -> Result<ModelsConfig, Box<dyn Error + Send + Sync>> {
@@ -101,3 +607,233 @@ pub async fn load_analysis_config() -> Result<AnalysisConfig, Box<dyn Error + Se
 
     Ok(analysis_config)
 }
+
+// Load authentication configuration from config file
+pub async fn load_auth_config() -> Result<AuthConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded auth configuration from: {}", config_path);
+
+    let auth_config = config.auth.unwrap_or_default();
+    app_log!(
+        debug,
+        "Auth config: enabled={}, jwt={}, api_keys={}",
+        auth_config.enabled,
+        auth_config.jwt.is_some(),
+        auth_config.api_keys.len()
+    );
+
+    Ok(auth_config)
+}
+
+// Load rate limit and token quota configuration from config file
+pub async fn load_rate_limit_config() -> Result<RateLimitConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded rate limit configuration from: {}", config_path);
+
+    let rate_limit_config = config.rate_limits.unwrap_or_default();
+    app_log!(debug, "Rate limit config: {:#?}", rate_limit_config);
+
+    Ok(rate_limit_config)
+}
+
+pub async fn load_usage_config() -> Result<UsageConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded usage configuration from: {}", config_path);
+
+    let usage_config = config.usage.unwrap_or_default();
+    app_log!(debug, "Usage config: {:#?}", usage_config);
+
+    Ok(usage_config)
+}
+
+pub async fn load_metrics_config() -> Result<MetricsConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded metrics configuration from: {}", config_path);
+
+    let metrics_config = config.metrics.unwrap_or_default();
+    app_log!(debug, "Metrics config: {:#?}", metrics_config);
+
+    Ok(metrics_config)
+}
+
+pub async fn load_trace_config() -> Result<TraceConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded tracing configuration from: {}", config_path);
+
+    let trace_config = config.tracing.unwrap_or_default();
+    app_log!(debug, "Tracing config: {:#?}", trace_config);
+
+    Ok(trace_config)
+}
+
+pub async fn load_tokenizers_config(
+) -> Result<HashMap<String, TokenizerConfig>, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded tokenizers configuration from: {}", config_path);
+
+    Ok(config.tokenizers.unwrap_or_default())
+}
+
+pub async fn load_prompt_budget_config(
+) -> Result<PromptBudgetConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded prompt budget configuration from: {}", config_path);
+
+    Ok(config.prompt_budget.unwrap_or_default())
+}
+
+pub async fn load_progressive_matching_config(
+) -> Result<ProgressiveMatchingConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(
+        debug,
+        "Loaded progressive matching configuration from: {}",
+        config_path
+    );
+
+    Ok(config.progressive_matching.unwrap_or_default())
+}
+
+pub async fn load_confirmation_config(
+) -> Result<ConfirmationConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded confirmation configuration from: {}", config_path);
+
+    Ok(config.confirmation.unwrap_or_default())
+}
+
+pub async fn load_parameter_memory_config(
+) -> Result<ParameterMemoryConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(
+        debug,
+        "Loaded parameter memory configuration from: {}",
+        config_path
+    );
+
+    Ok(config.parameter_memory.unwrap_or_default())
+}
+
+pub async fn load_missing_fields_config(
+) -> Result<MissingFieldsConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(
+        debug,
+        "Loaded missing fields configuration from: {}",
+        config_path
+    );
+
+    Ok(config.missing_fields.unwrap_or_default())
+}
+
+pub async fn load_database_config() -> Result<DatabaseConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded database configuration from: {}", config_path);
+
+    Ok(config.database.unwrap_or_default())
+}
+
+pub async fn load_language_detection_config(
+) -> Result<LanguageDetectionConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(
+        debug,
+        "Loaded language detection configuration from: {}",
+        config_path
+    );
+
+    Ok(config.language_detection.unwrap_or_default())
+}
+
+pub async fn load_http_config() -> Result<HttpConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded HTTP configuration from: {}", config_path);
+
+    let http_config = config.http.unwrap_or_default();
+    app_log!(debug, "HTTP config: {:#?}", http_config);
+
+    Ok(http_config)
+}
+
+pub async fn load_health_config() -> Result<HealthConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded health configuration from: {}", config_path);
+
+    let health_config = config.health.unwrap_or_default();
+    app_log!(debug, "Health config: {:#?}", health_config);
+
+    Ok(health_config)
+}
+
+pub async fn load_workflows_config() -> Result<WorkflowsConfig, Box<dyn Error + Send + Sync>> {
+    let config_path = get_config_path();
+    let config_str = tokio::fs::read_to_string(&config_path).await?;
+    let config: Config = serde_yaml::from_str(&config_str)?;
+
+    app_log!(debug, "Loaded workflow definitions from: {}", config_path);
+
+    Ok(config.workflows.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::registry::StepRegistry;
+
+    #[test]
+    fn test_shipped_config_parses() {
+        let config_str =
+            std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/config.yaml")).unwrap();
+        let config: Config = serde_yaml::from_str(&config_str).unwrap();
+
+        let workflows = config.workflows.unwrap_or_default();
+        StepRegistry::with_builtin_steps()
+            .validate_all(&workflows)
+            .unwrap();
+    }
+}
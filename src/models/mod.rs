@@ -4,15 +4,19 @@ pub mod providers;
 pub use providers::ModelsConfig;
 use serde::{Deserialize, Serialize};
 
+use crate::analysis::missing_fields;
+use crate::parameter_memory::{ParameterDefaults, ValueSource};
 use crate::workflow::classify_intent::IntentType;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MissingField {
     pub name: String,
     pub description: String,
+    #[serde(default)]
+    pub examples: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Endpoint {
     pub id: String,
     pub text: String,
@@ -20,23 +24,26 @@ pub struct Endpoint {
     pub parameters: Vec<EndpointParameter>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct EndpointParameter {
     pub name: String,
     pub description: String,
     pub required: Option<bool>,
     pub alternatives: Option<Vec<String>>,
+    /// Sample values from the catalog, shown when the parameter is asked for
+    #[serde(default)]
+    pub examples: Option<Vec<String>>,
     pub semantic_value: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ConfigFile {
     pub endpoints: Vec<Endpoint>,
 }
 
 impl ConfigFile {}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 pub struct EnhancedEndpoint {
     pub id: String,
     pub name: String,
@@ -49,6 +56,43 @@ pub struct EnhancedEndpoint {
     pub api_group_id: String,
     pub api_group_name: String,
     pub parameters: Vec<EndpointParameter>,
+    pub risk_level: RiskLevel,
+}
+
+/// How much harm an endpoint can do, from the catalog or else derived from its verb.
+/// Endpoints at or above `confirmation.min_risk_level` are confirmed before use.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    /// Reads only
+    #[default]
+    Low,
+    /// Creates or changes data
+    Medium,
+    /// Deletes data
+    High,
+}
+
+impl RiskLevel {
+    pub fn from_verb(verb: &str) -> Self {
+        match verb.to_uppercase().as_str() {
+            "DELETE" => RiskLevel::High,
+            "POST" | "PUT" | "PATCH" => RiskLevel::Medium,
+            _ => RiskLevel::Low,
+        }
+    }
+
+    /// `low`, `medium` or `high`, as set in the catalog
+    pub fn parse(level: &str) -> Option<Self> {
+        match level.trim().to_lowercase().as_str() {
+            "low" => Some(RiskLevel::Low),
+            "medium" => Some(RiskLevel::Medium),
+            "high" => Some(RiskLevel::High),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -72,7 +116,7 @@ impl From<&crate::models::providers::token_counter::TokenUsage> for UsageInfo {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 pub struct EnhancedAnalysisResult {
     pub endpoint_id: String,
     pub endpoint_name: String,
@@ -92,6 +136,38 @@ pub struct EnhancedAnalysisResult {
     pub total_output_tokens: u32,
     pub usage: UsageInfo,
     pub intent: IntentType,
+    /// Values of a pending request that this message corrected or removed
+    pub changed_parameters: Vec<ParameterChange>,
+    pub risk_level: RiskLevel,
+    /// Set when the matched endpoint must be confirmed before use, see `confirmation.rs`
+    pub confirmation: Option<Confirmation>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum ConfirmationStatus {
+    /// The client must not call the endpoint before the user confirms
+    Required,
+    Confirmed,
+    Declined,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Confirmation {
+    pub status: ConfirmationStatus,
+    /// What the endpoint will do, with which values
+    pub summary: String,
+}
+
+impl EnhancedAnalysisResult {
+    /// Account an LLM call made outside the workflow, e.g. intent classification
+    pub fn add_usage(&mut self, usage: &crate::models::providers::token_counter::TokenUsage) {
+        self.usage.input_tokens += usage.input_tokens;
+        self.usage.output_tokens += usage.output_tokens;
+        self.usage.total_tokens += usage.total_tokens;
+        self.usage.estimated |= usage.estimated;
+        self.total_input_tokens = self.usage.input_tokens;
+        self.total_output_tokens = self.usage.output_tokens;
+    }
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -99,6 +175,17 @@ pub struct ParameterMatch {
     pub name: String,
     pub description: String,
     pub value: Option<String>,
+    /// Set when the value was not given but filled in from the user's remembered values
+    pub default_source: Option<ValueSource>,
+}
+
+/// A parameter value the user changed after giving it. `value` is `None` when it
+/// was removed.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ParameterChange {
+    pub name: String,
+    pub previous: String,
+    pub value: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -273,6 +360,7 @@ impl MatchingInfo {
             .map(|r| MissingField {
                 name: r.endpoint_param.name.clone(),
                 description: r.endpoint_param.description.clone(),
+                examples: r.endpoint_param.examples.clone().unwrap_or_default(),
             })
             .collect();
 
@@ -284,6 +372,7 @@ impl MatchingInfo {
             .map(|r| MissingField {
                 name: r.endpoint_param.name.clone(),
                 description: r.endpoint_param.description.clone(),
+                examples: r.endpoint_param.examples.clone().unwrap_or_default(),
             })
             .collect();
 
@@ -346,66 +435,63 @@ impl MatchingInfo {
         }
     }
 
-    /// Generate a natural language prompt for missing fields
-    pub fn generate_user_prompt(&self, endpoint_name: &str) -> Option<String> {
-        if self.missing_required_fields.is_empty() {
-            return None;
+    /// Like `compute`, after filling in missing required parameters from `defaults`.
+    /// Defaults are remembered by parameter name across endpoints, so endpoints of
+    /// `RiskLevel::High` always ask for their values instead.
+    pub fn compute_with_defaults(
+        parameters: &mut Vec<ParameterMatch>,
+        endpoint_params: &[EndpointParameter],
+        risk_level: RiskLevel,
+        defaults: &ParameterDefaults,
+    ) -> Self {
+        if risk_level == RiskLevel::High {
+            return Self::compute(parameters, endpoint_params);
         }
-
-        let missing_count = self.missing_required_fields.len();
-
-        match missing_count {
-            1 => {
-                let field = &self.missing_required_fields[0];
-                Some(format!(
-                    "To proceed with {}, I need one more piece of information: {}. Could you please provide that?",
-                    endpoint_name.to_lowercase(),
-                    Self::format_field_request(&field.name, &field.description)
-                ))
+        for endpoint_param in endpoint_params {
+            if !endpoint_param.required.unwrap_or(false) {
+                continue;
             }
-            2 => {
-                let field1 = &self.missing_required_fields[0];
-                let field2 = &self.missing_required_fields[1];
-                Some(format!(
-                    "To complete your {} request, I need {} and {}. Could you provide these details?",
-                    endpoint_name.to_lowercase(),
-                    Self::format_field_request(&field1.name, &field1.description),
-                    Self::format_field_request(&field2.name, &field2.description)
-                ))
+            let Some((value, source)) = defaults.get(&endpoint_param.name) else {
+                continue;
+            };
+            let existing = parameters.iter_mut().find(|p| p.name == endpoint_param.name);
+            if existing
+                .as_ref()
+                .and_then(|p| p.value.as_ref())
+                .is_some_and(|v| !v.trim().is_empty())
+            {
+                continue;
             }
-            _ => {
-                let field_list: Vec<String> = self
-                    .missing_required_fields
-                    .iter()
-                    .map(|f| Self::format_field_request(&f.name, &f.description))
-                    .collect();
-
-                let (initial_fields, last_field) = field_list.split_at(field_list.len() - 1);
-
-                Some(format!(
-                    "To process your {} request, I need a few more details: {}, and {}. Could you provide this information?",
-                    endpoint_name.to_lowercase(),
-                    initial_fields.join(", "),
-                    last_field[0]
-                ))
+
+            crate::app_log!(
+                debug,
+                "Filling in '{}' from the {} values",
+                endpoint_param.name,
+                source
+            );
+            match existing {
+                Some(param) => {
+                    param.value = Some(value.to_string());
+                    param.default_source = Some(source);
+                }
+                None => parameters.push(ParameterMatch {
+                    name: endpoint_param.name.clone(),
+                    description: endpoint_param.description.clone(),
+                    value: Some(value.to_string()),
+                    default_source: Some(source),
+                }),
             }
         }
+        Self::compute(parameters, endpoint_params)
     }
 
-    fn format_field_request(field_name: &str, field_description: &str) -> String {
-        // Convert snake_case to natural language
-        let natural_name = field_name.replace(['_', '-'], " ");
-
-        // Use description if it's more descriptive than the field name
-        if field_description.len() > natural_name.len() + 5
-            && !field_description
-                .to_lowercase()
-                .starts_with("missing parameter")
-        {
-            field_description.to_lowercase()
-        } else {
-            format!("the {natural_name}")
-        }
+    /// Ask for the missing required fields in the request's language
+    pub fn generate_user_prompt(&self, endpoint_name: &str) -> Option<String> {
+        missing_fields::question(
+            &missing_fields::request_language(),
+            Some(endpoint_name),
+            &self.missing_required_fields,
+        )
     }
 }
 
@@ -1,4 +1,6 @@
 // src/models/providers/claude.rs
+use super::error::ProviderError;
+use super::http;
 use super::{GenerationResult, ModelConfig, ModelProvider, ProviderConfig, TokenCounter};
 use crate::app_log;
 use async_trait::async_trait;
@@ -60,39 +62,40 @@ impl ModelProvider for ClaudeProvider {
             }],
         };
 
-        let client = reqwest::Client::new();
-        let response = client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .header("anthropic-version", "2023-06-01")
-            .json(&request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            app_log!(
-                error,
-                "Claude request failed with status {}: {}",
-                status.as_u16(),
-                error_text
-            );
-            return Err(format!("Claude request failed: {status} - {error_text}").into());
-        }
+        let response = http::send_with_retry("Claude", |client| {
+            client
+                .post("https://api.anthropic.com/v1/messages")
+                .header("x-api-key", &self.api_key)
+                .header("Content-Type", "application/json")
+                .header("anthropic-version", "2023-06-01")
+                .json(&request)
+        })
+        .await?;
 
         // Get raw JSON first for token extraction
-        let response_json: serde_json::Value = response.json().await?;
+        let response_json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| ProviderError::from_reqwest("Claude", e))?;
+
+        if response_json["stop_reason"] == "refusal" {
+            return Err(Box::new(ProviderError::ContentFiltered {
+                provider: "Claude",
+                message: "the model refused to answer".to_string(),
+            }));
+        }
 
         let content = response_json["content"][0]["text"]
             .as_str()
-            .ok_or("No content in Claude response")?
+            .ok_or_else(|| ProviderError::invalid_response("Claude", "no content"))?
             .to_string();
 
         if content.trim().is_empty() {
             app_log!(error, "Received empty response from Claude");
-            return Err("Empty response from Claude".into());
+            return Err(Box::new(ProviderError::invalid_response(
+                "Claude",
+                "empty content",
+            )));
         }
 
         let counter = TokenCounter::new();
@@ -105,4 +108,12 @@ impl ModelProvider for ClaudeProvider {
     fn get_model_name(&self) -> &str {
         "claude"
     }
+
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let request = http::client()
+            .get("https://api.anthropic.com/v1/models")
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01");
+        super::check_provider_reachable("Claude", request).await
+    }
 }
@@ -1,4 +1,6 @@
 // src/models/providers/cohere.rs - Fix token extraction
+use super::error::ProviderError;
+use super::http;
 use super::{GenerationResult, ModelConfig, ModelProvider, ProviderConfig};
 use crate::app_log;
 use async_trait::async_trait;
@@ -69,39 +71,40 @@ impl ModelProvider for CohereProvider {
             response_format: None,
         };
 
-        let client = reqwest::Client::new();
-        let response = client
-            .post("https://api.cohere.ai/v1/chat")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            app_log!(
-                error,
-                "Cohere request failed with status {}: {}",
-                status.as_u16(),
-                error_text
-            );
-            return Err(format!("Cohere request failed: {status} - {error_text}").into());
-        }
+        let response = http::send_with_retry("Cohere", |client| {
+            client
+                .post("https://api.cohere.ai/v1/chat")
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&request)
+        })
+        .await?;
 
         // Get raw JSON first for token extraction
-        let response_json: serde_json::Value = response.json().await?;
+        let response_json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| ProviderError::from_reqwest("Cohere", e))?;
         app_log!(debug, "Cohere raw response: {:?}", response_json);
 
+        if response_json["finish_reason"] == "ERROR_TOXIC" {
+            return Err(Box::new(ProviderError::ContentFiltered {
+                provider: "Cohere",
+                message: "the generation was flagged as toxic".to_string(),
+            }));
+        }
+
         let content = response_json["text"]
             .as_str()
-            .ok_or("No text in Cohere response")?
+            .ok_or_else(|| ProviderError::invalid_response("Cohere", "no text"))?
             .to_string();
 
         if content.trim().is_empty() {
             app_log!(error, "Received empty response from Cohere");
-            return Err("Empty response from Cohere".into());
+            return Err(Box::new(ProviderError::invalid_response(
+                "Cohere",
+                "empty text",
+            )));
         }
 
         // let counter = TokenCounter::new();
@@ -212,4 +215,11 @@ impl ModelProvider for CohereProvider {
     fn get_model_name(&self) -> &str {
         "cohere"
     }
+
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let request = http::client()
+            .get("https://api.cohere.ai/v1/models")
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        super::check_provider_reachable("Cohere", request).await
+    }
 }
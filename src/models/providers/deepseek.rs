@@ -1,4 +1,6 @@
 // src/models/providers/deepseek.rs
+use super::error::ProviderError;
+use super::http;
 use super::{GenerationResult, ModelConfig, ModelProvider, ProviderConfig, TokenCounter};
 use crate::app_log;
 use async_trait::async_trait;
@@ -33,6 +35,8 @@ struct DeepSeekResponse {
 #[derive(Debug, Deserialize)]
 struct Choice {
     message: ResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -85,43 +89,44 @@ impl ModelProvider for DeepSeekProvider {
             max_tokens: config.max_tokens,
         };
 
-        let client = reqwest::Client::new();
-        let response = client
-            .post(&self.base_url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            app_log!(
-                error,
-                "DeepSeek request failed with status {}: {}",
-                status.as_u16(),
-                error_text
-            );
-            return Err(format!("DeepSeek request failed: {status} - {error_text}").into());
-        }
+        let response = http::send_with_retry("DeepSeek", |client| {
+            client
+                .post(&self.base_url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&request)
+        })
+        .await?;
 
         // Get raw JSON first for token extraction
-        let response_json: serde_json::Value = response.json().await?;
+        let response_json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| ProviderError::from_reqwest("DeepSeek", e))?;
 
-        let deepseek_response: DeepSeekResponse = serde_json::from_value(response_json.clone())?;
+        let deepseek_response: DeepSeekResponse = serde_json::from_value(response_json.clone())
+            .map_err(|e| ProviderError::invalid_response("DeepSeek", e))?;
 
-        let content = deepseek_response
+        let choice = deepseek_response
             .choices
             .first()
-            .ok_or("No choices in DeepSeek response")?
-            .message
-            .content
-            .clone();
+            .ok_or_else(|| ProviderError::invalid_response("DeepSeek", "no choices"))?;
+
+        if choice.finish_reason.as_deref() == Some("content_filter") {
+            return Err(Box::new(ProviderError::ContentFiltered {
+                provider: "DeepSeek",
+                message: "the generation was stopped by the content filter".to_string(),
+            }));
+        }
+
+        let content = choice.message.content.clone();
 
         if content.trim().is_empty() {
             app_log!(error, "Received empty response from DeepSeek");
-            return Err("Empty response from DeepSeek".into());
+            return Err(Box::new(ProviderError::invalid_response(
+                "DeepSeek",
+                "empty content",
+            )));
         }
 
         let counter = TokenCounter::new();
@@ -147,4 +152,11 @@ impl ModelProvider for DeepSeekProvider {
     fn get_model_name(&self) -> &str {
         "deepseek"
     }
+
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let request = http::client()
+            .get("https://api.deepseek.com/models")
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        super::check_provider_reachable("DeepSeek", request).await
+    }
 }
@@ -0,0 +1,223 @@
+// src/models/providers/error.rs - Typed failures of LLM provider calls
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+/// Why a provider call failed. Providers return it boxed, callers downcast it.
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+    #[error("{provider} rate limited the request: {message}")]
+    RateLimited {
+        provider: &'static str,
+        /// From the `Retry-After` header, when the provider sent one
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    #[error("{provider} rejected the API key ({status}): {message}")]
+    Auth {
+        provider: &'static str,
+        status: u16,
+        message: String,
+    },
+    #[error("{provider} request timed out")]
+    Timeout { provider: &'static str },
+    #[error("{provider} is overloaded or unavailable ({status}): {message}")]
+    Overloaded {
+        provider: &'static str,
+        status: u16,
+        message: String,
+    },
+    #[error("{provider} returned an invalid response: {message}")]
+    InvalidResponse {
+        provider: &'static str,
+        message: String,
+    },
+    #[error("{provider} filtered the content: {message}")]
+    ContentFiltered {
+        provider: &'static str,
+        message: String,
+    },
+    /// Any other unsuccessful status, e.g. a malformed request
+    #[error("{provider} request failed ({status}): {message}")]
+    Http {
+        provider: &'static str,
+        status: u16,
+        message: String,
+    },
+    #[error("{provider} could not be reached: {message}")]
+    Connection {
+        provider: &'static str,
+        message: String,
+    },
+}
+
+impl ProviderError {
+    /// Classify an unsuccessful response
+    pub fn from_status(
+        provider: &'static str,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: String,
+    ) -> Self {
+        let status_code = status.as_u16();
+        match status_code {
+            429 => ProviderError::RateLimited {
+                provider,
+                retry_after: headers
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| parse_retry_after(value, Utc::now())),
+                message: body,
+            },
+            401 | 403 => ProviderError::Auth {
+                provider,
+                status: status_code,
+                message: body,
+            },
+            408 | 504 => ProviderError::Timeout { provider },
+            // 529 is Anthropic's "overloaded"
+            500 | 502 | 503 | 529 => ProviderError::Overloaded {
+                provider,
+                status: status_code,
+                message: body,
+            },
+            _ => ProviderError::Http {
+                provider,
+                status: status_code,
+                message: body,
+            },
+        }
+    }
+
+    pub fn from_reqwest(provider: &'static str, error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            ProviderError::Timeout { provider }
+        } else if error.is_decode() || error.is_body() {
+            ProviderError::InvalidResponse {
+                provider,
+                message: error.to_string(),
+            }
+        } else {
+            ProviderError::Connection {
+                provider,
+                message: error.to_string(),
+            }
+        }
+    }
+
+    pub fn invalid_response(provider: &'static str, message: impl ToString) -> Self {
+        ProviderError::InvalidResponse {
+            provider,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn provider(&self) -> &'static str {
+        match self {
+            ProviderError::RateLimited { provider, .. }
+            | ProviderError::Auth { provider, .. }
+            | ProviderError::Timeout { provider }
+            | ProviderError::Overloaded { provider, .. }
+            | ProviderError::InvalidResponse { provider, .. }
+            | ProviderError::ContentFiltered { provider, .. }
+            | ProviderError::Http { provider, .. }
+            | ProviderError::Connection { provider, .. } => provider,
+        }
+    }
+
+    /// Worth sending again: the same request may succeed a moment later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ProviderError::RateLimited { .. }
+                | ProviderError::Timeout { .. }
+                | ProviderError::Overloaded { .. }
+                | ProviderError::Connection { .. }
+        )
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ProviderError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Label for metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProviderError::RateLimited { .. } => "rate_limited",
+            ProviderError::Auth { .. } => "auth",
+            ProviderError::Timeout { .. } => "timeout",
+            ProviderError::Overloaded { .. } => "overloaded",
+            ProviderError::InvalidResponse { .. } => "invalid_response",
+            ProviderError::ContentFiltered { .. } => "content_filtered",
+            ProviderError::Http { .. } => "http_status",
+            ProviderError::Connection { .. } => "connect",
+        }
+    }
+}
+
+/// `Retry-After` is either a whole number of seconds or an HTTP date
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        // Too many digits for a u64 still means "a very long time"
+        return Some(Duration::from_secs(value.parse().unwrap_or(u64::MAX)));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_status_classification_and_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("2"));
+        let limited = ProviderError::from_status(
+            "Claude",
+            StatusCode::TOO_MANY_REQUESTS,
+            &headers,
+            "slow down".to_string(),
+        );
+        assert_eq!(limited.kind(), "rate_limited");
+        assert!(limited.is_retryable());
+        assert_eq!(limited.retry_after(), Some(Duration::from_secs(2)));
+
+        let status = |code: u16| {
+            ProviderError::from_status(
+                "Cohere",
+                StatusCode::from_u16(code).unwrap(),
+                &HeaderMap::new(),
+                String::new(),
+            )
+        };
+        assert_eq!(status(401).kind(), "auth");
+        assert!(!status(401).is_retryable());
+        assert_eq!(status(529).kind(), "overloaded");
+        assert!(status(503).is_retryable());
+        assert_eq!(status(400).kind(), "http_status");
+        assert!(!status(400).is_retryable());
+
+        let now = DateTime::parse_from_rfc3339("2025-03-14T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after("Fri, 14 Mar 2025 08:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(
+            parse_retry_after("99999999999999999999999", now),
+            Some(Duration::from_secs(u64::MAX))
+        );
+        for invalid in ["1e30", "inf", "-1", "1.5"] {
+            assert_eq!(parse_retry_after(invalid, now), None, "{invalid}");
+        }
+    }
+}
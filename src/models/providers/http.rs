@@ -0,0 +1,189 @@
+// src/models/providers/http.rs - Shared HTTP client and retries of provider calls
+use super::error::ProviderError;
+use crate::app_log;
+use crate::metrics;
+use crate::models::config::{HttpConfig, ProviderRetryConfig};
+use reqwest::{Client, RequestBuilder, Response};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+struct SharedClient {
+    client: Client,
+    retry: ProviderRetryConfig,
+}
+
+static SHARED: OnceLock<SharedClient> = OnceLock::new();
+
+/// Build the shared client from config. Later calls keep the first configuration.
+pub fn configure(config: &HttpConfig) {
+    if SHARED.set(build(config)).is_err() {
+        app_log!(debug, "Provider HTTP client already configured");
+    }
+}
+
+fn build(config: &HttpConfig) -> SharedClient {
+    let client = Client::builder()
+        .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+        .timeout(Duration::from_millis(config.request_timeout_ms))
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_secs))
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .build()
+        .expect("HTTP client configuration is valid");
+    SharedClient {
+        client,
+        retry: config.retry.clone(),
+    }
+}
+
+fn shared() -> &'static SharedClient {
+    SHARED.get_or_init(|| build(&HttpConfig::default()))
+}
+
+/// Pooled client shared by all providers
+pub fn client() -> &'static Client {
+    &shared().client
+}
+
+/// Send the request built by `request`, retrying retryable failures with exponential
+/// backoff and jitter. A `Retry-After` from the provider replaces the backoff.
+pub async fn send_with_retry(
+    provider: &'static str,
+    request: impl Fn(&Client) -> RequestBuilder,
+) -> Result<Response, ProviderError> {
+    let shared = shared();
+    send(provider, &shared.client, &shared.retry, request).await
+}
+
+async fn send(
+    provider: &'static str,
+    client: &Client,
+    retry: &ProviderRetryConfig,
+    request: impl Fn(&Client) -> RequestBuilder,
+) -> Result<Response, ProviderError> {
+    let max_backoff = Duration::from_millis(retry.max_backoff_ms);
+    let mut attempt = 1;
+    loop {
+        let error = match request(client).send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body = response.text().await.unwrap_or_default();
+                ProviderError::from_status(provider, status, &headers, body)
+            }
+            Err(e) => ProviderError::from_reqwest(provider, e),
+        };
+
+        // A provider asking for a longer wait is retried after max_backoff
+        let delay = match error.retry_after() {
+            Some(retry_after) => retry_after.min(max_backoff),
+            None => backoff(retry, attempt),
+        };
+        if !error.is_retryable() || attempt >= retry.max_attempts {
+            app_log!(
+                error,
+                "{} request failed after {} attempt(s): {}",
+                provider,
+                attempt,
+                error
+            );
+            return Err(error);
+        }
+
+        app_log!(
+            warn,
+            "{} request failed (attempt {}/{}), retrying in {}ms: {}",
+            provider,
+            attempt,
+            retry.max_attempts,
+            delay.as_millis(),
+            error
+        );
+        metrics::record_provider_retry(provider, error.kind());
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Exponential backoff with equal jitter: half the window fixed, half random
+fn backoff(retry: &ProviderRetryConfig, attempt: u32) -> Duration {
+    let window = retry
+        .initial_backoff_ms
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(retry.max_backoff_ms);
+    Duration::from_millis(window / 2 + fastrand::u64(0..=window / 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    fn retry(max_attempts: u32) -> ProviderRetryConfig {
+        ProviderRetryConfig {
+            max_attempts,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 1000,
+        }
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let config = ProviderRetryConfig {
+            max_attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+        };
+        for _ in 0..20 {
+            let first = backoff(&config, 1).as_millis();
+            assert!((50..=100).contains(&first), "{first}");
+            let third = backoff(&config, 3).as_millis();
+            assert!((200..=400).contains(&third), "{third}");
+            assert!(backoff(&config, 10).as_millis() <= 1000);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_request_is_retried() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/",
+            get(move || {
+                let counter = counter.clone();
+                async move {
+                    match counter.fetch_add(1, Ordering::SeqCst) {
+                        0 => (
+                            StatusCode::TOO_MANY_REQUESTS,
+                            [("retry-after", "0")],
+                            "slow down",
+                        ),
+                        _ => (StatusCode::OK, [("retry-after", "0")], "ok"),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = Client::new();
+        let response = send("Test", &client, &retry(3), |client| client.get(&url))
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Not retried once the attempts are used up
+        calls.store(0, Ordering::SeqCst);
+        let error = send("Test", &client, &retry(1), |client| client.get(&url))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), "rate_limited");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
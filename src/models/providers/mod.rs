@@ -7,6 +7,8 @@ use token_counter::{TokenCounter, TokenUsage};
 pub mod claude;
 pub mod cohere;
 pub mod deepseek;
+pub mod error;
+pub mod http;
 pub mod token_counter;
 
 #[derive(Debug)]
@@ -24,6 +26,30 @@ pub trait ModelProvider: Send + Sync {
     ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>>;
 
     fn get_model_name(&self) -> &str;
+
+    /// Cheap reachability and credential check that does not consume tokens
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+/// Send a lightweight request (e.g. a model listing) and require a success status
+pub(crate) async fn check_provider_reachable(
+    provider: &'static str,
+    request: reqwest::RequestBuilder,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let response = request
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| error::ProviderError::from_reqwest(provider, e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await.unwrap_or_default();
+        return Err(error::ProviderError::from_status(provider, status, &headers, body).into());
+    }
+    Ok(())
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -32,7 +58,7 @@ pub struct ProviderConfig {
     pub api_key: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
 pub struct ModelConfig {
     #[serde(default)]
     pub cohere: String,
@@ -44,7 +70,19 @@ pub struct ModelConfig {
     pub max_tokens: u32,
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+impl ModelConfig {
+    /// Concrete model name used by the given provider
+    pub fn model_for<'a>(&'a self, provider: &'a str) -> &'a str {
+        match provider {
+            "cohere" => &self.cohere,
+            "claude" => &self.claude,
+            "deepseek" => &self.deepseek,
+            _ => provider,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
 pub struct ModelsConfig {
     pub default: ModelConfig,
 }
@@ -1,7 +1,7 @@
 // src/models/providers/token_counter.rs
 use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct TokenUsage {
     pub input_tokens: u32,
     pub output_tokens: u32,
@@ -9,6 +9,26 @@ pub struct TokenUsage {
     pub estimated: bool, // Whether tokens were estimated or from API
 }
 
+impl TokenUsage {
+    /// Add another call's usage; the sum is estimated if any part was
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.total_tokens += other.total_tokens;
+        self.estimated |= other.estimated;
+    }
+
+    /// Usage accumulated since an `earlier` reading of the same counter
+    pub fn since(&self, earlier: &TokenUsage) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.input_tokens.saturating_sub(earlier.input_tokens),
+            output_tokens: self.output_tokens.saturating_sub(earlier.output_tokens),
+            total_tokens: self.total_tokens.saturating_sub(earlier.total_tokens),
+            estimated: self.estimated,
+        }
+    }
+}
+
 pub struct TokenCounter {
     model_rates: HashMap<String, f32>, // tokens per character ratio
 }
@@ -29,6 +49,10 @@ impl TokenCounter {
     }
 
     pub fn estimate_tokens(&self, text: &str, model: &str) -> u32 {
+        if let Some(count) = crate::utils::tokenizer::count_tokens(model, text) {
+            return count as u32;
+        }
+
         let rate = self
             .model_rates
             .get(model)
@@ -38,6 +62,7 @@ impl TokenCounter {
         (text.len() as f32 * rate).ceil() as u32
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub fn from_response(&self, response_text: &str, input_text: &str, model: &str) -> TokenUsage {
         TokenUsage {
             input_tokens: self.estimate_tokens(input_text, model),
@@ -49,6 +74,7 @@ impl TokenCounter {
     }
 
     // Try to extract from API response, fallback to estimation
+    #[allow(clippy::wrong_self_convention)]
     pub fn from_api_response(
         &self,
         response_json: &serde_json::Value,
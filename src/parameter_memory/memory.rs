@@ -0,0 +1,95 @@
+// src/parameter_memory/memory.rs - Process-local remembered values
+use super::{ParameterMemoryStore, RememberedValue, ValueSource};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::error::Error;
+use tokio::sync::Mutex;
+
+/// email -> remembered values of that user
+type Values = HashMap<String, Vec<RememberedValue>>;
+
+pub struct MemoryParameterStore {
+    values: Mutex<Values>,
+}
+
+impl MemoryParameterStore {
+    pub fn new() -> Self {
+        Self {
+            values: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn upsert(&self, email: &str, name: &str, value: &str, source: ValueSource) {
+        let mut values = self.values.lock().await;
+        let user_values = values.entry(email.to_string()).or_default();
+        let now = Utc::now();
+        match user_values
+            .iter_mut()
+            .find(|v| v.name == name && v.source == source)
+        {
+            Some(existing) => {
+                existing.uses = match source {
+                    ValueSource::Learned if existing.value == value => existing.uses + 1,
+                    _ => 1,
+                };
+                existing.value = value.to_string();
+                existing.updated_at = now;
+            }
+            None => user_values.push(RememberedValue {
+                name: name.to_string(),
+                value: value.to_string(),
+                source,
+                uses: 1,
+                updated_at: now,
+            }),
+        }
+    }
+}
+
+impl Default for MemoryParameterStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ParameterMemoryStore for MemoryParameterStore {
+    async fn values(
+        &self,
+        email: &str,
+    ) -> Result<Vec<RememberedValue>, Box<dyn Error + Send + Sync>> {
+        let values = self.values.lock().await;
+        Ok(values.get(email).cloned().unwrap_or_default())
+    }
+
+    async fn learn(
+        &self,
+        email: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.upsert(email, name, value, ValueSource::Learned).await;
+        Ok(())
+    }
+
+    async fn set_profile(
+        &self,
+        email: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.upsert(email, name, value, ValueSource::Profile).await;
+        Ok(())
+    }
+
+    async fn forget(&self, email: &str, name: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let mut values = self.values.lock().await;
+        let Some(user_values) = values.get_mut(email) else {
+            return Ok(false);
+        };
+        let before = user_values.len();
+        user_values.retain(|v| v.name != name);
+        Ok(user_values.len() < before)
+    }
+}
@@ -0,0 +1,421 @@
+// src/parameter_memory/mod.rs - Parameter values remembered per user
+//
+// Values come from the user's profile, set explicitly, or are learned from the
+// values of completed matches. Missing required parameters of a new match are
+// filled in from them (see `MatchingInfo::compute_with_defaults`) instead of being
+// asked for again. Profile values take precedence; a learned value is only used
+// once `parameter_memory.min_uses` matches in a row gave it. High-risk endpoints
+// are never filled in: their values are always the user's own.
+pub mod memory;
+pub mod postgres;
+
+use crate::app_log;
+use crate::health::{PendingStore, StoreSlot};
+use crate::models::config::{CounterBackend, ParameterMemoryConfig};
+use crate::models::{ConfirmationStatus, EnhancedAnalysisResult, MatchingStatus};
+use crate::workflow::classify_intent::IntentType;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueSource {
+    /// Set by the user
+    Profile,
+    /// Taken from completed matches
+    Learned,
+}
+
+impl ValueSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueSource::Profile => "profile",
+            ValueSource::Learned => "learned",
+        }
+    }
+
+    pub fn parse(source: &str) -> Option<Self> {
+        match source {
+            "profile" => Some(ValueSource::Profile),
+            "learned" => Some(ValueSource::Learned),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RememberedValue {
+    pub name: String,
+    pub value: String,
+    pub source: ValueSource,
+    /// Completed matches in a row that gave this value; 1 for profile values
+    pub uses: u32,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Remembered values keyed by email (lowercase), parameter name and source
+#[async_trait]
+pub trait ParameterMemoryStore: Send + Sync {
+    async fn values(
+        &self,
+        email: &str,
+    ) -> Result<Vec<RememberedValue>, Box<dyn Error + Send + Sync>>;
+
+    /// Count one more use of `value`, or start over from one when it differs
+    /// from the learned value
+    async fn learn(
+        &self,
+        email: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    async fn set_profile(
+        &self,
+        email: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Forget the profile and the learned value of `name`
+    async fn forget(&self, email: &str, name: &str) -> Result<bool, Box<dyn Error + Send + Sync>>;
+}
+
+/// The values that may fill in missing parameters of one user's matches
+#[derive(Debug, Clone, Default)]
+pub struct ParameterDefaults {
+    values: HashMap<String, (String, ValueSource)>,
+}
+
+impl ParameterDefaults {
+    pub fn from_values(values: Vec<RememberedValue>, min_uses: u32) -> Self {
+        let mut defaults = Self::default();
+        for remembered in values {
+            let usable = match remembered.source {
+                ValueSource::Profile => true,
+                ValueSource::Learned => remembered.uses >= min_uses,
+            };
+            let overridden = defaults
+                .values
+                .get(&remembered.name)
+                .is_some_and(|(_, source)| *source == ValueSource::Profile);
+            if usable && !overridden {
+                defaults
+                    .values
+                    .insert(remembered.name, (remembered.value, remembered.source));
+            }
+        }
+        defaults
+    }
+
+    pub fn get(&self, name: &str) -> Option<(&str, ValueSource)> {
+        self.values
+            .get(name)
+            .map(|(value, source)| (value.as_str(), *source))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+pub struct ParameterMemory {
+    enabled: bool,
+    /// Empty while the `postgres` backend is not connected yet
+    store: Arc<StoreSlot<dyn ParameterMemoryStore>>,
+    learn: bool,
+    min_uses: u32,
+}
+
+impl ParameterMemory {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            store: Arc::new(StoreSlot::empty()),
+            learn: false,
+            min_uses: 1,
+        }
+    }
+
+    pub fn new(store: Arc<dyn ParameterMemoryStore>, config: &ParameterMemoryConfig) -> Self {
+        Self {
+            store: Arc::new(StoreSlot::new(store)),
+            ..Self::pending(config)
+        }
+    }
+
+    fn pending(config: &ParameterMemoryConfig) -> Self {
+        Self {
+            enabled: true,
+            store: Arc::new(StoreSlot::empty()),
+            learn: config.learn,
+            min_uses: config.min_uses.max(1),
+        }
+    }
+
+    /// With the `postgres` backend, nothing is remembered or filled in until
+    /// PostgreSQL is reachable; the store is then connected from `pending`
+    pub async fn from_config(
+        config: &ParameterMemoryConfig,
+        pending: &mut Vec<PendingStore>,
+    ) -> Self {
+        if !config.enabled {
+            app_log!(info, "Parameter memory is disabled");
+            return Self::disabled();
+        }
+
+        let memory = match config.backend {
+            CounterBackend::Memory => {
+                Self::new(Arc::new(memory::MemoryParameterStore::new()), config)
+            }
+            CounterBackend::Postgres => {
+                let memory = Self::pending(config);
+                PendingStore::connect_or_retry(
+                    "parameter memory",
+                    &memory.store,
+                    pending,
+                    || async {
+                        let database_url = crate::progressive_matching::get_postgres_url()?;
+                        let store: Arc<dyn ParameterMemoryStore> =
+                            Arc::new(postgres::PostgresParameterStore::new(&database_url).await?);
+                        Ok::<_, Box<dyn Error + Send + Sync>>(store)
+                    },
+                )
+                .await;
+                memory
+            }
+        };
+
+        app_log!(
+            info,
+            "Parameter memory enabled with {:?} storage (learn: {}, min_uses: {})",
+            config.backend,
+            config.learn,
+            config.min_uses
+        );
+        memory
+    }
+
+    fn store(&self) -> Result<Arc<dyn ParameterMemoryStore>, Box<dyn Error + Send + Sync>> {
+        if !self.enabled {
+            return Err("Parameter memory is disabled".into());
+        }
+        self.store
+            .get()
+            .ok_or_else(|| "Parameter memory database is unavailable".into())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Values that may fill in the user's missing parameters. A failed lookup only
+    /// means nothing is filled in.
+    pub async fn defaults(&self, email: &str) -> ParameterDefaults {
+        let Some(store) = self.store.get() else {
+            return ParameterDefaults::default();
+        };
+        match store.values(&email.to_lowercase()).await {
+            Ok(values) => ParameterDefaults::from_values(values, self.min_uses),
+            Err(e) => {
+                app_log!(warn, "Failed to read remembered values: {}", e);
+                ParameterDefaults::default()
+            }
+        }
+    }
+
+    /// Learn the values the user gave in a completed match, once the user wants it:
+    /// at once when it needs no confirmation, otherwise from the confirming reply.
+    /// Values filled in from memory are not counted again.
+    pub async fn learn_from(&self, email: &str, result: &EnhancedAnalysisResult) {
+        let Some(store) = self.store.get() else {
+            return;
+        };
+        let wanted = match &result.confirmation {
+            None => true,
+            Some(confirmation) => confirmation.status == ConfirmationStatus::Confirmed,
+        };
+        if !self.learn
+            || !wanted
+            || result.intent != IntentType::ActionableRequest
+            || !matches!(result.matching_info.status, MatchingStatus::Complete)
+        {
+            return;
+        }
+
+        let email = email.to_lowercase();
+        for param in &result.parameters {
+            let Some(value) = param.value.as_deref().map(str::trim) else {
+                continue;
+            };
+            if value.is_empty() || param.default_source.is_some() {
+                continue;
+            }
+            if let Err(e) = store.learn(&email, &param.name, value).await {
+                app_log!(warn, "Failed to remember {}: {}", param.name, e);
+            }
+        }
+    }
+
+    pub async fn values(
+        &self,
+        email: &str,
+    ) -> Result<Vec<RememberedValue>, Box<dyn Error + Send + Sync>> {
+        let mut values = self.store()?.values(&email.to_lowercase()).await?;
+        values.sort_by(|a, b| (&a.name, a.source.as_str()).cmp(&(&b.name, b.source.as_str())));
+        Ok(values)
+    }
+
+    pub async fn set_profile(
+        &self,
+        email: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if name.trim().is_empty() || value.trim().is_empty() {
+            return Err("Remembered values need a name and a value".into());
+        }
+        self.store()?
+            .set_profile(&email.to_lowercase(), name.trim(), value.trim())
+            .await
+    }
+
+    pub async fn forget(
+        &self,
+        email: &str,
+        name: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        self.store()?.forget(&email.to_lowercase(), name).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::response_builders::create_complete_progressive_response;
+    use crate::models::{Confirmation, EnhancedEndpoint};
+    use crate::progressive_matching::{ParameterValue, ProgressiveMatchResult};
+
+    #[tokio::test]
+    async fn test_profile_wins_and_learned_values_need_repeated_uses() {
+        let config = ParameterMemoryConfig {
+            min_uses: 2,
+            ..Default::default()
+        };
+        let memory = ParameterMemory::new(Arc::new(memory::MemoryParameterStore::new()), &config);
+        let store = memory.store().unwrap();
+
+        store
+            .learn("ann@example.com", "company_id", "42")
+            .await
+            .unwrap();
+        store
+            .learn("ann@example.com", "language", "fr")
+            .await
+            .unwrap();
+        assert!(memory.defaults("Ann@example.com").await.is_empty());
+
+        store
+            .learn("ann@example.com", "company_id", "42")
+            .await
+            .unwrap();
+        // A different value starts over
+        store
+            .learn("ann@example.com", "language", "de")
+            .await
+            .unwrap();
+        store
+            .learn("ann@example.com", "language", "fr")
+            .await
+            .unwrap();
+        let defaults = memory.defaults("ann@example.com").await;
+        assert_eq!(
+            defaults.get("company_id"),
+            Some(("42", ValueSource::Learned))
+        );
+        assert_eq!(defaults.get("language"), None);
+
+        memory
+            .set_profile("ann@example.com", "company_id", "7")
+            .await
+            .unwrap();
+        let defaults = memory.defaults("ann@example.com").await;
+        assert_eq!(
+            defaults.get("company_id"),
+            Some(("7", ValueSource::Profile))
+        );
+
+        assert!(memory
+            .forget("ann@example.com", "company_id")
+            .await
+            .unwrap());
+        assert!(memory
+            .defaults("ann@example.com")
+            .await
+            .get("company_id")
+            .is_none());
+        assert_eq!(memory.values("ann@example.com").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_learns_only_what_the_user_wants() {
+        let memory = ParameterMemory::new(
+            Arc::new(memory::MemoryParameterStore::new()),
+            &ParameterMemoryConfig::default(),
+        );
+        let endpoint = EnhancedEndpoint {
+            id: "transfer".to_string(),
+            ..Default::default()
+        };
+        let result = create_complete_progressive_response(
+            &endpoint,
+            ProgressiveMatchResult {
+                conversation_id: "conv".to_string(),
+                endpoint_id: endpoint.id.clone(),
+                endpoint_description: String::new(),
+                matched_parameters: vec![ParameterValue {
+                    name: "iban".to_string(),
+                    value: "FR76".to_string(),
+                    description: String::new(),
+                }],
+                missing_parameters: vec![],
+                is_complete: true,
+                completion_percentage: 100.0,
+                ready_for_execution: true,
+            },
+            &None,
+        )
+        .await
+        .unwrap();
+
+        // Waiting for a confirmation, then declined
+        for status in [ConfirmationStatus::Required, ConfirmationStatus::Declined] {
+            let mut pending = result.clone();
+            pending.confirmation = Some(Confirmation {
+                status,
+                summary: String::new(),
+            });
+            memory.learn_from("ann@example.com", &pending).await;
+            assert!(memory.values("ann@example.com").await.unwrap().is_empty());
+        }
+
+        let mut confirmed = result.clone();
+        confirmed.confirmation = Some(Confirmation {
+            status: ConfirmationStatus::Confirmed,
+            summary: String::new(),
+        });
+        memory.learn_from("ann@example.com", &confirmed).await;
+        assert_eq!(memory.values("ann@example.com").await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+// src/parameter_memory/postgres.rs - Remembered values shared by all instances
+use super::{ParameterMemoryStore, RememberedValue, ValueSource};
+use async_trait::async_trait;
+use deadpool_postgres::{Manager, Pool};
+use std::error::Error;
+use tokio_postgres::Config as PgConfig;
+use tokio_postgres::NoTls;
+
+pub struct PostgresParameterStore {
+    pool: Pool,
+}
+
+impl PostgresParameterStore {
+    pub async fn new(database_url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let pg_config: PgConfig = database_url.parse()?;
+        let mgr = Manager::new(pg_config, NoTls);
+        let pool = Pool::builder(mgr)
+            .max_size(10)
+            .runtime(deadpool_postgres::Runtime::Tokio1)
+            .build()?;
+
+        let mut client = pool.get().await?;
+        crate::migrations::prepare(&mut client).await?;
+
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl ParameterMemoryStore for PostgresParameterStore {
+    async fn values(
+        &self,
+        email: &str,
+    ) -> Result<Vec<RememberedValue>, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT name, value, source, uses, updated_at FROM parameter_memory WHERE email = $1",
+                &[&email],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let source: String = row.get(2);
+                let uses: i32 = row.get(3);
+                Some(RememberedValue {
+                    name: row.get(0),
+                    value: row.get(1),
+                    source: ValueSource::parse(&source)?,
+                    uses: uses.max(0) as u32,
+                    updated_at: row.get(4),
+                })
+            })
+            .collect())
+    }
+
+    async fn learn(
+        &self,
+        email: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                r#"
+                INSERT INTO parameter_memory (email, name, source, value)
+                VALUES ($1, $2, 'learned', $3)
+                ON CONFLICT (email, name, source)
+                DO UPDATE SET
+                    uses = CASE WHEN parameter_memory.value = EXCLUDED.value
+                           THEN parameter_memory.uses + 1 ELSE 1 END,
+                    value = EXCLUDED.value,
+                    updated_at = now()
+                "#,
+                &[&email, &name, &value],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn set_profile(
+        &self,
+        email: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                r#"
+                INSERT INTO parameter_memory (email, name, source, value)
+                VALUES ($1, $2, 'profile', $3)
+                ON CONFLICT (email, name, source)
+                DO UPDATE SET value = EXCLUDED.value, updated_at = now()
+                "#,
+                &[&email, &name, &value],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn forget(&self, email: &str, name: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let removed = client
+            .execute(
+                "DELETE FROM parameter_memory WHERE email = $1 AND name = $2",
+                &[&email, &name],
+            )
+            .await?;
+        Ok(removed > 0)
+    }
+}
@@ -0,0 +1,770 @@
+// src/progressive_matching/mod.rs - Requests waiting for more parameters
+pub mod postgres;
+pub mod sqlite;
+
+use crate::app_log;
+use crate::metrics;
+use crate::models::config::ProgressiveMatchingConfig;
+use crate::models::ParameterChange;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OngoingMatch {
+    pub conversation_id: String,
+    pub endpoint_id: String,
+    pub parameters: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// JSON array of `ParameterRevision`, oldest first
+    pub history: String,
+    /// The user the match belongs to; `None` for matches saved before owners were
+    /// recorded, which nobody can see or change
+    pub email: Option<String>,
+}
+
+impl OngoingMatch {
+    pub fn belongs_to(&self, email: &str) -> bool {
+        self.email
+            .as_deref()
+            .is_some_and(|owner| owner.eq_ignore_ascii_case(email))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterValue {
+    pub name: String,
+    pub value: String,
+    pub description: String,
+}
+
+/// One value given, corrected or removed (`value` is `None`) for a parameter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterRevision {
+    pub name: String,
+    pub value: Option<String>,
+    /// The user message the value came from
+    pub source: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Merge new values into `parameters` and drop the `removed` ones, recording each
+/// difference in `history`. Returns the values that replaced or removed earlier ones.
+fn apply_parameter_updates(
+    parameters: &mut Vec<ParameterValue>,
+    history: &mut Vec<ParameterRevision>,
+    new_parameters: Vec<ParameterValue>,
+    removed: &[String],
+    source: &str,
+    at: DateTime<Utc>,
+) -> Vec<ParameterChange> {
+    let mut changes = Vec::new();
+    let mut revise = |name: &str, value: Option<&str>| {
+        history.push(ParameterRevision {
+            name: name.to_string(),
+            value: value.map(str::to_string),
+            source: source.to_string(),
+            at,
+        })
+    };
+
+    for new_param in new_parameters {
+        match parameters.iter_mut().find(|p| p.name == new_param.name) {
+            Some(existing) if existing.value == new_param.value => {}
+            Some(existing) => {
+                revise(&new_param.name, Some(&new_param.value));
+                changes.push(ParameterChange {
+                    name: new_param.name,
+                    previous: std::mem::replace(&mut existing.value, new_param.value.clone()),
+                    value: Some(new_param.value),
+                });
+            }
+            None => {
+                revise(&new_param.name, Some(&new_param.value));
+                parameters.push(new_param);
+            }
+        }
+    }
+
+    for name in removed {
+        if let Some(index) = parameters.iter().position(|p| &p.name == name) {
+            let previous = parameters.remove(index);
+            revise(name, None);
+            changes.push(ParameterChange {
+                name: name.clone(),
+                previous: previous.value,
+                value: None,
+            });
+        }
+    }
+    changes
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressiveMatchResult {
+    pub conversation_id: String,
+    pub endpoint_id: String,
+    pub endpoint_description: String,
+    pub matched_parameters: Vec<ParameterValue>,
+    pub missing_parameters: Vec<String>,
+    pub is_complete: bool,
+    pub completion_percentage: f32,
+    pub ready_for_execution: bool,
+}
+
+/// Where pending matches live. Finished matches (`completed`, `abandoned`, `expired`)
+/// are moved to an audit table with their final parameters.
+#[async_trait]
+pub trait ProgressiveStore: Send + Sync {
+    async fn get(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<Option<OngoingMatch>, Box<dyn Error + Send + Sync>>;
+
+    /// Matches of a conversation updated since `updated_since`, most recent first
+    async fn pending(
+        &self,
+        conversation_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<Vec<OngoingMatch>, Box<dyn Error + Send + Sync>>;
+
+    /// Insert the match, or replace the parameters and `updated_at` of an existing one
+    async fn save(&self, ongoing: &OngoingMatch) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Set `updated_at` to now. Returns false when there is no such match.
+    async fn touch(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>>;
+
+    /// Move the match to the audit table with `outcome`, only if it was last updated
+    /// before `updated_before` when given. Returns false when nothing was moved.
+    async fn finish(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        outcome: &str,
+        updated_before: Option<DateTime<Utc>>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>>;
+
+    /// Move every match last updated before `updated_before` to the audit table
+    async fn expire(
+        &self,
+        updated_before: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>>;
+}
+
+pub fn is_sqlite_url(database_url: &str) -> bool {
+    database_url.starts_with("sqlite:")
+}
+
+pub struct ProgressiveMatchingManager {
+    store: Arc<dyn ProgressiveStore>,
+    /// Pending matches not updated for this long are expired
+    ttl: Duration,
+}
+
+impl ProgressiveMatchingManager {
+    /// Open the store for `database_url`: SQLite for `sqlite:` URLs, PostgreSQL otherwise
+    pub async fn new(
+        database_url: &str,
+        config: &ProgressiveMatchingConfig,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let store: Arc<dyn ProgressiveStore> = if is_sqlite_url(database_url) {
+            Arc::new(sqlite::SqliteProgressiveStore::new(database_url)?)
+        } else {
+            Arc::new(postgres::PostgresProgressiveStore::new(database_url).await?)
+        };
+        Ok(Self::with_store(store, config))
+    }
+
+    pub fn with_store(
+        store: Arc<dyn ProgressiveStore>,
+        config: &ProgressiveMatchingConfig,
+    ) -> Self {
+        Self {
+            store,
+            ttl: Duration::from_secs(config.ttl_secs),
+        }
+    }
+
+    /// Matches last updated before this are expired, even if not swept yet
+    fn expiry_cutoff(&self) -> DateTime<Utc> {
+        chrono::Duration::from_std(self.ttl)
+            .ok()
+            .and_then(|ttl| Utc::now().checked_sub_signed(ttl))
+            .unwrap_or(DateTime::UNIX_EPOCH)
+    }
+
+    /// Expire every pending match older than the TTL. Returns how many expired.
+    pub async fn expire_matches(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let expired = self.store.expire(self.expiry_cutoff()).await?;
+        if expired > 0 {
+            app_log!(info, "Expired {} pending progressive matches", expired);
+        }
+        Ok(expired)
+    }
+
+    /// Expire stale matches every `interval` in the background
+    pub fn spawn_sweeper(self: &Arc<Self>, interval: Duration) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match manager.expire_matches().await {
+                    Ok(expired) => metrics::record_progressive_matches_expired(expired),
+                    Err(e) => app_log!(warn, "Failed to expire progressive matches: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Record the values of `source`, a message of the user `email`: new ones,
+    /// corrections of earlier ones and the `removed` parameters. Returns the
+    /// corrections and removals.
+    pub async fn update_match(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        email: &str,
+        new_parameters: Vec<ParameterValue>,
+        removed: &[String],
+        source: &str,
+    ) -> Result<Vec<ParameterChange>, Box<dyn Error + Send + Sync>> {
+        let now = Utc::now();
+
+        // An expired match that was not swept yet starts over
+        let cutoff = self.expiry_cutoff();
+        self.store
+            .finish(conversation_id, endpoint_id, "expired", Some(cutoff))
+            .await?;
+        let existing = self.store.get(conversation_id, endpoint_id, cutoff).await?;
+        if existing
+            .as_ref()
+            .is_some_and(|existing| !existing.belongs_to(email))
+        {
+            return Err("The pending request belongs to another user".into());
+        }
+
+        let (mut all_parameters, mut history, created_at) = match existing {
+            Some(existing) => (
+                serde_json::from_str::<Vec<ParameterValue>>(&existing.parameters)?,
+                serde_json::from_str::<Vec<ParameterRevision>>(&existing.history)?,
+                existing.created_at,
+            ),
+            None => (Vec::new(), Vec::new(), now),
+        };
+        let changes = apply_parameter_updates(
+            &mut all_parameters,
+            &mut history,
+            new_parameters,
+            removed,
+            source,
+            now,
+        );
+
+        self.store
+            .save(&OngoingMatch {
+                conversation_id: conversation_id.to_string(),
+                endpoint_id: endpoint_id.to_string(),
+                parameters: serde_json::to_string(&all_parameters)?,
+                created_at,
+                updated_at: now,
+                history: serde_json::to_string(&history)?,
+                email: Some(email.to_lowercase()),
+            })
+            .await?;
+
+        app_log!(
+            info,
+            "Updated progressive match for conversation: {} endpoint: {} ({} changed)",
+            conversation_id,
+            endpoint_id,
+            changes.len()
+        );
+        Ok(changes)
+    }
+
+    /// The user's most recently updated pending match, the one follow-ups fill
+    pub async fn get_incomplete_match(
+        &self,
+        conversation_id: &str,
+        email: &str,
+    ) -> Result<Option<OngoingMatch>, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .pending_matches(conversation_id, email)
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Every unexpired pending match of the user in a conversation, most recently
+    /// updated first. Switching to another request leaves the previous one below it
+    /// until it is resumed, completed or abandoned.
+    pub async fn pending_matches(
+        &self,
+        conversation_id: &str,
+        email: &str,
+    ) -> Result<Vec<OngoingMatch>, Box<dyn Error + Send + Sync>> {
+        let mut matches = self
+            .store
+            .pending(conversation_id, self.expiry_cutoff())
+            .await?;
+        matches.retain(|ongoing| ongoing.belongs_to(email));
+        Ok(matches)
+    }
+
+    async fn owns_match(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        email: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .get_match(conversation_id, endpoint_id)
+            .await?
+            .is_some_and(|ongoing| ongoing.belongs_to(email)))
+    }
+
+    /// Bring a pending match of the user back on top. Returns false when the user has
+    /// no such match.
+    pub async fn resume_match(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        email: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        if !self.owns_match(conversation_id, endpoint_id, email).await? {
+            return Ok(false);
+        }
+        let updated = self
+            .store
+            .touch(conversation_id, endpoint_id, self.expiry_cutoff())
+            .await?;
+
+        app_log!(
+            info,
+            "Resumed progressive match for conversation: {} endpoint: {}",
+            conversation_id,
+            endpoint_id
+        );
+        Ok(updated)
+    }
+
+    /// Drop a pending match the user no longer wants. Returns false when the user has
+    /// no such match.
+    pub async fn abandon_match(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        email: &str,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        if !self.owns_match(conversation_id, endpoint_id, email).await? {
+            return Ok(false);
+        }
+        let abandoned = self
+            .store
+            .finish(conversation_id, endpoint_id, "abandoned", None)
+            .await?;
+
+        app_log!(
+            info,
+            "Abandoned progressive match for conversation: {} endpoint: {}",
+            conversation_id,
+            endpoint_id
+        );
+        Ok(abandoned)
+    }
+
+    pub async fn complete_match(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.store
+            .finish(conversation_id, endpoint_id, "completed", None)
+            .await?;
+
+        app_log!(
+            info,
+            "Completed and cleaned up match for conversation: {}",
+            conversation_id
+        );
+        Ok(())
+    }
+
+    pub async fn get_match(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+    ) -> Result<Option<OngoingMatch>, Box<dyn Error + Send + Sync>> {
+        self.store
+            .get(conversation_id, endpoint_id, self.expiry_cutoff())
+            .await
+    }
+
+    pub async fn check_completion(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        required_parameters: Vec<String>,
+        endpoint_parameters: &[crate::models::EndpointParameter],
+    ) -> Result<ProgressiveMatchResult, Box<dyn Error + Send + Sync>> {
+        let ongoing_match = self.get_match(conversation_id, endpoint_id).await?;
+
+        let matched_parameters = if let Some(match_data) = ongoing_match {
+            serde_json::from_str::<Vec<ParameterValue>>(&match_data.parameters)?
+        } else {
+            Vec::new()
+        };
+
+        // Generic parameter matching using endpoint definitions
+        let mut satisfied_required_params = Vec::new();
+        let mut missing_parameters = Vec::new();
+
+        for required_param in &required_parameters {
+            let is_satisfied =
+                is_parameter_satisfied(required_param, &matched_parameters, endpoint_parameters);
+
+            if is_satisfied {
+                satisfied_required_params.push(required_param.clone());
+            } else {
+                missing_parameters.push(required_param.clone());
+            }
+        }
+
+        let is_complete = missing_parameters.is_empty();
+        let completion_percentage = if required_parameters.is_empty() {
+            100.0
+        } else {
+            (satisfied_required_params.len() as f32 / required_parameters.len() as f32) * 100.0
+        };
+
+        Ok(ProgressiveMatchResult {
+            conversation_id: conversation_id.to_string(),
+            endpoint_id: endpoint_id.to_string(),
+            endpoint_description: format!("Endpoint {endpoint_id}"),
+            matched_parameters,
+            missing_parameters,
+            is_complete,
+            completion_percentage,
+            ready_for_execution: is_complete,
+        })
+    }
+}
+
+// Generic parameter satisfaction checker
+fn is_parameter_satisfied(
+    required_param: &str,
+    matched_parameters: &[ParameterValue],
+    endpoint_parameters: &[crate::models::EndpointParameter],
+) -> bool {
+    // Find the endpoint parameter definition
+    let endpoint_param = endpoint_parameters
+        .iter()
+        .find(|p| p.name == required_param);
+
+    for matched in matched_parameters {
+        // Direct match
+        if matched.name == required_param {
+            return true;
+        }
+
+        // Check alternatives from endpoint definition
+        if let Some(ep) = endpoint_param {
+            if let Some(ref alternatives) = ep.alternatives {
+                if alternatives.contains(&matched.name) {
+                    return true;
+                }
+            }
+        }
+
+        // Reverse check: see if the matched parameter accepts this required param as alternative
+        if let Some(matched_endpoint_param) =
+            endpoint_parameters.iter().find(|p| p.name == matched.name)
+        {
+            if let Some(ref alternatives) = matched_endpoint_param.alternatives {
+                if alternatives.contains(&required_param.to_string()) {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+pub async fn integrate_progressive_matching(
+    conversation_id: &str,
+    endpoint_id: &str,
+    email: &str,
+    source: &str,
+    new_parameters: Vec<ParameterValue>,
+    required_parameter_names: Vec<String>,
+    manager: &ProgressiveMatchingManager,
+    endpoint_parameters: &[crate::models::EndpointParameter],
+) -> Result<ProgressiveMatchResult, Box<dyn Error + Send + Sync>> {
+    manager
+        .update_match(
+            conversation_id,
+            endpoint_id,
+            email,
+            new_parameters,
+            &[],
+            source,
+        )
+        .await?;
+    let result = manager
+        .check_completion(
+            conversation_id,
+            endpoint_id,
+            required_parameter_names,
+            endpoint_parameters,
+        )
+        .await?;
+
+    app_log!(
+        debug,
+        "Progressive matching result: completion {}%, ready: {}",
+        result.completion_percentage,
+        result.ready_for_execution
+    );
+
+    Ok(result)
+}
+
+pub fn get_database_url() -> Result<String, Box<dyn Error + Send + Sync>> {
+    env::var("DATABASE_URL").map_err(|_| "DATABASE_URL environment variable not set".into())
+}
+
+/// DATABASE_URL for the stores that need PostgreSQL: the usage ledger, shared rate
+/// limit counters and migrations. Only progressive matching also runs on SQLite.
+pub fn get_postgres_url() -> Result<String, Box<dyn Error + Send + Sync>> {
+    let database_url = get_database_url()?;
+    if is_sqlite_url(&database_url) {
+        return Err("DATABASE_URL is a SQLite URL, but this feature needs PostgreSQL".into());
+    }
+    Ok(database_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EndpointParameter;
+
+    fn manager(ttl_secs: u64) -> ProgressiveMatchingManager {
+        let store = sqlite::SqliteProgressiveStore::new("sqlite::memory:").unwrap();
+        let config = ProgressiveMatchingConfig {
+            ttl_secs,
+            ..Default::default()
+        };
+        ProgressiveMatchingManager::with_store(Arc::new(store), &config)
+    }
+
+    fn param(name: &str, value: &str) -> ParameterValue {
+        ParameterValue {
+            name: name.to_string(),
+            value: value.to_string(),
+            description: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multi_turn_flow_on_sqlite() {
+        let manager = manager(3600);
+        let parameters: Vec<EndpointParameter> = ["to", "subject"]
+            .iter()
+            .map(|name| EndpointParameter {
+                name: name.to_string(),
+                description: String::new(),
+                required: Some(true),
+                alternatives: None,
+                examples: None,
+                semantic_value: None,
+            })
+            .collect();
+        let required = vec!["to".to_string(), "subject".to_string()];
+
+        let result = integrate_progressive_matching(
+            "conv",
+            "send_email",
+            "ann@example.com",
+            "email john",
+            vec![param("to", "john@example.com")],
+            required.clone(),
+            &manager,
+            &parameters,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.missing_parameters, vec!["subject"]);
+
+        // Switching leaves the first request below the new one
+        manager
+            .update_match(
+                "conv",
+                "list_invoices",
+                "ann@example.com",
+                vec![param("month", "may")],
+                &[],
+                "my invoices of may",
+            )
+            .await
+            .unwrap();
+        let pending: Vec<String> = manager
+            .pending_matches("conv", "ann@example.com")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.endpoint_id)
+            .collect();
+        assert_eq!(pending, vec!["list_invoices", "send_email"]);
+
+        // Other users of the conversation id can neither see nor change them
+        let other = "mallory@example.com";
+        assert!(manager
+            .pending_matches("conv", other)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!manager
+            .resume_match("conv", "send_email", other)
+            .await
+            .unwrap());
+        assert!(!manager
+            .abandon_match("conv", "send_email", other)
+            .await
+            .unwrap());
+        assert!(manager
+            .update_match(
+                "conv",
+                "send_email",
+                other,
+                vec![param("to", "me")],
+                &[],
+                "to me"
+            )
+            .await
+            .is_err());
+
+        assert!(manager
+            .resume_match("conv", "send_email", "ann@example.com")
+            .await
+            .unwrap());
+        let top = manager
+            .get_incomplete_match("conv", "ann@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(top.endpoint_id, "send_email");
+
+        let result = integrate_progressive_matching(
+            "conv",
+            "send_email",
+            "ann@example.com",
+            "the subject is Hello",
+            vec![param("subject", "Hello")],
+            required,
+            &manager,
+            &parameters,
+        )
+        .await
+        .unwrap();
+        assert!(result.is_complete);
+        assert_eq!(result.matched_parameters.len(), 2);
+
+        manager.complete_match("conv", "send_email").await.unwrap();
+        assert!(manager
+            .abandon_match("conv", "list_invoices", "ann@example.com")
+            .await
+            .unwrap());
+        assert!(!manager
+            .abandon_match("conv", "list_invoices", "ann@example.com")
+            .await
+            .unwrap());
+        assert!(manager
+            .get_incomplete_match("conv", "ann@example.com")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_corrections_and_removals_are_recorded() {
+        let mut parameters = vec![param("date", "10"), param("to", "john")];
+        let mut history = Vec::new();
+        let changes = apply_parameter_updates(
+            &mut parameters,
+            &mut history,
+            vec![param("date", "12"), param("to", "john"), param("cc", "ann")],
+            &["to".to_string(), "unknown".to_string()],
+            "no, the 12th, and drop john, cc ann",
+            Utc::now(),
+        );
+        assert_eq!(
+            changes,
+            vec![
+                ParameterChange {
+                    name: "date".to_string(),
+                    previous: "10".to_string(),
+                    value: Some("12".to_string()),
+                },
+                ParameterChange {
+                    name: "to".to_string(),
+                    previous: "john".to_string(),
+                    value: None,
+                },
+            ]
+        );
+        let names: Vec<&str> = parameters.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["date", "cc"]);
+        // The unchanged "to" value is not a revision
+        let revisions: Vec<(&str, Option<&str>)> = history
+            .iter()
+            .map(|r| (r.name.as_str(), r.value.as_deref()))
+            .collect();
+        assert_eq!(
+            revisions,
+            vec![("date", Some("12")), ("cc", Some("ann")), ("to", None)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_matches_expire() {
+        let manager = manager(0);
+        manager
+            .update_match(
+                "conv",
+                "send_email",
+                "ann@example.com",
+                vec![param("to", "john@example.com")],
+                &[],
+                "email john",
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        assert!(manager
+            .get_incomplete_match("conv", "ann@example.com")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(!manager
+            .resume_match("conv", "send_email", "ann@example.com")
+            .await
+            .unwrap());
+        assert_eq!(manager.expire_matches().await.unwrap(), 1);
+        assert_eq!(manager.expire_matches().await.unwrap(), 0);
+    }
+}
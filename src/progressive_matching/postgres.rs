@@ -0,0 +1,200 @@
+// src/progressive_matching/postgres.rs - Pending matches shared by all instances
+use super::{OngoingMatch, ProgressiveStore};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+use std::error::Error;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Config as PgConfig;
+use tokio_postgres::{NoTls, Row};
+
+async fn create_db_pool(database_url: &str) -> Result<Pool, Box<dyn Error + Send + Sync>> {
+    // Parse the PostgreSQL connection string directly
+    let pg_config: PgConfig = database_url.parse()?;
+
+    let _mgr_config = ManagerConfig {
+        recycling_method: RecyclingMethod::Fast,
+    };
+    let mgr = Manager::new(pg_config, NoTls);
+    let pool = Pool::builder(mgr)
+        .max_size(10)
+        .runtime(deadpool_postgres::Runtime::Tokio1)
+        .build()?;
+
+    Ok(pool)
+}
+
+/// Delete the pending matches selected by `condition` and record them in the audit
+/// table. `$1` is the outcome, the condition's parameters start at `$2`.
+fn finish_matches_sql(condition: &str) -> String {
+    format!(
+        r#"
+        WITH finished AS (
+            DELETE FROM ongoing_matches
+            WHERE {condition}
+            RETURNING conversation_id, endpoint_id, parameters, history, email, created_at
+        )
+        INSERT INTO progressive_match_audit
+        (conversation_id, endpoint_id, outcome, parameters, history, email, started_at, finished_at)
+        SELECT conversation_id, endpoint_id, $1, parameters, history, email, created_at, now()
+        FROM finished
+        "#
+    )
+}
+
+fn ongoing_match(row: &Row) -> OngoingMatch {
+    OngoingMatch {
+        conversation_id: row.get(0),
+        endpoint_id: row.get(1),
+        parameters: row.get(2),
+        created_at: row.get(3),
+        updated_at: row.get(4),
+        history: row.get(5),
+        email: row.get(6),
+    }
+}
+
+pub struct PostgresProgressiveStore {
+    pool: Pool,
+}
+
+impl PostgresProgressiveStore {
+    pub async fn new(database_url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let pool = create_db_pool(database_url).await?;
+
+        let mut client = pool.get().await?;
+        crate::migrations::prepare(&mut client).await?;
+
+        Ok(Self { pool })
+    }
+
+    async fn finish_matches(
+        &self,
+        outcome: &str,
+        condition: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let mut all_params: Vec<&(dyn ToSql + Sync)> = vec![&outcome];
+        all_params.extend_from_slice(params);
+        Ok(client
+            .execute(&finish_matches_sql(condition), &all_params)
+            .await?)
+    }
+}
+
+#[async_trait]
+impl ProgressiveStore for PostgresProgressiveStore {
+    async fn get(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<Option<OngoingMatch>, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_opt(
+                "SELECT conversation_id, endpoint_id, parameters, created_at, updated_at, history, email
+                 FROM ongoing_matches
+                 WHERE conversation_id = $1 AND endpoint_id = $2 AND updated_at >= $3",
+                &[&conversation_id, &endpoint_id, &updated_since],
+            )
+            .await?;
+        Ok(row.as_ref().map(ongoing_match))
+    }
+
+    async fn pending(
+        &self,
+        conversation_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<Vec<OngoingMatch>, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT conversation_id, endpoint_id, parameters, created_at, updated_at, history, email
+                 FROM ongoing_matches
+                 WHERE conversation_id = $1 AND updated_at >= $2
+                 ORDER BY updated_at DESC",
+                &[&conversation_id, &updated_since],
+            )
+            .await?;
+        Ok(rows.iter().map(ongoing_match).collect())
+    }
+
+    async fn save(&self, ongoing: &OngoingMatch) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                r#"
+                INSERT INTO ongoing_matches
+                (conversation_id, endpoint_id, parameters, created_at, updated_at, history, email)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (conversation_id, endpoint_id)
+                DO UPDATE SET parameters = $3, updated_at = $5, history = $6
+                "#,
+                &[
+                    &ongoing.conversation_id,
+                    &ongoing.endpoint_id,
+                    &ongoing.parameters,
+                    &ongoing.created_at,
+                    &ongoing.updated_at,
+                    &ongoing.history,
+                    &ongoing.email,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn touch(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let updated = client
+            .execute(
+                "UPDATE ongoing_matches SET updated_at = now() WHERE conversation_id = $1 AND endpoint_id = $2 AND updated_at >= $3",
+                &[&conversation_id, &endpoint_id, &updated_since],
+            )
+            .await?;
+        Ok(updated > 0)
+    }
+
+    async fn finish(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        outcome: &str,
+        updated_before: Option<DateTime<Utc>>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let finished = match updated_before {
+            Some(before) => {
+                self.finish_matches(
+                    outcome,
+                    "conversation_id = $2 AND endpoint_id = $3 AND updated_at < $4",
+                    &[&conversation_id, &endpoint_id, &before],
+                )
+                .await?
+            }
+            None => {
+                self.finish_matches(
+                    outcome,
+                    "conversation_id = $2 AND endpoint_id = $3",
+                    &[&conversation_id, &endpoint_id],
+                )
+                .await?
+            }
+        };
+        Ok(finished > 0)
+    }
+
+    async fn expire(
+        &self,
+        updated_before: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        self.finish_matches("expired", "updated_at < $2", &[&updated_before])
+            .await
+    }
+}
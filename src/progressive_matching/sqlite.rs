@@ -0,0 +1,344 @@
+// src/progressive_matching/sqlite.rs - Embedded store for development and tests
+use super::{OngoingMatch, ProgressiveStore};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+/// Same tables as the PostgreSQL migrations (0001, 0004 and 0006), which the test
+/// below checks. Timestamps are microseconds since the epoch.
+const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS ongoing_matches (
+        conversation_id TEXT NOT NULL,
+        endpoint_id TEXT NOT NULL,
+        parameters TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        history TEXT NOT NULL DEFAULT '[]',
+        email TEXT,
+        PRIMARY KEY (conversation_id, endpoint_id)
+    );
+    CREATE INDEX IF NOT EXISTS ongoing_matches_updated_at
+        ON ongoing_matches (updated_at);
+    CREATE TABLE IF NOT EXISTS progressive_match_audit (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        conversation_id TEXT NOT NULL,
+        endpoint_id TEXT NOT NULL,
+        outcome TEXT NOT NULL,
+        parameters TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        finished_at INTEGER NOT NULL,
+        history TEXT NOT NULL DEFAULT '[]',
+        email TEXT
+    );
+"#;
+
+/// Move the selected matches to the audit table. `?1` is the outcome, `?2` the
+/// finish time, the condition's parameters start at `?3`.
+fn finish_matches(
+    connection: &mut Connection,
+    condition: &str,
+    params: &[&dyn rusqlite::ToSql],
+) -> rusqlite::Result<usize> {
+    let transaction = connection.transaction()?;
+    transaction.execute(
+        &format!(
+            "INSERT INTO progressive_match_audit
+             (conversation_id, endpoint_id, outcome, parameters, history, email, started_at, finished_at)
+             SELECT conversation_id, endpoint_id, ?1, parameters, history, email, created_at, ?2
+             FROM ongoing_matches WHERE {condition}"
+        ),
+        params,
+    )?;
+    // Binds the same parameters: SQLite allows unused ones below the highest index
+    let finished = transaction.execute(
+        &format!("DELETE FROM ongoing_matches WHERE {condition}"),
+        params,
+    )?;
+    transaction.commit()?;
+    Ok(finished)
+}
+
+fn add_column(
+    connection: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists: bool = connection.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        connection.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+    Ok(())
+}
+
+fn micros(at: DateTime<Utc>) -> i64 {
+    at.timestamp_micros()
+}
+
+fn ongoing_match(row: &Row) -> rusqlite::Result<OngoingMatch> {
+    let timestamp = |index| {
+        row.get::<_, i64>(index)
+            .map(|at| DateTime::from_timestamp_micros(at).unwrap_or_default())
+    };
+    Ok(OngoingMatch {
+        conversation_id: row.get(0)?,
+        endpoint_id: row.get(1)?,
+        parameters: row.get(2)?,
+        created_at: timestamp(3)?,
+        updated_at: timestamp(4)?,
+        history: row.get(5)?,
+        email: row.get(6)?,
+    })
+}
+
+/// A single connection used from blocking tasks. `sqlite::memory:` keeps the data
+/// in memory for the lifetime of the store.
+pub struct SqliteProgressiveStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteProgressiveStore {
+    /// Open `sqlite://path/to/file.db`, `sqlite:///absolute/path.db` or `sqlite::memory:`
+    pub fn new(database_url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let path = database_url
+            .strip_prefix("sqlite://")
+            .or_else(|| database_url.strip_prefix("sqlite:"))
+            .ok_or("SQLite URLs start with sqlite:")?;
+        let connection = match path {
+            "" | ":memory:" => Connection::open_in_memory()?,
+            path => Connection::open(path)?,
+        };
+        connection.execute_batch(SCHEMA)?;
+        // Databases created before the parameter history and match owners
+        for table in ["ongoing_matches", "progressive_match_audit"] {
+            add_column(&connection, table, "history", "TEXT NOT NULL DEFAULT '[]'")?;
+            add_column(&connection, table, "email", "TEXT")?;
+        }
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn run<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> Result<T, Box<dyn Error + Send + Sync>> {
+        let connection = self.connection.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| "SQLite connection poisoned")?;
+            query(&mut connection).map_err(Box::<dyn Error + Send + Sync>::from)
+        })
+        .await??;
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl ProgressiveStore for SqliteProgressiveStore {
+    async fn get(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<Option<OngoingMatch>, Box<dyn Error + Send + Sync>> {
+        let (conversation_id, endpoint_id) = (conversation_id.to_string(), endpoint_id.to_string());
+        self.run(move |connection| {
+            connection
+                .query_row(
+                    "SELECT conversation_id, endpoint_id, parameters, created_at, updated_at, history, email
+                     FROM ongoing_matches
+                     WHERE conversation_id = ?1 AND endpoint_id = ?2 AND updated_at >= ?3",
+                    params![conversation_id, endpoint_id, micros(updated_since)],
+                    ongoing_match,
+                )
+                .optional()
+        })
+        .await
+    }
+
+    async fn pending(
+        &self,
+        conversation_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<Vec<OngoingMatch>, Box<dyn Error + Send + Sync>> {
+        let conversation_id = conversation_id.to_string();
+        self.run(move |connection| {
+            connection
+                .prepare(
+                    "SELECT conversation_id, endpoint_id, parameters, created_at, updated_at, history, email
+                     FROM ongoing_matches
+                     WHERE conversation_id = ?1 AND updated_at >= ?2
+                     ORDER BY updated_at DESC",
+                )?
+                .query_map(
+                    params![conversation_id, micros(updated_since)],
+                    ongoing_match,
+                )?
+                .collect()
+        })
+        .await
+    }
+
+    async fn save(&self, ongoing: &OngoingMatch) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let ongoing = ongoing.clone();
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT INTO ongoing_matches
+                 (conversation_id, endpoint_id, parameters, created_at, updated_at, history, email)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT (conversation_id, endpoint_id)
+                 DO UPDATE SET parameters = ?3, updated_at = ?5, history = ?6",
+                params![
+                    ongoing.conversation_id,
+                    ongoing.endpoint_id,
+                    ongoing.parameters,
+                    micros(ongoing.created_at),
+                    micros(ongoing.updated_at),
+                    ongoing.history,
+                    ongoing.email,
+                ],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn touch(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        updated_since: DateTime<Utc>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let (conversation_id, endpoint_id) = (conversation_id.to_string(), endpoint_id.to_string());
+        let updated = self
+            .run(move |connection| {
+                connection.execute(
+                    "UPDATE ongoing_matches SET updated_at = ?4
+                     WHERE conversation_id = ?1 AND endpoint_id = ?2 AND updated_at >= ?3",
+                    params![
+                        conversation_id,
+                        endpoint_id,
+                        micros(updated_since),
+                        micros(Utc::now())
+                    ],
+                )
+            })
+            .await?;
+        Ok(updated > 0)
+    }
+
+    async fn finish(
+        &self,
+        conversation_id: &str,
+        endpoint_id: &str,
+        outcome: &str,
+        updated_before: Option<DateTime<Utc>>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let (conversation_id, endpoint_id, outcome) = (
+            conversation_id.to_string(),
+            endpoint_id.to_string(),
+            outcome.to_string(),
+        );
+        // Without a bound, every match qualifies
+        let before = updated_before.map(micros).unwrap_or(i64::MAX);
+        let finished = self
+            .run(move |connection| {
+                finish_matches(
+                    connection,
+                    "conversation_id = ?3 AND endpoint_id = ?4 AND updated_at < ?5",
+                    params![
+                        outcome,
+                        micros(Utc::now()),
+                        conversation_id,
+                        endpoint_id,
+                        before
+                    ],
+                )
+            })
+            .await?;
+        Ok(finished > 0)
+    }
+
+    async fn expire(
+        &self,
+        updated_before: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let expired = self
+            .run(move |connection| {
+                finish_matches(
+                    connection,
+                    "updated_at < ?3",
+                    params!["expired", micros(Utc::now()), micros(updated_before)],
+                )
+            })
+            .await?;
+        Ok(expired as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations::MIGRATIONS;
+    use std::collections::BTreeSet;
+
+    /// Columns the migrations create in `table` or add to it later
+    fn postgres_columns(table: &str) -> BTreeSet<String> {
+        let create = format!("CREATE TABLE IF NOT EXISTS {table} (");
+        let add = format!("ALTER TABLE {table} ADD COLUMN IF NOT EXISTS ");
+        let mut columns = BTreeSet::new();
+        for migration in MIGRATIONS {
+            if let Some(start) = migration.sql.find(&create) {
+                let body = &migration.sql[start + create.len()..];
+                let body = &body[..body.find("\n);").unwrap()];
+                columns.extend(
+                    body.lines()
+                        .filter_map(|line| line.split_whitespace().next())
+                        .filter(|name| *name != "PRIMARY")
+                        .map(str::to_string),
+                );
+            }
+            columns.extend(migration.sql.lines().filter_map(|line| {
+                line.trim()
+                    .strip_prefix(&add)
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .map(str::to_string)
+            }));
+        }
+        columns
+    }
+
+    fn sqlite_columns(connection: &Connection, table: &str) -> BTreeSet<String> {
+        let mut statement = connection
+            .prepare("SELECT name FROM pragma_table_info(?1)")
+            .unwrap();
+        let rows = statement.query_map([table], |row| row.get(0)).unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn test_schema_matches_the_postgres_migrations() {
+        let store = SqliteProgressiveStore::new("sqlite::memory:").unwrap();
+        let connection = store.connection.lock().unwrap();
+        for table in ["ongoing_matches", "progressive_match_audit"] {
+            let mut expected = postgres_columns(table);
+            // Left over in PostgreSQL: completion is computed on read, never stored
+            if table == "ongoing_matches" {
+                assert!(expected.remove("completion_percentage"));
+            }
+            assert!(expected.contains("email"), "{table}");
+            assert_eq!(sqlite_columns(&connection, table), expected, "{table}");
+        }
+    }
+}
@@ -0,0 +1,374 @@
+// src/prompts/budget.rs - Fit prompts into the model context window before sending
+//
+// A prompt is a template plus named sections. When the rendered prompt does not
+// leave `max_tokens` of room in the context window, list sections are trimmed from
+// the last one back, as later sections are taken to matter less: long items are
+// shortened first, then items are dropped from the end of the list (so lists should
+// put what matters most first, e.g. history newest first). If the prompt still does
+// not fit, the output allowance shrinks down to `min_output_tokens` before the call
+// is refused.
+use crate::app_log;
+use crate::models::config::{load_prompt_budget_config, PromptBudgetConfig};
+use crate::models::providers::token_counter::TokenCounter;
+use crate::models::providers::ModelConfig;
+use crate::prompts::template::{Template, Value, Vars};
+use std::error::Error;
+
+/// A template variable, either text or records for `{#each}`
+#[derive(Debug, Clone)]
+pub struct PromptSection {
+    name: &'static str,
+    items: Items,
+    /// Fixed sections are never trimmed
+    fixed: bool,
+    min_items: usize,
+    /// Items (or record fields) longer than this are shortened before any item is dropped
+    item_chars: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Items {
+    /// Joined into one text value
+    Lines(Vec<String>, &'static str),
+    Records(Vec<Vars>),
+}
+
+impl PromptSection {
+    /// Never trimmed, e.g. the user's sentence
+    pub fn fixed(name: &'static str, text: impl Into<String>) -> Self {
+        Self {
+            name,
+            items: Items::Lines(vec![text.into()], ""),
+            fixed: true,
+            min_items: 1,
+            item_chars: None,
+        }
+    }
+
+    /// A list that may lose items from its end, down to one
+    pub fn list(name: &'static str, items: Vec<String>, separator: &'static str) -> Self {
+        Self {
+            name,
+            items: Items::Lines(items, separator),
+            fixed: false,
+            min_items: 1,
+            item_chars: None,
+        }
+    }
+
+    /// Records for `{#each name}`, trimmed like `list`
+    pub fn records(name: &'static str, records: Vec<Vars>) -> Self {
+        Self {
+            name,
+            items: Items::Records(records),
+            fixed: false,
+            min_items: 1,
+            item_chars: None,
+        }
+    }
+
+    pub fn keep_at_least(mut self, min_items: usize) -> Self {
+        self.min_items = min_items;
+        self
+    }
+
+    pub fn shorten_items_to(mut self, chars: usize) -> Self {
+        self.item_chars = Some(chars);
+        self
+    }
+
+    fn len(&self) -> usize {
+        match &self.items {
+            Items::Lines(lines, _) => lines.len(),
+            Items::Records(records) => records.len(),
+        }
+    }
+
+    /// Shorten texts longer than `limit`, returning how many were
+    fn shorten(&mut self, limit: usize) -> usize {
+        let texts: Vec<&mut String> = match &mut self.items {
+            Items::Lines(lines, _) => lines.iter_mut().collect(),
+            Items::Records(records) => records
+                .iter_mut()
+                .flat_map(|record| record.values_mut())
+                .filter_map(|value| match value {
+                    Value::Text(text) => Some(text),
+                    _ => None,
+                })
+                .collect(),
+        };
+        let mut shortened = 0;
+        for text in texts {
+            if text.chars().count() > limit {
+                *text = text.chars().take(limit).collect::<String>() + "…";
+                shortened += 1;
+            }
+        }
+        shortened
+    }
+
+    /// Drop the last item, returning its text to estimate the tokens freed
+    fn pop(&mut self) -> String {
+        match &mut self.items {
+            Items::Lines(lines, separator) => lines.pop().unwrap_or_default() + separator,
+            Items::Records(records) => records
+                .pop()
+                .unwrap_or_default()
+                .into_values()
+                .filter_map(|value| match value {
+                    Value::Text(text) => Some(text),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    fn value(&self) -> Value {
+        match &self.items {
+            Items::Lines(lines, separator) => Value::Text(lines.join(separator)),
+            Items::Records(records) => Value::List(records.clone()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BudgetedPrompt {
+    pub prompt: String,
+    /// The caller's model config, with `max_tokens` lowered if the window required it
+    pub model_config: ModelConfig,
+}
+
+/// Render `template` with `sections`, trimmed to fit the provider's context window.
+/// The sections must supply exactly the template's variables.
+pub async fn fit(
+    template: &Template,
+    sections: Vec<PromptSection>,
+    provider: &str,
+    model_config: &ModelConfig,
+) -> Result<BudgetedPrompt, Box<dyn Error + Send + Sync>> {
+    let config = load_prompt_budget_config().await?;
+    fit_with(&config, template, sections, provider, model_config)
+}
+
+pub fn fit_with(
+    config: &PromptBudgetConfig,
+    template: &Template,
+    mut sections: Vec<PromptSection>,
+    provider: &str,
+    model_config: &ModelConfig,
+) -> Result<BudgetedPrompt, Box<dyn Error + Send + Sync>> {
+    let counter = TokenCounter::new();
+    let count = |text: &str| counter.estimate_tokens(text, provider);
+
+    let mut prompt = render(template, &sections)?;
+    let mut prompt_tokens = count(&prompt);
+    let mut model_config = model_config.clone();
+    if !config.enabled {
+        return Ok(BudgetedPrompt {
+            prompt,
+            model_config,
+        });
+    }
+
+    let model = model_config.model_for(provider).to_string();
+    let window = config
+        .context_window(&model, provider)
+        .saturating_sub(config.safety_margin);
+    let budget = window.saturating_sub(model_config.max_tokens);
+    let mut trimmed = Vec::new();
+
+    let order: Vec<usize> = (0..sections.len())
+        .rev()
+        .filter(|&i| !sections[i].fixed)
+        .collect();
+
+    for i in order {
+        if prompt_tokens <= budget {
+            break;
+        }
+
+        if let Some(limit) = sections[i].item_chars {
+            let shortened = sections[i].shorten(limit);
+            if shortened > 0 {
+                trimmed.push(format!(
+                    "shortened {shortened} {} item(s) to {limit} chars",
+                    sections[i].name
+                ));
+                prompt = render(template, &sections)?;
+                prompt_tokens = count(&prompt);
+            }
+        }
+
+        let mut dropped = 0;
+        while prompt_tokens > budget && sections[i].len() > sections[i].min_items {
+            // Drop enough items to cover the overshoot, then measure the real prompt
+            let section = &mut sections[i];
+            let mut freed = 0;
+            while freed < prompt_tokens - budget && section.len() > section.min_items {
+                freed += count(&section.pop());
+                dropped += 1;
+            }
+            prompt = render(template, &sections)?;
+            prompt_tokens = count(&prompt);
+        }
+        if dropped > 0 {
+            trimmed.push(format!(
+                "dropped {dropped} of {} {} item(s)",
+                sections[i].len() + dropped,
+                sections[i].name
+            ));
+        }
+    }
+
+    if !trimmed.is_empty() {
+        app_log!(
+            warn,
+            "Prompt trimmed to fit {} ({} tokens for input): {}",
+            model,
+            budget,
+            trimmed.join("; ")
+        );
+    }
+
+    if prompt_tokens > budget {
+        let available = window.saturating_sub(prompt_tokens);
+        if available < config.min_output_tokens {
+            return Err(format!(
+                "Prompt needs {prompt_tokens} tokens, {model} has room for {window} \
+                 including {} output tokens",
+                config.min_output_tokens
+            )
+            .into());
+        }
+        app_log!(
+            warn,
+            "Lowering max_tokens for {} from {} to {} to fit a {} token prompt",
+            model,
+            model_config.max_tokens,
+            available,
+            prompt_tokens
+        );
+        model_config.max_tokens = available;
+    }
+
+    app_log!(debug, "Prompt for {} is {} tokens", model, prompt_tokens);
+
+    Ok(BudgetedPrompt {
+        prompt,
+        model_config,
+    })
+}
+
+fn render(
+    template: &Template,
+    sections: &[PromptSection],
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let vars: Vars = sections
+        .iter()
+        .map(|section| (section.name.to_string(), section.value()))
+        .collect();
+    Ok(template.render(&vars)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(max_tokens: u32) -> ModelConfig {
+        ModelConfig {
+            max_tokens,
+            ..Default::default()
+        }
+    }
+
+    // Without a tokenizer, "test" counts one token per four characters
+    fn config(window: u32) -> PromptBudgetConfig {
+        PromptBudgetConfig {
+            context_windows: [("test".to_string(), window)].into(),
+            safety_margin: 0,
+            min_output_tokens: 10,
+            ..Default::default()
+        }
+    }
+
+    fn endpoints() -> PromptSection {
+        let items = (0..10)
+            .map(|i| format!("- endpoint_{i} ({})", "long description ".repeat(10)))
+            .collect();
+        PromptSection::list("endpoints_list", items, "\n").shorten_items_to(40)
+    }
+
+    #[test]
+    fn test_prompt_within_budget_is_untouched() {
+        let sections = vec![
+            PromptSection::fixed("sentence", "send {endpoints_list}"),
+            endpoints(),
+        ];
+        let fitted = fit_with(
+            &config(10_000),
+            &Template::parse("{sentence}\n{endpoints_list}").unwrap(),
+            sections,
+            "test",
+            &model(100),
+        )
+        .unwrap();
+
+        assert!(fitted
+            .prompt
+            .starts_with("send {endpoints_list}\n- endpoint_0"));
+        assert_eq!(fitted.prompt.matches("- endpoint_").count(), 10);
+        assert_eq!(fitted.model_config.max_tokens, 100);
+    }
+
+    #[test]
+    fn test_shortens_then_drops_from_the_end() {
+        let fitted = fit_with(
+            &config(200),
+            &Template::parse("Pick one:\n{endpoints_list}").unwrap(),
+            vec![endpoints()],
+            "test",
+            &model(150),
+        )
+        .unwrap();
+
+        assert!(TokenCounter::new().estimate_tokens(&fitted.prompt, "test") <= 50);
+        assert!(fitted.prompt.contains("…"));
+        assert!(fitted.prompt.contains("endpoint_0"));
+        assert!(!fitted.prompt.contains("endpoint_9"));
+
+        // Same input, same result
+        let again = fit_with(
+            &config(200),
+            &Template::parse("Pick one:\n{endpoints_list}").unwrap(),
+            vec![endpoints()],
+            "test",
+            &model(150),
+        )
+        .unwrap();
+        assert_eq!(fitted.prompt, again.prompt);
+    }
+
+    #[test]
+    fn test_output_allowance_shrinks_before_refusing() {
+        let sentence = PromptSection::fixed("sentence", "x".repeat(400));
+        let fitted = fit_with(
+            &config(150),
+            &Template::parse("{sentence}").unwrap(),
+            vec![sentence.clone()],
+            "test",
+            &model(100),
+        )
+        .unwrap();
+        assert_eq!(fitted.model_config.max_tokens, 50);
+
+        assert!(fit_with(
+            &config(105),
+            &Template::parse("{sentence}").unwrap(),
+            vec![sentence],
+            "test",
+            &model(100)
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,183 @@
+// src/prompts/experiments.rs - A/B experiments between prompt versions
+//
+// An experiment splits the traffic of one prompt between versions by weight. The
+// variant is picked by hashing the experiment name with the caller's email (or
+// conversation id), so a caller keeps the same variant as long as the experiment
+// definition does not change.
+use crate::app_log;
+use crate::metrics;
+use crate::usage::{self, ledger::UsageLedger, UsageScope};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Experiment {
+    /// Name of the prompt in `prompts`
+    pub prompt: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub sticky: StickyKey,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StickyKey {
+    #[default]
+    Email,
+    Conversation,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Variant {
+    pub version: String,
+    /// Relative share of traffic
+    pub weight: u32,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Experiment {
+    /// Version for the caller of `scope`, `None` when the scope has no sticky key
+    pub fn assign(&self, name: &str, scope: &UsageScope) -> Option<&str> {
+        let key = match self.sticky {
+            StickyKey::Email => scope.email.as_ref()?.to_lowercase(),
+            StickyKey::Conversation => scope.conversation_id.clone()?,
+        };
+        Some(self.pick(&format!("{name}:{key}")))
+    }
+
+    fn pick(&self, key: &str) -> &str {
+        let total: u64 = self.variants.iter().map(|v| v.weight as u64).sum();
+        let mut point = fnv1a(key.as_bytes()) % total.max(1);
+        for variant in &self.variants {
+            if point < variant.weight as u64 {
+                return &variant.version;
+            }
+            point -= variant.weight as u64;
+        }
+        &self.variants[0].version
+    }
+}
+
+/// Stable across processes and releases, unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Check experiments against the prompt versions they split traffic between
+pub fn validate(
+    experiments: &BTreeMap<String, Experiment>,
+    has_version: impl Fn(&str, &str) -> bool,
+) -> Result<(), String> {
+    let mut prompts = HashSet::new();
+    for (name, experiment) in experiments.iter().filter(|(_, e)| e.enabled) {
+        if !prompts.insert(experiment.prompt.as_str()) {
+            return Err(format!(
+                "Experiment {name}: prompt {} already has an enabled experiment",
+                experiment.prompt
+            ));
+        }
+        if experiment.variants.iter().map(|v| v.weight).sum::<u32>() == 0 {
+            return Err(format!(
+                "Experiment {name}: variant weights must not all be 0"
+            ));
+        }
+        for variant in &experiment.variants {
+            if !has_version(&experiment.prompt, &variant.version) {
+                return Err(format!(
+                    "Experiment {name}: prompt {} has no version {}",
+                    experiment.prompt, variant.version
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Record how the current request ended for every experiment it was assigned to.
+/// `outcome` is `matched`, `answered` (general or help question) or `failed`.
+pub async fn record_outcome(ledger: Option<&UsageLedger>, outcome: &str) {
+    let email = usage::current_scope().and_then(|scope| scope.email);
+    for (experiment, variant) in usage::assigned_variants() {
+        metrics::record_experiment_outcome(&experiment, &variant, outcome);
+        if let Some(ledger) = ledger {
+            if let Err(e) = ledger
+                .record_experiment_outcome(&experiment, &variant, email.as_deref(), outcome)
+                .await
+            {
+                app_log!(warn, "Failed to record experiment outcome: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn experiment(weights: &[(&str, u32)]) -> Experiment {
+        Experiment {
+            prompt: "find_endpoint".to_string(),
+            enabled: true,
+            sticky: StickyKey::Email,
+            variants: weights
+                .iter()
+                .map(|(version, weight)| Variant {
+                    version: version.to_string(),
+                    weight: *weight,
+                })
+                .collect(),
+        }
+    }
+
+    fn scope(email: &str) -> UsageScope {
+        UsageScope {
+            email: Some(email.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_assignment_is_sticky_and_follows_weights() {
+        let split = experiment(&[("v2", 50), ("v3", 50)]);
+        let first = split.assign("trial", &scope("alice@example.com"));
+        assert_eq!(split.assign("trial", &scope("Alice@Example.com")), first);
+
+        let v3 = (0..1000)
+            .filter(|i| {
+                split.assign("trial", &scope(&format!("user{i}@example.com"))) == Some("v3")
+            })
+            .count();
+        assert!((400..600).contains(&v3), "v3 got {v3} of 1000");
+
+        let all_v2 = experiment(&[("v2", 1), ("v3", 0)]);
+        assert_eq!(
+            all_v2.assign("trial", &scope("bob@example.com")),
+            Some("v2")
+        );
+        assert_eq!(split.assign("trial", &UsageScope::default()), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let has_version = |prompt: &str, version: &str| {
+            prompt == "find_endpoint" && ["v1", "v2"].contains(&version)
+        };
+        let mut experiments =
+            BTreeMap::from([("trial".to_string(), experiment(&[("v1", 10), ("v2", 90)]))]);
+        assert!(validate(&experiments, has_version).is_ok());
+
+        experiments.insert("other".to_string(), experiment(&[("v2", 1)]));
+        assert!(validate(&experiments, has_version).is_err());
+
+        experiments.remove("other");
+        experiments.insert("trial".to_string(), experiment(&[("v9", 1)]));
+        assert!(validate(&experiments, has_version).is_err());
+    }
+}
@@ -1,85 +1,267 @@
 use crate::app_log;
+use crate::trace;
+use crate::usage;
+use experiments::Experiment;
+use overrides::{PromptOverride, Tenant};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::error::Error;
+use std::fmt;
+use template::{Template, Vars};
+
+pub mod budget;
+pub mod experiments;
+pub mod overrides;
+pub mod template;
+
+/// Variables the code renders each prompt with. The default version, every override
+/// and every version of an enabled experiment must use exactly these.
+const SUPPLIED_VARIABLES: &[(&str, &[&str])] = &[
+    ("find_endpoint", &["input_sentence", "endpoints"]),
+    ("match_fields", &["input_fields", "parameters"]),
+    ("sentence_to_json", &["sentence"]),
+    ("intent_classification", &["sentence", "endpoints"]),
+    (
+        "help_response",
+        &["sentence", "endpoints_list", "detected_language"],
+    ),
+    (
+        "extract_followup_parameters_mapping",
+        &["sentence", "available_parameters", "current_values"],
+    ),
+    ("language_detection", &["sentence"]),
+    (
+        "followup_intent",
+        &["sentence", "pending_request", "missing_fields", "other_requests"],
+    ),
+    ("polish_missing_fields", &["question", "language"]),
+];
 
 #[derive(Debug, Deserialize)]
 struct PromptVersion {
     template: String,
+    /// When set, the template must use exactly these variables
+    #[serde(default)]
+    variables: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct PromptVersions {
     versions: HashMap<String, PromptVersion>,
     default_version: String,
+    /// Versions for tenants and languages, see `overrides.rs`
+    #[serde(default)]
+    overrides: Vec<PromptOverride>,
 }
 
 #[derive(Debug, Deserialize)]
 struct PromptConfig {
     prompts: HashMap<String, PromptVersions>,
+    /// Keyed by experiment name, see `experiments.rs`
+    #[serde(default)]
+    experiments: BTreeMap<String, Experiment>,
+    /// Caller groups that prompt overrides can name as their tenant
+    #[serde(default)]
+    tenants: BTreeMap<String, Tenant>,
+}
+
+/// A problem found while loading prompts.yaml
+#[derive(Debug)]
+pub struct PromptIssue {
+    pub location: String,
+    pub message: String,
+    /// Fatal issues stop the prompts from loading, others only show in `prompts check`
+    pub fatal: bool,
+}
+
+impl fmt::Display for PromptIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
 }
 
 pub struct PromptManager {
     config: PromptConfig,
+    /// Parsed templates by prompt, then version
+    templates: HashMap<String, HashMap<String, Template>>,
 }
 
-impl PromptManager {
-    pub async fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let prompts_path = env::var("PROMPTS_PATH").unwrap_or_else(|_| "prompts.yaml".to_string());
-        let config_str = tokio::fs::read_to_string(&prompts_path).await?;
-        let config: PromptConfig = serde_yaml::from_str(&config_str)?;
-        Ok(Self { config })
+fn prompts_path() -> String {
+    env::var("PROMPTS_PATH").unwrap_or_else(|_| "prompts.yaml".to_string())
+}
+
+/// Check the prompts file at `path` (or `PROMPTS_PATH`) without loading it
+pub async fn check(path: Option<&str>) -> Result<Vec<PromptIssue>, Box<dyn Error + Send + Sync>> {
+    let path = path.map(str::to_string).unwrap_or_else(prompts_path);
+    let config_str = tokio::fs::read_to_string(&path).await?;
+    let config: PromptConfig = serde_yaml::from_str(&config_str)?;
+    Ok(compile(&config).1)
+}
+
+/// Parse every template and check it against the variables the code supplies
+fn compile(
+    config: &PromptConfig,
+) -> (HashMap<String, HashMap<String, Template>>, Vec<PromptIssue>) {
+    let mut templates = HashMap::new();
+    let mut issues = Vec::new();
+    let mut issue = |location: String, message: String, fatal: bool| {
+        issues.push(PromptIssue {
+            location,
+            message,
+            fatal,
+        })
+    };
+
+    let names: BTreeSet<&String> = config.prompts.keys().collect();
+    for name in names {
+        let prompt = &config.prompts[name];
+        if !prompt.versions.contains_key(&prompt.default_version) {
+            issue(
+                name.clone(),
+                format!("default_version {} does not exist", prompt.default_version),
+                true,
+            );
+        }
+
+        for problem in overrides::validate(&prompt.overrides, &config.tenants, |version| {
+            prompt.versions.contains_key(version)
+        }) {
+            issue(name.clone(), problem, true);
+        }
+
+        // Versions that serve traffic have to match what the code supplies
+        let mut live: BTreeSet<&str> = BTreeSet::from([prompt.default_version.as_str()]);
+        live.extend(prompt.overrides.iter().map(|o| o.version.as_str()));
+        for experiment in config.experiments.values() {
+            if experiment.enabled && experiment.prompt == *name {
+                live.extend(experiment.variants.iter().map(|v| v.version.as_str()));
+            }
+        }
+        let supplied = SUPPLIED_VARIABLES
+            .iter()
+            .find(|(prompt, _)| prompt == name)
+            .map(|(_, vars)| vars.iter().map(|v| v.to_string()).collect::<BTreeSet<_>>());
+
+        let versions: BTreeMap<&String, &PromptVersion> = prompt.versions.iter().collect();
+        for (version, source) in versions {
+            let location = format!("{name}.{version}");
+            let parsed = match &source.variables {
+                Some(declared) => Template::with_variables(&source.template, declared),
+                None => Template::parse(&source.template),
+            };
+            let template = match parsed {
+                Ok(template) => template,
+                Err(e) => {
+                    issue(location, e.to_string(), true);
+                    continue;
+                }
+            };
+            if let Some(supplied) = &supplied {
+                if template.variables() != supplied {
+                    let list = |vars: &BTreeSet<String>| {
+                        vars.iter().cloned().collect::<Vec<_>>().join(", ")
+                    };
+                    issue(
+                        location,
+                        format!(
+                            "uses [{}] but is rendered with [{}]",
+                            list(template.variables()),
+                            list(supplied)
+                        ),
+                        live.contains(version.as_str()),
+                    );
+                }
+            }
+            templates
+                .entry(name.clone())
+                .or_insert_with(HashMap::new)
+                .insert(version.clone(), template);
+        }
     }
 
-    pub fn format_extract_followup_parameters_with_mapping(
-        &self,
-        sentence: &str,
-        available_parameters: &str,
-        version: Option<&str>,
-    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let template = self
-            .get_prompt("extract_followup_parameters_mapping", version)
-            .ok_or("extract_followup_parameters_mapping prompt not found in prompts.yaml")?;
+    for (name, _) in SUPPLIED_VARIABLES {
+        if !config.prompts.contains_key(*name) {
+            issue(
+                name.to_string(),
+                "missing, the code renders this prompt".to_string(),
+                true,
+            );
+        }
+    }
 
-        Ok(template
-            .replace("{sentence}", sentence)
-            .replace("{available_parameters}", available_parameters))
+    if let Err(e) = experiments::validate(&config.experiments, |prompt, version| {
+        config
+            .prompts
+            .get(prompt)
+            .is_some_and(|p| p.versions.contains_key(version))
+    }) {
+        issue("experiments".to_string(), e, true);
     }
 
-    pub fn language_detection(&self, sentence: &str, version: Option<&str>) -> String {
-        let template = self
-            .get_prompt("language_detection", version)
-            .unwrap_or_default();
+    (templates, issues)
+}
+
+impl PromptManager {
+    pub async fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let prompts_path = prompts_path();
+        let config_str = tokio::fs::read_to_string(&prompts_path).await?;
+        let config: PromptConfig = serde_yaml::from_str(&config_str)?;
 
-        template.replace("{sentence}", sentence)
+        let (templates, issues) = compile(&config);
+        let fatal: Vec<String> = issues
+            .iter()
+            .filter(|issue| issue.fatal)
+            .map(|issue| issue.to_string())
+            .collect();
+        if !fatal.is_empty() {
+            return Err(
+                format!("Invalid prompts in {}: {}", prompts_path, fatal.join("; ")).into(),
+            );
+        }
+        for issue in issues {
+            app_log!(debug, "Prompt warning: {}", issue);
+        }
+
+        Ok(Self { config, templates })
     }
 
-    pub fn format_help_response_with_language(
+    pub fn language_detection(
         &self,
         sentence: &str,
-        endpoints_list: &str,
-        detected_language: &str,
         version: Option<&str>,
-    ) -> String {
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
         let template = self
-            .get_prompt("help_response", version)
-            .unwrap_or_default();
-
-        template
-            .replace("{sentence}", sentence)
-            .replace("{endpoints_list}", endpoints_list)
-            .replace("{detected_language}", detected_language)
+            .get_prompt("language_detection", version)
+            .ok_or("language_detection prompt not found")?;
+        let vars = Vars::from([("sentence".to_string(), sentence.into())]);
+        Ok(template.render(&vars)?)
     }
 
-    /// Gets a prompt template by name and optional version
-    pub fn get_prompt(&self, name: &str, version: Option<&str>) -> Option<&str> {
+    /// Gets a prompt template by name and optional version. A tenant or language
+    /// override for the current caller comes first, then the variant of an enabled
+    /// experiment on the prompt.
+    pub fn get_prompt(&self, name: &str, version: Option<&str>) -> Option<&Template> {
         let prompt_versions = self.config.prompts.get(name)?;
+        let templates = self.templates.get(name)?;
 
-        let version_key = version.unwrap_or(&prompt_versions.default_version);
+        let prompt_override = usage::current_scope().and_then(|scope| {
+            overrides::resolve(&prompt_versions.overrides, &self.config.tenants, &scope)
+        });
+        let version_key = match prompt_override {
+            Some(prompt_override) => prompt_override.version.as_str(),
+            None => self
+                .experiment_version(name)
+                .or(version)
+                .unwrap_or(&prompt_versions.default_version),
+        };
 
-        match prompt_versions.versions.get(version_key) {
-            Some(version) => Some(&version.template),
+        match templates.get(version_key) {
+            Some(template) => {
+                let prompt_override = prompt_override.map(|o| o.to_string());
+                trace::note_prompt(name, version_key, prompt_override.as_deref());
+                Some(template)
+            }
             None => {
                 app_log!(
                     warn,
@@ -87,50 +269,50 @@ impl PromptManager {
                     version_key,
                     name
                 );
-                prompt_versions
-                    .versions
-                    .get(&prompt_versions.default_version)
-                    .map(|v| &v.template)
-                    .map(|x| x.as_str())
+                trace::note_prompt(name, &prompt_versions.default_version, None);
+                templates.get(&prompt_versions.default_version)
             }
         }
     }
 
-    pub fn format_intent_classification(
-        &self,
-        sentence: &str,
-        endpoints_list: &str,
-        version: Option<&str>,
-    ) -> String {
-        let template = self
-            .get_prompt("intent_classification", version)
-            .unwrap_or_default();
-
-        template
-            .replace("{sentence}", sentence)
-            .replace("{endpoints_list}", endpoints_list)
+    /// Variant of the enabled experiment on `prompt` for the current caller
+    fn experiment_version(&self, prompt: &str) -> Option<&str> {
+        let (name, experiment) = self
+            .config
+            .experiments
+            .iter()
+            .find(|(_, e)| e.enabled && e.prompt == prompt)?;
+        let version = experiment.assign(name, &usage::current_scope()?)?;
+        usage::note_variant(name, version);
+        Some(version)
     }
+}
 
-    pub fn format_find_endpoint_v2(
-        &self,
-        input_sentence: &str,
-        endpoints_list: &str,
-        version: Option<&str>,
-    ) -> String {
-        let template = self
-            .get_prompt("find_endpoint", version)
-            .unwrap_or_default();
-
-        template
-            .replace("{input_sentence}", input_sentence)
-            .replace("{endpoints_list}", endpoints_list)
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn format_sentence_to_json(&self, sentence: &str, version: Option<&str>) -> String {
-        let template = self
-            .get_prompt("sentence_to_json", version)
-            .unwrap_or_default();
+    #[test]
+    fn test_shipped_prompts_render_with_supplied_variables() {
+        let config: PromptConfig =
+            serde_yaml::from_str(&std::fs::read_to_string("prompts.yaml").unwrap()).unwrap();
+        let (templates, issues) = compile(&config);
+        let fatal: Vec<String> = issues
+            .iter()
+            .filter(|issue| issue.fatal)
+            .map(|issue| issue.to_string())
+            .collect();
+        assert!(fatal.is_empty(), "{fatal:?}");
 
-        template.replace("{sentence}", sentence)
+        let endpoints = vec![Vars::from([
+            ("id".to_string(), "send_email".into()),
+            ("description".to_string(), "Send an email".into()),
+        ])];
+        let vars = Vars::from([
+            ("input_sentence".to_string(), "mail bob".into()),
+            ("endpoints".to_string(), template::Value::List(endpoints)),
+        ]);
+        let prompt = templates["find_endpoint"]["v2"].render(&vars).unwrap();
+        assert!(prompt.contains("Available endpoints:\n- send_email (Send an email)\n\nRespond"));
     }
 }
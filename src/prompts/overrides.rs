@@ -0,0 +1,219 @@
+// src/prompts/overrides.rs - Prompt versions chosen by tenant and language
+//
+// A prompt can name other versions for callers of a tenant, for requests in a
+// language, or both. A tenant is an email domain, a group from `tenants`, or the
+// tenant claim of the caller's verified credentials. The most specific matching
+// override wins: tenant and language, then tenant, then language; without a match
+// the prompt's usual version applies.
+use crate::usage::UsageScope;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PromptOverride {
+    /// Email domain, or a group name from `tenants`
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// Two-letter language code of the request
+    #[serde(default)]
+    pub language: Option<String>,
+    pub version: String,
+}
+
+/// A named group of callers
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Tenant {
+    #[serde(default)]
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub emails: Vec<String>,
+}
+
+impl fmt::Display for PromptOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.tenant, &self.language) {
+            (Some(tenant), Some(language)) => write!(f, "tenant={tenant} language={language}"),
+            (Some(tenant), None) => write!(f, "tenant={tenant}"),
+            (None, Some(language)) => write!(f, "language={language}"),
+            (None, None) => f.write_str("any"),
+        }
+    }
+}
+
+fn belongs(email: &str, tenant: &str, tenants: &BTreeMap<String, Tenant>) -> bool {
+    let email = email.to_lowercase();
+    let domain = email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .unwrap_or("");
+    if tenant.eq_ignore_ascii_case(domain) {
+        return true;
+    }
+    tenants.get(tenant).is_some_and(|group| {
+        group.domains.iter().any(|d| d.eq_ignore_ascii_case(domain))
+            || group.emails.iter().any(|e| e.eq_ignore_ascii_case(&email))
+    })
+}
+
+/// The most specific override matching the caller of `scope`, if any
+pub fn resolve<'a>(
+    overrides: &'a [PromptOverride],
+    tenants: &BTreeMap<String, Tenant>,
+    scope: &UsageScope,
+) -> Option<&'a PromptOverride> {
+    let mut best: Option<(&PromptOverride, (bool, bool))> = None;
+    for candidate in overrides {
+        let tenant_matches = match &candidate.tenant {
+            Some(tenant) => {
+                scope
+                    .tenant
+                    .as_deref()
+                    .is_some_and(|verified| verified.eq_ignore_ascii_case(tenant))
+                    || scope
+                        .email
+                        .as_deref()
+                        .is_some_and(|email| belongs(email, tenant, tenants))
+            }
+            None => true,
+        };
+        let language_matches = match &candidate.language {
+            Some(language) => scope
+                .language
+                .as_deref()
+                .is_some_and(|l| l.eq_ignore_ascii_case(language)),
+            None => true,
+        };
+        if !tenant_matches || !language_matches {
+            continue;
+        }
+        // Earlier overrides win ties, e.g. two groups the caller is in
+        let rank = (candidate.tenant.is_some(), candidate.language.is_some());
+        if best.is_none_or(|(_, best_rank)| rank > best_rank) {
+            best = Some((candidate, rank));
+        }
+    }
+    best.map(|(candidate, _)| candidate)
+}
+
+/// Check the overrides of one prompt, returning one message per problem
+pub fn validate(
+    overrides: &[PromptOverride],
+    tenants: &BTreeMap<String, Tenant>,
+    has_version: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen = HashSet::new();
+    for candidate in overrides {
+        if candidate.tenant.is_none() && candidate.language.is_none() {
+            problems.push(format!(
+                "override to {} needs a tenant or a language",
+                candidate.version
+            ));
+            continue;
+        }
+        if !has_version(&candidate.version) {
+            problems.push(format!(
+                "override {candidate} uses missing version {}",
+                candidate.version
+            ));
+        }
+        if let Some(tenant) = &candidate.tenant {
+            if !tenants.contains_key(tenant) && !tenant.contains('.') {
+                problems.push(format!(
+                    "override {candidate}: {tenant} is neither a tenant group nor an email domain"
+                ));
+            }
+        }
+        if !seen.insert((&candidate.tenant, &candidate.language)) {
+            problems.push(format!("override {candidate} is defined twice"));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn over(tenant: Option<&str>, language: Option<&str>, version: &str) -> PromptOverride {
+        PromptOverride {
+            tenant: tenant.map(str::to_string),
+            language: language.map(str::to_string),
+            version: version.to_string(),
+        }
+    }
+
+    fn scope(email: &str, language: &str) -> UsageScope {
+        UsageScope {
+            email: Some(email.to_string()),
+            language: Some(language.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_most_specific_override_wins() {
+        let tenants = BTreeMap::from([(
+            "acme".to_string(),
+            Tenant {
+                domains: vec!["acme.fr".to_string()],
+                emails: vec!["ops@partner.com".to_string()],
+            },
+        )]);
+        let overrides = vec![
+            over(None, Some("fr"), "v2_fr"),
+            over(Some("acme"), None, "v2_strict"),
+            over(Some("acme"), Some("fr"), "v2_strict_fr"),
+            over(Some("example.com"), None, "v2_example"),
+        ];
+        let version = |email: &str, language: &str| {
+            resolve(&overrides, &tenants, &scope(email, language)).map(|o| o.version.as_str())
+        };
+
+        assert_eq!(version("marie@acme.fr", "fr"), Some("v2_strict_fr"));
+        assert_eq!(version("Ops@Partner.com", "de"), Some("v2_strict"));
+        assert_eq!(version("jean@other.fr", "fr"), Some("v2_fr"));
+        assert_eq!(version("bob@example.com", "en"), Some("v2_example"));
+        assert_eq!(version("bob@other.com", "en"), None);
+        assert_eq!(
+            resolve(&overrides, &tenants, &UsageScope::default()).map(|o| o.version.as_str()),
+            None
+        );
+
+        // A tenant from verified credentials counts even when the email domain differs
+        let verified = UsageScope {
+            tenant: Some("acme".to_string()),
+            ..scope("bob@other.com", "en")
+        };
+        assert_eq!(
+            resolve(&overrides, &tenants, &verified).map(|o| o.version.as_str()),
+            Some("v2_strict")
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let tenants = BTreeMap::from([("acme".to_string(), Tenant::default())]);
+        let has_version = |version: &str| version == "v2";
+        assert!(validate(
+            &[over(Some("acme"), Some("fr"), "v2")],
+            &tenants,
+            has_version
+        )
+        .is_empty());
+
+        let problems = validate(
+            &[
+                over(None, None, "v2"),
+                over(Some("acme"), None, "v9"),
+                over(Some("unknown"), None, "v2"),
+                over(None, Some("de"), "v2"),
+                over(None, Some("de"), "v2"),
+            ],
+            &tenants,
+            has_version,
+        );
+        assert_eq!(problems.len(), 4, "{problems:?}");
+    }
+}
@@ -0,0 +1,452 @@
+// src/prompts/template.rs - Prompt templates parsed once and rendered with checked variables
+//
+// Syntax:
+//   {name}                         a variable
+//   {#each list} ... {/each}       repeat for every record of a list; inside, names
+//                                  resolve against the record first
+//   {#if name} ... {else} ... {/if} also {#if !name}; text is set when non-empty,
+//                                  lists when they have records
+// A block tag alone on its line takes the line with it, so loops can be written one
+// tag per line in YAML. Any other `{` (JSON examples in prompts) is literal text.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Bool(bool),
+    List(Vec<Vars>),
+}
+
+pub type Vars = BTreeMap<String, Value>;
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(flag: bool) -> Self {
+        Value::Bool(flag)
+    }
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Text(text) => !text.is_empty(),
+            Value::Bool(flag) => *flag,
+            Value::List(records) => !records.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateError(pub String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Var(String),
+    Each {
+        list: String,
+        body: Vec<Node>,
+    },
+    If {
+        name: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+enum Tag<'a> {
+    Var(&'a str),
+    Each(&'a str),
+    If(&'a str, bool),
+    Else,
+    EndEach,
+    EndIf,
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+    /// Top-level variables a render must supply, exactly
+    variables: BTreeSet<String>,
+}
+
+impl Template {
+    /// Parse `source`, declaring the variables it references
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let nodes = parse_nodes(source)?;
+        let mut variables = BTreeSet::new();
+        collect_variables(&nodes, false, &mut variables);
+        Ok(Self { nodes, variables })
+    }
+
+    /// Parse `source` and require it to reference exactly the `declared` variables
+    pub fn with_variables(source: &str, declared: &[String]) -> Result<Self, TemplateError> {
+        let template = Self::parse(source)?;
+        let declared: BTreeSet<String> = declared.iter().cloned().collect();
+        if let Some(unknown) = template.variables.difference(&declared).next() {
+            return Err(TemplateError(format!(
+                "uses undeclared variable '{unknown}'"
+            )));
+        }
+        if let Some(unused) = declared.difference(&template.variables).next() {
+            return Err(TemplateError(format!(
+                "declares '{unused}' but never uses it"
+            )));
+        }
+        Ok(template)
+    }
+
+    pub fn variables(&self) -> &BTreeSet<String> {
+        &self.variables
+    }
+
+    /// Render with exactly the declared variables, failing on missing or unknown ones
+    pub fn render(&self, vars: &Vars) -> Result<String, TemplateError> {
+        if let Some(missing) = self.variables.iter().find(|v| !vars.contains_key(*v)) {
+            return Err(TemplateError(format!("missing variable '{missing}'")));
+        }
+        if let Some(unknown) = vars.keys().find(|v| !self.variables.contains(*v)) {
+            return Err(TemplateError(format!(
+                "unknown variable '{unknown}', the template uses: {}",
+                self.variables
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        let mut out = String::new();
+        render_nodes(&self.nodes, &[vars], &mut out)?;
+        Ok(out)
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The tag starting at `{`, with its length, or `None` for literal text
+fn parse_tag(rest: &str) -> Option<(Tag<'_>, usize)> {
+    let end = rest.find('}')?;
+    let inner = &rest[1..end];
+    let tag = match inner {
+        "else" => Tag::Else,
+        "/each" => Tag::EndEach,
+        "/if" => Tag::EndIf,
+        _ => {
+            if let Some(list) = inner.strip_prefix("#each ") {
+                Tag::Each(list.trim())
+            } else if let Some(condition) = inner.strip_prefix("#if ") {
+                let condition = condition.trim();
+                match condition.strip_prefix('!') {
+                    Some(name) => Tag::If(name.trim(), true),
+                    None => Tag::If(condition, false),
+                }
+            } else if is_name(inner) {
+                Tag::Var(inner)
+            } else {
+                return None;
+            }
+        }
+    };
+    Some((tag, end + 1))
+}
+
+/// A block whose closing tag has not been seen yet
+enum Block {
+    Each(String),
+    If {
+        name: String,
+        negate: bool,
+        /// Set once `{else}` is seen
+        then: Option<Vec<Node>>,
+    },
+}
+
+fn parse_nodes(source: &str) -> Result<Vec<Node>, TemplateError> {
+    // Open blocks, innermost last, with the nodes of the enclosing level
+    let mut stack: Vec<(Block, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some((tag, len)) = parse_tag(rest) else {
+            text.push('{');
+            rest = &rest[1..];
+            continue;
+        };
+        let offset = source.len() - rest.len();
+        let line_start = offset == 0 || source[..offset].ends_with('\n');
+        rest = &rest[len..];
+        if line_start && !matches!(tag, Tag::Var(_)) && rest.starts_with('\n') {
+            rest = &rest[1..];
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(std::mem::take(&mut text)));
+        }
+
+        match tag {
+            Tag::Var(name) => nodes.push(Node::Var(name.to_string())),
+            Tag::Each(name) | Tag::If(name, _) if !is_name(name) => {
+                return Err(TemplateError(format!("invalid block name '{name}'")));
+            }
+            Tag::Each(list) => {
+                stack.push((Block::Each(list.to_string()), std::mem::take(&mut nodes)));
+            }
+            Tag::If(name, negate) => {
+                let block = Block::If {
+                    name: name.to_string(),
+                    negate,
+                    then: None,
+                };
+                stack.push((block, std::mem::take(&mut nodes)));
+            }
+            Tag::Else => match stack.last_mut() {
+                Some((
+                    Block::If {
+                        then: then @ None, ..
+                    },
+                    _,
+                )) => {
+                    *then = Some(std::mem::take(&mut nodes));
+                }
+                _ => return Err(TemplateError("{else} outside of {#if}".to_string())),
+            },
+            Tag::EndEach => match stack.pop() {
+                Some((Block::Each(list), outer)) => {
+                    let body = std::mem::replace(&mut nodes, outer);
+                    nodes.push(Node::Each { list, body });
+                }
+                _ => return Err(TemplateError("{/each} without {#each}".to_string())),
+            },
+            Tag::EndIf => match stack.pop() {
+                Some((Block::If { name, negate, then }, outer)) => {
+                    let inner = std::mem::replace(&mut nodes, outer);
+                    let (then, otherwise) = match then {
+                        Some(then) => (then, inner),
+                        None => (inner, Vec::new()),
+                    };
+                    nodes.push(Node::If {
+                        name,
+                        negate,
+                        then,
+                        otherwise,
+                    });
+                }
+                _ => return Err(TemplateError("{/if} without {#if}".to_string())),
+            },
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+
+    match stack.last() {
+        Some((Block::Each(list), _)) => {
+            Err(TemplateError(format!("{{#each {list}}} is never closed")))
+        }
+        Some((Block::If { name, .. }, _)) => {
+            Err(TemplateError(format!("{{#if {name}}} is never closed")))
+        }
+        None => Ok(nodes),
+    }
+}
+
+/// Names used outside loops; inside a loop they are fields of the record
+fn collect_variables(nodes: &[Node], in_loop: bool, variables: &mut BTreeSet<String>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Var(name) => {
+                if !in_loop {
+                    variables.insert(name.clone());
+                }
+            }
+            Node::Each { list, body } => {
+                if !in_loop {
+                    variables.insert(list.clone());
+                }
+                collect_variables(body, true, variables);
+            }
+            Node::If {
+                name,
+                then,
+                otherwise,
+                ..
+            } => {
+                if !in_loop {
+                    variables.insert(name.clone());
+                }
+                collect_variables(then, in_loop, variables);
+                collect_variables(otherwise, in_loop, variables);
+            }
+        }
+    }
+}
+
+fn lookup<'a>(name: &str, scopes: &[&'a Vars]) -> Result<&'a Value, TemplateError> {
+    scopes
+        .iter()
+        .rev()
+        .find_map(|scope| scope.get(name))
+        .ok_or_else(|| TemplateError(format!("missing variable '{name}'")))
+}
+
+fn render_nodes(nodes: &[Node], scopes: &[&Vars], out: &mut String) -> Result<(), TemplateError> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(name) => match lookup(name, scopes)? {
+                Value::Text(text) => out.push_str(text),
+                Value::Bool(flag) => out.push_str(if *flag { "true" } else { "false" }),
+                Value::List(_) => {
+                    return Err(TemplateError(format!(
+                        "'{name}' is a list, use {{#each {name}}}"
+                    )))
+                }
+            },
+            Node::Each { list, body } => {
+                let Value::List(records) = lookup(list, scopes)? else {
+                    return Err(TemplateError(format!("'{list}' is not a list")));
+                };
+                for record in records {
+                    let mut inner = scopes.to_vec();
+                    inner.push(record);
+                    render_nodes(body, &inner, out)?;
+                }
+            }
+            Node::If {
+                name,
+                negate,
+                then,
+                otherwise,
+            } => {
+                let value = lookup(name, scopes)?;
+                if value.is_truthy() != *negate {
+                    render_nodes(then, scopes, out)?;
+                } else {
+                    render_nodes(otherwise, scopes, out)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[(&str, Value)]) -> Vars {
+        fields
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_loops_and_conditionals() {
+        let template = Template::parse(
+            "Input: {sentence}\n{#each parameters}- {name}{#if required} (REQUIRED){else} (optional){/if}\n{/each}{#if !parameters}none{/if}{\"json\": {}}",
+        )
+        .unwrap();
+        assert_eq!(
+            template.variables().iter().collect::<Vec<_>>(),
+            ["parameters", "sentence"]
+        );
+
+        let vars = Vars::from([
+            ("sentence".to_string(), Value::from("send mail")),
+            (
+                "parameters".to_string(),
+                Value::List(vec![
+                    record(&[("name", "to".into()), ("required", true.into())]),
+                    record(&[("name", "cc".into()), ("required", false.into())]),
+                ]),
+            ),
+        ]);
+        assert_eq!(
+            template.render(&vars).unwrap(),
+            "Input: send mail\n- to (REQUIRED)\n- cc (optional)\n{\"json\": {}}"
+        );
+    }
+
+    #[test]
+    fn test_block_tags_on_their_own_line_take_the_line() {
+        let template =
+            Template::parse("Endpoints:\n{#each endpoints}\n- {id}\n{/each}\nDone").unwrap();
+        let vars = Vars::from([(
+            "endpoints".to_string(),
+            Value::List(vec![
+                record(&[("id", "send_email".into())]),
+                record(&[("id", "create_ticket".into())]),
+            ]),
+        )]);
+        assert_eq!(
+            template.render(&vars).unwrap(),
+            "Endpoints:\n- send_email\n- create_ticket\nDone"
+        );
+    }
+
+    #[test]
+    fn test_render_rejects_missing_and_unknown_variables() {
+        let template = Template::parse("{input_sentence}: {actions_list}").unwrap();
+        let vars = Vars::from([
+            ("input_sentence".to_string(), Value::from("hi")),
+            ("endpoints_list".to_string(), Value::from("- a")),
+        ]);
+        assert_eq!(
+            template.render(&vars).unwrap_err().to_string(),
+            "missing variable 'actions_list'"
+        );
+
+        let vars = Vars::from([
+            ("input_sentence".to_string(), Value::from("hi")),
+            ("actions_list".to_string(), Value::from("- a")),
+            ("endpoints_list".to_string(), Value::from("- a")),
+        ]);
+        assert!(template.render(&vars).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors_and_declarations() {
+        assert!(Template::parse("{#each endpoints}- {id}").is_err());
+        assert!(Template::parse("{/if}").is_err());
+        assert!(Template::parse("{else}").is_err());
+
+        let declared = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert!(Template::with_variables("{a} {b}", &declared(&["a", "b"])).is_ok());
+        assert!(Template::with_variables("{a} {b}", &declared(&["a"])).is_err());
+        assert!(Template::with_variables("{a}", &declared(&["a", "b"])).is_err());
+    }
+}
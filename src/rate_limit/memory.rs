@@ -0,0 +1,63 @@
+// src/rate_limit/memory.rs - Process-local counters
+use super::CounterStore;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::error::Error;
+use tokio::sync::Mutex;
+
+/// Prune expired buckets once the map grows past this many entries
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// (subject, bucket) -> (value, expiry)
+type Counters = HashMap<(String, String), (u64, DateTime<Utc>)>;
+
+pub struct MemoryCounterStore {
+    counters: Mutex<Counters>,
+}
+
+impl MemoryCounterStore {
+    pub fn new() -> Self {
+        Self {
+            counters: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for MemoryCounterStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CounterStore for MemoryCounterStore {
+    async fn increment(
+        &self,
+        subject: &str,
+        bucket: &str,
+        amount: u64,
+        expires_at: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let mut counters = self.counters.lock().await;
+
+        if counters.len() > PRUNE_THRESHOLD {
+            let now = Utc::now();
+            counters.retain(|_, (_, expiry)| *expiry > now);
+        }
+
+        let entry = counters
+            .entry((subject.to_string(), bucket.to_string()))
+            .or_insert((0, expires_at));
+        entry.0 += amount;
+        Ok(entry.0)
+    }
+
+    async fn get(&self, subject: &str, bucket: &str) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let counters = self.counters.lock().await;
+        Ok(counters
+            .get(&(subject.to_string(), bucket.to_string()))
+            .map(|(value, _)| *value)
+            .unwrap_or(0))
+    }
+}
@@ -0,0 +1,448 @@
+// src/rate_limit/mod.rs - Per-email and per-client request rates and token quotas
+pub mod memory;
+pub mod postgres;
+
+use crate::app_log;
+use crate::health::{PendingStore, StoreSlot};
+use crate::models::config::{CounterBackend, LimitRule, RateLimitConfig};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use tonic::metadata::MetadataValue;
+use tonic::Status;
+
+/// Windowed counters keyed by subject (e.g. `email:a@b.com`) and bucket (e.g. `day:2025-01-31`)
+#[async_trait]
+pub trait CounterStore: Send + Sync {
+    /// Add `amount` to the counter and return its new value
+    async fn increment(
+        &self,
+        subject: &str,
+        bucket: &str,
+        amount: u64,
+        expires_at: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>>;
+
+    async fn get(&self, subject: &str, bucket: &str) -> Result<u64, Box<dyn Error + Send + Sync>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitKind {
+    RequestRate,
+    DailyTokens,
+    MonthlyTokens,
+}
+
+impl fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitKind::RequestRate => write!(f, "request rate"),
+            LimitKind::DailyTokens => write!(f, "daily token quota"),
+            LimitKind::MonthlyTokens => write!(f, "monthly token quota"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{kind} exceeded for {subject}, retry after {retry_after_secs}s")]
+pub struct LimitExceeded {
+    pub subject: String,
+    pub kind: LimitKind,
+    pub retry_after_secs: u64,
+}
+
+impl From<LimitExceeded> for Status {
+    fn from(exceeded: LimitExceeded) -> Self {
+        let mut status = Status::resource_exhausted(exceeded.to_string());
+        status.metadata_mut().insert(
+            "retry-after",
+            MetadataValue::from(exceeded.retry_after_secs),
+        );
+        status
+    }
+}
+
+pub struct RateLimiter {
+    /// Empty when disabled
+    store: Arc<StoreSlot<dyn CounterStore>>,
+    per_email: Option<LimitRule>,
+    per_client: Option<LimitRule>,
+}
+
+impl RateLimiter {
+    pub fn disabled() -> Self {
+        Self {
+            store: Arc::new(StoreSlot::empty()),
+            per_email: None,
+            per_client: None,
+        }
+    }
+
+    pub fn new(
+        store: Arc<dyn CounterStore>,
+        per_email: Option<LimitRule>,
+        per_client: Option<LimitRule>,
+    ) -> Self {
+        Self {
+            store: Arc::new(StoreSlot::new(store)),
+            per_email,
+            per_client,
+        }
+    }
+
+    /// With the `postgres` backend, limits are enforced with process-local counters
+    /// until PostgreSQL is reachable; the store is then connected from `pending`
+    pub async fn from_config(config: &RateLimitConfig, pending: &mut Vec<PendingStore>) -> Self {
+        if !config.enabled {
+            app_log!(info, "Rate limiting and token quotas are disabled");
+            return Self::disabled();
+        }
+
+        let limiter = Self::new(
+            Arc::new(memory::MemoryCounterStore::new()),
+            config.per_email.clone(),
+            config.per_client.clone(),
+        );
+        if config.backend == CounterBackend::Postgres {
+            PendingStore::connect_or_retry(
+                "rate limit counters",
+                &limiter.store,
+                pending,
+                || async {
+                    let database_url = crate::progressive_matching::get_postgres_url()?;
+                    let store = Arc::new(postgres::PostgresCounterStore::new(&database_url).await?);
+                    store.spawn_sweeper(postgres::SWEEP_INTERVAL);
+                    Ok::<Arc<dyn CounterStore>, Box<dyn Error + Send + Sync>>(store)
+                },
+            )
+            .await;
+        }
+
+        app_log!(
+            info,
+            "Rate limiting enabled with {:?} counters: per_email={:?}, per_client={:?}",
+            config.backend,
+            config.per_email,
+            config.per_client
+        );
+        limiter
+    }
+
+    /// Enforce token quotas and count this request against the per-minute rate.
+    /// Must be called before any provider call is made for the request.
+    pub async fn check(
+        &self,
+        email: Option<&str>,
+        client_id: Option<&str>,
+    ) -> Result<(), LimitExceeded> {
+        self.check_at(email, client_id, Utc::now()).await
+    }
+
+    /// Add the tokens consumed by a completed request to the daily and monthly quotas
+    pub async fn record_tokens(&self, email: Option<&str>, client_id: Option<&str>, tokens: u64) {
+        self.record_tokens_at(email, client_id, tokens, Utc::now())
+            .await
+    }
+
+    fn subjects<'a>(
+        &'a self,
+        email: Option<&str>,
+        client_id: Option<&str>,
+    ) -> Vec<(String, &'a LimitRule)> {
+        let mut subjects = Vec::new();
+        if let (Some(email), Some(rule)) = (email, &self.per_email) {
+            subjects.push((format!("email:{}", email.to_lowercase()), rule));
+        }
+        if let (Some(client_id), Some(rule)) = (client_id, &self.per_client) {
+            subjects.push((format!("client:{client_id}"), rule));
+        }
+        subjects
+    }
+
+    async fn check_at(
+        &self,
+        email: Option<&str>,
+        client_id: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<(), LimitExceeded> {
+        let Some(store) = self.store.get() else {
+            return Ok(());
+        };
+        let subjects = self.subjects(email, client_id);
+
+        // Every limit of every subject is checked before any counter moves, so a
+        // request refused for its client id does not count against its email
+        for (subject, rule) in &subjects {
+            let limits = [
+                (LimitKind::DailyTokens, rule.daily_tokens, day_window(now)),
+                (
+                    LimitKind::MonthlyTokens,
+                    rule.monthly_tokens,
+                    month_window(now),
+                ),
+                (
+                    LimitKind::RequestRate,
+                    rule.requests_per_minute,
+                    minute_window(now),
+                ),
+            ];
+            for (kind, limit, (bucket, reset_at)) in limits {
+                let Some(limit) = limit else { continue };
+                match store.get(subject, &bucket).await {
+                    Ok(used) if used >= limit => {
+                        app_log!(
+                            warn,
+                            "{} exceeded for {}: {}/{}",
+                            kind,
+                            subject,
+                            used,
+                            limit
+                        );
+                        return Err(LimitExceeded {
+                            subject: subject.clone(),
+                            kind,
+                            retry_after_secs: seconds_until(now, reset_at),
+                        });
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        app_log!(warn, "Limit lookup failed for {}, allowing: {}", subject, e)
+                    }
+                }
+            }
+        }
+
+        for (subject, rule) in subjects {
+            let Some(limit) = rule.requests_per_minute else {
+                continue;
+            };
+            let (bucket, reset_at) = minute_window(now);
+            match store.increment(&subject, &bucket, 1, reset_at).await {
+                // A concurrent request took the last slot since the check above
+                Ok(count) if count > limit => {
+                    app_log!(
+                        warn,
+                        "Request rate exceeded for {}: {}/{} per minute",
+                        subject,
+                        count,
+                        limit
+                    );
+                    return Err(LimitExceeded {
+                        subject,
+                        kind: LimitKind::RequestRate,
+                        retry_after_secs: seconds_until(now, reset_at),
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    app_log!(warn, "Rate counter failed for {}, allowing: {}", subject, e)
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn record_tokens_at(
+        &self,
+        email: Option<&str>,
+        client_id: Option<&str>,
+        tokens: u64,
+        now: DateTime<Utc>,
+    ) {
+        let Some(store) = self.store.get() else {
+            return;
+        };
+        if tokens == 0 {
+            return;
+        }
+
+        for (subject, rule) in self.subjects(email, client_id) {
+            let windows = [
+                (rule.daily_tokens, day_window(now)),
+                (rule.monthly_tokens, month_window(now)),
+            ];
+            for (limit, (bucket, reset_at)) in windows {
+                if limit.is_none() {
+                    continue;
+                }
+                if let Err(e) = store.increment(&subject, &bucket, tokens, reset_at).await {
+                    app_log!(
+                        warn,
+                        "Failed to record {} tokens for {}: {}",
+                        tokens,
+                        subject,
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn minute_window(now: DateTime<Utc>) -> (String, DateTime<Utc>) {
+    let start = now.with_second(0).unwrap().with_nanosecond(0).unwrap();
+    (
+        format!("rpm:{}", start.format("%Y-%m-%dT%H:%M")),
+        start + Duration::minutes(1),
+    )
+}
+
+fn day_window(now: DateTime<Utc>) -> (String, DateTime<Utc>) {
+    let today = now.date_naive();
+    let reset_at =
+        Utc.from_utc_datetime(&(today + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap());
+    (format!("day:{}", today.format("%Y-%m-%d")), reset_at)
+}
+
+fn month_window(now: DateTime<Utc>) -> (String, DateTime<Utc>) {
+    let (year, month) = if now.month() == 12 {
+        (now.year() + 1, 1)
+    } else {
+        (now.year(), now.month() + 1)
+    };
+    let reset_at = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
+    (format!("month:{}", now.format("%Y-%m")), reset_at)
+}
+
+fn seconds_until(now: DateTime<Utc>, reset_at: DateTime<Utc>) -> u64 {
+    (reset_at - now).num_seconds().max(1) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(rule: LimitRule) -> RateLimiter {
+        RateLimiter::new(
+            Arc::new(memory::MemoryCounterStore::new()),
+            Some(rule.clone()),
+            Some(rule),
+        )
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[tokio::test]
+    async fn test_requests_per_minute() {
+        let limiter = limiter(LimitRule {
+            requests_per_minute: Some(2),
+            ..Default::default()
+        });
+        let now = at("2025-03-10T12:30:45Z");
+
+        assert!(limiter
+            .check_at(Some("a@example.com"), None, now)
+            .await
+            .is_ok());
+        assert!(limiter
+            .check_at(Some("a@example.com"), None, now)
+            .await
+            .is_ok());
+        let exceeded = limiter
+            .check_at(Some("a@example.com"), None, now)
+            .await
+            .unwrap_err();
+        assert_eq!(exceeded.kind, LimitKind::RequestRate);
+        assert_eq!(exceeded.retry_after_secs, 15);
+
+        // Other subjects and the next minute are unaffected
+        assert!(limiter
+            .check_at(Some("b@example.com"), None, now)
+            .await
+            .is_ok());
+        let next_minute = at("2025-03-10T12:31:00Z");
+        assert!(limiter
+            .check_at(Some("a@example.com"), None, next_minute)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_id_limited_independently() {
+        let limiter = limiter(LimitRule {
+            requests_per_minute: Some(1),
+            ..Default::default()
+        });
+        let now = at("2025-03-10T12:30:00Z");
+
+        assert!(limiter
+            .check_at(Some("a@example.com"), Some("web"), now)
+            .await
+            .is_ok());
+        let exceeded = limiter
+            .check_at(Some("b@example.com"), Some("web"), now)
+            .await
+            .unwrap_err();
+        assert_eq!(exceeded.subject, "client:web");
+
+        // The refused request did not count against b@example.com
+        assert!(limiter
+            .check_at(Some("b@example.com"), Some("cli"), now)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_daily_and_monthly_token_quotas() {
+        let limiter = limiter(LimitRule {
+            daily_tokens: Some(1000),
+            monthly_tokens: Some(1500),
+            ..Default::default()
+        });
+        let day1 = at("2025-03-10T23:00:00Z");
+
+        limiter
+            .record_tokens_at(Some("a@example.com"), None, 1000, day1)
+            .await;
+        let exceeded = limiter
+            .check_at(Some("a@example.com"), None, day1)
+            .await
+            .unwrap_err();
+        assert_eq!(exceeded.kind, LimitKind::DailyTokens);
+        assert_eq!(exceeded.retry_after_secs, 3600);
+
+        // A new day resets the daily quota but the month keeps counting
+        let day2 = at("2025-03-11T08:00:00Z");
+        assert!(limiter
+            .check_at(Some("a@example.com"), None, day2)
+            .await
+            .is_ok());
+        limiter
+            .record_tokens_at(Some("a@example.com"), None, 600, day2)
+            .await;
+        let exceeded = limiter
+            .check_at(Some("a@example.com"), None, day2)
+            .await
+            .unwrap_err();
+        assert_eq!(exceeded.kind, LimitKind::MonthlyTokens);
+        assert_eq!(
+            exceeded.retry_after_secs,
+            seconds_until(day2, at("2025-04-01T00:00:00Z"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_carries_retry_after() {
+        let status: Status = LimitExceeded {
+            subject: "email:a@example.com".to_string(),
+            kind: LimitKind::RequestRate,
+            retry_after_secs: 42,
+        }
+        .into();
+
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "42");
+    }
+
+    #[test]
+    fn test_month_window_rolls_over_year() {
+        let (bucket, reset_at) = month_window(at("2025-12-31T10:00:00Z"));
+        assert_eq!(bucket, "month:2025-12");
+        assert_eq!(reset_at, at("2026-01-01T00:00:00Z"));
+    }
+}
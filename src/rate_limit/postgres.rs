@@ -0,0 +1,114 @@
+// src/rate_limit/postgres.rs - Counters shared by all instances through PostgreSQL
+use super::CounterStore;
+use crate::app_log;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{Manager, Pool};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_postgres::Config as PgConfig;
+use tokio_postgres::NoTls;
+
+/// How often expired counters are deleted
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+
+pub struct PostgresCounterStore {
+    pool: Pool,
+}
+
+impl PostgresCounterStore {
+    pub async fn new(database_url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let pg_config: PgConfig = database_url.parse()?;
+        let mgr = Manager::new(pg_config, NoTls);
+        let pool = Pool::builder(mgr)
+            .max_size(10)
+            .runtime(deadpool_postgres::Runtime::Tokio1)
+            .build()?;
+
+        let mut client = pool.get().await?;
+        crate::migrations::prepare(&mut client).await?;
+
+        let store = Self { pool };
+        let removed = store.remove_expired().await?;
+        app_log!(
+            info,
+            "Rate limit counters ready, removed {} expired rows",
+            removed
+        );
+
+        Ok(store)
+    }
+
+    /// Delete counters whose window has passed, returning how many were removed
+    pub async fn remove_expired(&self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let removed = client
+            .execute(
+                "DELETE FROM rate_limit_counters WHERE expires_at < NOW()",
+                &[],
+            )
+            .await?;
+        Ok(removed)
+    }
+
+    /// Remove expired counters every `interval`, so finished windows do not pile up
+    pub fn spawn_sweeper(self: &Arc<Self>, interval: Duration) {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately, and `new` already swept
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match store.remove_expired().await {
+                    Ok(removed) => {
+                        app_log!(debug, "Removed {} expired rate limit counters", removed)
+                    }
+                    Err(e) => app_log!(warn, "Failed to remove expired rate limit counters: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl CounterStore for PostgresCounterStore {
+    async fn increment(
+        &self,
+        subject: &str,
+        bucket: &str,
+        amount: u64,
+        expires_at: DateTime<Utc>,
+    ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_one(
+                r#"
+                INSERT INTO rate_limit_counters (subject, bucket, value, expires_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (subject, bucket)
+                DO UPDATE SET value = rate_limit_counters.value + EXCLUDED.value
+                RETURNING value
+                "#,
+                &[&subject, &bucket, &(amount as i64), &expires_at],
+            )
+            .await?;
+
+        let value: i64 = row.get(0);
+        Ok(value.max(0) as u64)
+    }
+
+    async fn get(&self, subject: &str, bucket: &str) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let value: Option<i64> = client
+            .query_opt(
+                "SELECT value FROM rate_limit_counters WHERE subject = $1 AND bucket = $2",
+                &[&subject, &bucket],
+            )
+            .await?
+            .map(|row| row.get(0));
+
+        Ok(value.unwrap_or(0).max(0) as u64)
+    }
+}
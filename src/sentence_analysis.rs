@@ -5,22 +5,43 @@ use crate::models::EnhancedAnalysisResult;
 use crate::progressive_matching::{integrate_progressive_matching, ParameterValue, ProgressiveMatchingManager};
 use crate::workflow::classify_intent::IntentType;
 use crate::analysis::analyze_sentence_enhanced::analyze_sentence_enhanced;
+use crate::analysis::missing_fields;
+use crate::analysis::confirmation::{
+    handle_confirmation_reply, needs_confirmation, require_confirmation,
+};
+use crate::models::config::load_confirmation_config;
+use crate::parameter_memory::ParameterMemory;
+use crate::health::StoreSlot;
 
 use std::sync::Arc;
 use graflog::app_span;
 use tonic::Status;
 use crate::app_log;
+use crate::error::SemanticError;
+use crate::metrics;
+use crate::trace;
+use crate::usage;
 use crate::sentence_service::sentence::{
-    IntentType as ProtoIntentType, MatchingInfo, MatchingStatus, MissingField, Parameter,
-    SentenceResponse, Usage,
+    Confirmation, ConfirmationStatus, IntentType as ProtoIntentType, MatchingInfo, MatchingStatus,
+    MissingField, Parameter, ParameterChange, RiskLevel as ProtoRiskLevel, SentenceResponse, Usage,
 };
 
+/// What a streamed analysis consumed and how it ended
+pub struct StreamedAnalysis {
+    /// Every LLM call of the request, including those of failed attempts
+    pub tokens_used: u32,
+    /// `matched`, `answered` (general or help question) or `failed`
+    pub outcome: &'static str,
+}
+
 #[derive(Clone)]
 pub struct SentenceAnalyzer {
     pub provider: Arc<dyn ModelProvider>,
     pub api_url: Option<String>,
     pub conversation_manager: Arc<ConversationManager>,
-    progressive_manager: Option<Arc<ProgressiveMatchingManager>>,
+    /// Empty without a database, or until it is reachable
+    progressive_manager: Arc<StoreSlot<ProgressiveMatchingManager>>,
+    parameter_memory: Arc<ParameterMemory>,
 }
 
 impl SentenceAnalyzer {
@@ -28,16 +49,31 @@ impl SentenceAnalyzer {
         provider: Arc<dyn ModelProvider>,
         api_url: Option<String>,
         conversation_manager: Arc<ConversationManager>,
-        progressive_manager: Option<Arc<ProgressiveMatchingManager>>,
+        progressive_manager: Arc<StoreSlot<ProgressiveMatchingManager>>,
     ) -> Self {
         Self {
             provider,
             api_url,
             conversation_manager,
             progressive_manager,
+            parameter_memory: Arc::new(ParameterMemory::disabled()),
         }
     }
 
+    pub fn with_parameter_memory(mut self, parameter_memory: Arc<ParameterMemory>) -> Self {
+        self.parameter_memory = parameter_memory;
+        self
+    }
+
+    pub fn progressive_manager(&self) -> Option<Arc<ProgressiveMatchingManager>> {
+        self.progressive_manager.get()
+    }
+
+    pub fn parameter_memory(&self) -> &Arc<ParameterMemory> {
+        &self.parameter_memory
+    }
+
+    /// Analyze a sentence and stream the response
     pub async fn analyze_sentence_stream(
         &self,
         input_sentence: String,
@@ -45,7 +81,7 @@ impl SentenceAnalyzer {
         email: String,
         client_id: String,
         tx: tokio::sync::mpsc::Sender<Result<SentenceResponse, Status>>,
-    ) {
+    ) -> StreamedAnalysis {
         let analyze_span = app_span!(
             "analyze_sentence",
             client_id = %client_id,
@@ -59,20 +95,37 @@ impl SentenceAnalyzer {
         let provider_clone = self.provider.clone();
         let api_url_clone = self.api_url.clone();
         let conversation_manager_clone = self.conversation_manager.clone();
-        let progressive_manager_clone = self.progressive_manager.clone();
+        let progressive_manager_clone = self.progressive_manager();
 
-        // Only if no progressive match found, do normal analysis
-        let result = analyze_sentence_enhanced(
-                &input_sentence,
-                provider_clone,
-                api_url_clone,
-                &email,
-                Some(conversation_id.clone()),
-            )
-            .await;
+        // A reply to a confirmation request is answered first
+        let result = match self
+            .confirmation_reply(&input_sentence, &conversation_id, &email)
+            .await
+        {
+            Some(result) => result,
+            None => {
+                let defaults = self.parameter_memory.defaults(&email).await;
+                analyze_sentence_enhanced(
+                    &input_sentence,
+                    provider_clone,
+                    api_url_clone,
+                    &email,
+                    Some(conversation_id.clone()),
+                    &defaults,
+                    progressive_manager_clone.as_deref(),
+                )
+                .await
+            }
+        };
 
         match result {
-            Ok(enhanced_result) => {
+            Ok(mut enhanced_result) => {
+                self.polish_user_prompt(&mut enhanced_result).await;
+                metrics::record_request(Some(&enhanced_result.intent));
+                let outcome = match enhanced_result.intent {
+                    IntentType::ActionableRequest => "matched",
+                    _ => "answered",
+                };
                 self.handle_successful_analysis(
                     enhanced_result,
                     input_sentence,
@@ -85,8 +138,13 @@ impl SentenceAnalyzer {
                     progressive_manager_clone,
                 )
                 .await;
+                StreamedAnalysis {
+                    tokens_used: usage::request_usage().total_tokens,
+                    outcome,
+                }
             }
             Err(e) => {
+                metrics::record_request(None);
                 self.handle_analysis_error(
                     e,
                     input_sentence,
@@ -96,10 +154,75 @@ impl SentenceAnalyzer {
                     tx,
                 )
                 .await;
+                StreamedAnalysis {
+                    tokens_used: usage::request_usage().total_tokens,
+                    outcome: "failed",
+                }
             }
         }
     }
 
+    /// Answer a conversation waiting for a confirmation, see `analysis/confirmation.rs`.
+    /// `None` when it waits for none or the message is not a reply.
+    async fn confirmation_reply(
+        &self,
+        input_sentence: &str,
+        conversation_id: &str,
+        email: &str,
+    ) -> Option<Result<EnhancedAnalysisResult, SemanticError>> {
+        let config = load_confirmation_config().await.unwrap_or_default();
+        let ttl = chrono::Duration::seconds(config.ttl_secs as i64);
+        let pending = self
+            .conversation_manager
+            .take_confirmation(conversation_id, email, ttl)
+            .await?;
+        let api_url = self.api_url.clone().unwrap_or_default();
+
+        match trace::step(
+            "confirmation_reply",
+            handle_confirmation_reply(
+                input_sentence,
+                &pending.result,
+                self.provider.clone(),
+                &api_url,
+                email,
+            ),
+        )
+        .await
+        {
+            Ok(Some(result)) => Some(Ok(result)),
+            Ok(None) => None,
+            Err(e) => {
+                // Still waiting for an answer
+                self.conversation_manager
+                    .request_confirmation(conversation_id, email, pending.result)
+                    .await;
+                Some(Err(SemanticError::from(e)))
+            }
+        }
+    }
+
+    /// Let the LLM polish the question for missing parameters, when configured
+    async fn polish_user_prompt(&self, result: &mut EnhancedAnalysisResult) {
+        if result.intent != IntentType::ActionableRequest
+            || result.matching_info.missing_required_fields.is_empty()
+        {
+            return;
+        }
+        let Some(question) = result.user_prompt.take() else {
+            return;
+        };
+        let (question, usage) = missing_fields::polish(
+            question,
+            &missing_fields::request_language(),
+            self.provider.clone(),
+        )
+        .await;
+        result.user_prompt = Some(question);
+        result.add_usage(&usage);
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn handle_successful_analysis(
         &self,
         enhanced_result: crate::models::EnhancedAnalysisResult,
@@ -125,6 +248,7 @@ impl SentenceAnalyzer {
         if let Some(ref manager) = progressive_manager {
             self.save_incomplete_request_if_needed(
                 &enhanced_result,
+                &input_sentence,
                 &conversation_id,
                 &email,
                 manager,
@@ -132,6 +256,20 @@ impl SentenceAnalyzer {
             .await;
         }
 
+        // Endpoints that change or delete data wait for the user's confirmation
+        let mut enhanced_result = enhanced_result;
+        let confirmation_config = load_confirmation_config().await.unwrap_or_default();
+        if needs_confirmation(&enhanced_result, &confirmation_config) {
+            require_confirmation(&mut enhanced_result);
+            conversation_manager
+                .request_confirmation(&conversation_id, &email, enhanced_result.clone())
+                .await;
+            metrics::record_confirmation("requested");
+        }
+        self.parameter_memory
+            .learn_from(&email, &enhanced_result)
+            .await;
+
         // Add message to conversation history
         self.save_to_conversation_history(
             &enhanced_result,
@@ -142,8 +280,14 @@ impl SentenceAnalyzer {
         .await;
 
         // Build and send response
-        let response =
+        let mut response =
             self.build_sentence_response(enhanced_result, conversation_id.clone(), model);
+        if let Some(recorder) = trace::current() {
+            response.request_id = Some(recorder.request_id().to_string());
+            if recorder.debug() {
+                response.trace = Some(recorder.snapshot().into());
+            }
+        }
 
         if tx.send(Ok(response)).await.is_err() {
             app_log!(error, 
@@ -157,7 +301,7 @@ impl SentenceAnalyzer {
 
     async fn handle_analysis_error(
         &self,
-        error: Box<dyn std::error::Error + Send + Sync>,
+        error: SemanticError,
         input_sentence: String,
         conversation_id: String,
         email: String,
@@ -173,18 +317,9 @@ impl SentenceAnalyzer {
             "Analysis failed"
         );
 
-        let status = if error.to_string().contains("No endpoints found for user") {
-            Status::not_found(format!(
-                "No endpoints configured for your account ({email}). Please contact your administrator."
-            ))
-        } else if error
-            .to_string()
-            .contains("No endpoint configuration available")
-        {
-            Status::failed_precondition("Endpoint configuration is not available.")
-        } else {
-            Status::internal(format!("Analysis failed: {error}"))
-        };
+        // The request id lets the caller fetch the failed request's trace with GetTrace
+        let request_id = trace::current().map(|recorder| recorder.request_id().to_string());
+        let status = error.to_status(request_id.as_deref());
 
         if tx.send(Err(status)).await.is_err() {
             app_log!(error, "Failed to send error response - stream closed");
@@ -194,6 +329,7 @@ impl SentenceAnalyzer {
     async fn save_incomplete_request_if_needed(
         &self,
         enhanced_result: &crate::models::EnhancedAnalysisResult,
+        input_sentence: &str,
         conversation_id: &str,
         email: &str, // Add email parameter
         manager: &Arc<ProgressiveMatchingManager>,
@@ -235,6 +371,8 @@ impl SentenceAnalyzer {
                         match integrate_progressive_matching(
                             conversation_id,
                             &enhanced_result.endpoint_id,
+                            email,
+                            input_sentence,
                             new_parameters,
                             required_param_names,
                             manager,
@@ -243,6 +381,7 @@ impl SentenceAnalyzer {
                         .await
                         {
                             Ok(progressive_result) => {
+                                metrics::record_progressive_match("started");
                                 app_log!(info, 
                                 "Saved incomplete request to progressive matching: {}% complete",
                                 progressive_result.completion_percentage
@@ -332,6 +471,36 @@ impl SentenceAnalyzer {
                 matched_endpoint_id: Some(endpoint_id), // Use the clone
                 user_sentence: None,
             }),
+            request_id: None,
+            trace: None,
+            changed_parameters: enhanced_result
+                .changed_parameters
+                .into_iter()
+                .map(|change| ParameterChange {
+                    name: change.name,
+                    previous_value: change.previous,
+                    value: change.value,
+                })
+                .collect(),
+            risk_level: match enhanced_result.risk_level {
+                crate::models::RiskLevel::Low => ProtoRiskLevel::Low as i32,
+                crate::models::RiskLevel::Medium => ProtoRiskLevel::Medium as i32,
+                crate::models::RiskLevel::High => ProtoRiskLevel::High as i32,
+            },
+            confirmation: enhanced_result.confirmation.map(|confirmation| Confirmation {
+                status: match confirmation.status {
+                    crate::models::ConfirmationStatus::Required => {
+                        ConfirmationStatus::ConfirmationRequired as i32
+                    }
+                    crate::models::ConfirmationStatus::Confirmed => {
+                        ConfirmationStatus::Confirmed as i32
+                    }
+                    crate::models::ConfirmationStatus::Declined => {
+                        ConfirmationStatus::Declined as i32
+                    }
+                },
+                summary: confirmation.summary,
+            }),
             intent: match enhanced_result.intent {
                 IntentType::ActionableRequest => ProtoIntentType::ActionableRequest as i32,
                 IntentType::GeneralQuestion => ProtoIntentType::GeneralQuestion as i32,
@@ -344,6 +513,7 @@ impl SentenceAnalyzer {
                     name: param.name,
                     description: param.description,
                     semantic_value: param.value,
+                    default_source: param.default_source.map(|source| source.to_string()),
                 })
                 .collect(),
             json_output: match serde_json::to_string(&enhanced_result.raw_json) {
@@ -371,6 +541,7 @@ impl SentenceAnalyzer {
                     .map(|field| MissingField {
                         name: field.name,
                         description: field.description,
+                        examples: field.examples,
                     })
                     .collect(),
                 missing_optional_fields: enhanced_result
@@ -380,6 +551,7 @@ impl SentenceAnalyzer {
                     .map(|field| MissingField {
                         name: field.name,
                         description: field.description,
+                        examples: field.examples,
                     })
                     .collect(),
             }),
@@ -1,8 +1,20 @@
 // src/sentence_service.rs
+use crate::auth::AuthenticatedIdentity;
 use crate::conversation::ConversationManager;
+use crate::health::{PendingStore, StoreSlot};
+use crate::metrics;
+use crate::models::config::{load_language_detection_config, load_progressive_matching_config};
 use crate::models::providers::ModelProvider;
-use crate::progressive_matching::ProgressiveMatchingManager;
+use crate::parameter_memory::ParameterMemory;
+use crate::progressive_matching::{ParameterRevision, ParameterValue, ProgressiveMatchingManager};
+use crate::prompts::experiments;
+use crate::rate_limit::RateLimiter;
 use crate::sentence_analysis::SentenceAnalyzer;
+use crate::trace::{self, TraceStore};
+use crate::usage::ledger::UsageLedger;
+use crate::usage::{self, UsageScope};
+use crate::utils::language;
+use chrono::{Duration, Utc};
 use futures::Stream;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -15,40 +27,104 @@ pub mod sentence {
 }
 
 use crate::app_log;
+use crate::error::SemanticError;
 use sentence::sentence_service_server::SentenceService;
-use sentence::{MessageRequest, MessageResponse, SentenceRequest, SentenceResponse};
+use sentence::{
+    ContextChange, ExecutionTrace, LlmCallTrace, MessageRequest, MessageResponse, Parameter,
+    ParameterRevision as ProtoParameterRevision, PendingRequest, PendingRequestAction,
+    PendingRequests, PendingRequestsRequest, RememberedValue, RememberedValues,
+    RememberedValuesRequest, SentenceRequest, SentenceResponse, SetRememberedValuesRequest,
+    StepTrace, TraceRequest, UsageBucket, UsageReport, UsageRequest,
+};
 use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
 pub struct SentenceAnalyzeService {
     analyzer: SentenceAnalyzer,
+    rate_limiter: Arc<RateLimiter>,
+    usage_ledger: Option<Arc<StoreSlot<UsageLedger>>>,
+    traces: Arc<TraceStore>,
 }
 
 impl SentenceAnalyzeService {
     pub fn new(provider: Arc<dyn ModelProvider>, api_url: Option<String>) -> Self {
-        let analyzer = SentenceAnalyzer::new(
-            provider,
-            api_url,
-            Arc::new(ConversationManager::new()),
-            None,
-        );
-        Self { analyzer }
+        Self::with_progressive_slot(provider, api_url, Arc::new(StoreSlot::empty()))
     }
 
+    /// Progressive matching on `database_url`. When the database is unreachable the
+    /// service starts without it and connects from `pending` later.
     pub async fn with_progressive_matching(
         provider: Arc<dyn ModelProvider>,
         api_url: Option<String>,
         database_url: &str,
+        pending: &mut Vec<PendingStore>,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let progressive_manager = Arc::new(ProgressiveMatchingManager::new(database_url).await?);
+        let config = load_progressive_matching_config().await?;
+        let slot = Arc::new(StoreSlot::empty());
+        let database_url = database_url.to_string();
+        PendingStore::connect_or_retry("progressive matching store", &slot, pending, move || {
+            let database_url = database_url.clone();
+            let config = config.clone();
+            async move {
+                let manager =
+                    Arc::new(ProgressiveMatchingManager::new(&database_url, &config).await?);
+                manager.spawn_sweeper(std::time::Duration::from_secs(
+                    config.sweep_interval_secs.max(1),
+                ));
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(manager)
+            }
+        })
+        .await;
+        Ok(Self::with_progressive_slot(provider, api_url, slot))
+    }
+
+    fn with_progressive_slot(
+        provider: Arc<dyn ModelProvider>,
+        api_url: Option<String>,
+        progressive_manager: Arc<StoreSlot<ProgressiveMatchingManager>>,
+    ) -> Self {
         let analyzer = SentenceAnalyzer::new(
             provider,
             api_url,
             Arc::new(ConversationManager::new()),
-            Some(progressive_manager),
+            progressive_manager,
         );
-        Ok(Self { analyzer })
+        Self {
+            analyzer,
+            rate_limiter: Arc::new(RateLimiter::disabled()),
+            usage_ledger: None,
+            traces: Arc::new(TraceStore::new(Default::default())),
+        }
+    }
+
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    pub fn with_usage_ledger(mut self, usage_ledger: Option<Arc<StoreSlot<UsageLedger>>>) -> Self {
+        self.usage_ledger = usage_ledger;
+        self
+    }
+
+    pub fn with_parameter_memory(mut self, parameter_memory: Arc<ParameterMemory>) -> Self {
+        self.analyzer = self.analyzer.with_parameter_memory(parameter_memory);
+        self
+    }
+
+    pub fn with_trace_store(mut self, traces: Arc<TraceStore>) -> Self {
+        self.traces = traces;
+        self
     }
 
-    fn get_email_validated(&self, metadata: &MetadataMap) -> Result<String, tonic::Status> {
+    fn get_email_validated(
+        &self,
+        identity: Option<&AuthenticatedIdentity>,
+        metadata: &MetadataMap,
+    ) -> Result<String, tonic::Status> {
+        // Verified credentials take precedence over the legacy email header
+        if let Some(identity) = identity {
+            return Ok(identity.email.clone());
+        }
+
         let email = metadata
             .get("email")
             .and_then(|v| v.to_str().ok())
@@ -68,11 +144,98 @@ impl SentenceAnalyzeService {
     }
 
     fn get_client_id(metadata: &MetadataMap) -> String {
+        Self::get_explicit_client_id(metadata).unwrap_or_else(|| "unknown-client".to_string())
+    }
+
+    /// The `client-id` header when the caller actually sent one
+    fn get_explicit_client_id(metadata: &MetadataMap) -> Option<String> {
         metadata
             .get("client-id")
             .and_then(|v| v.to_str().ok())
-            .unwrap_or("unknown-client")
-            .to_string()
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| v.to_string())
+    }
+
+    fn progressive_manager(&self) -> Result<Arc<ProgressiveMatchingManager>, Status> {
+        self.analyzer
+            .progressive_manager()
+            .ok_or_else(|| Status::failed_precondition("Progressive matching is not available"))
+    }
+
+    fn parameter_memory(&self) -> Result<&Arc<ParameterMemory>, Status> {
+        let memory = self.analyzer.parameter_memory();
+        if memory.is_enabled() {
+            Ok(memory)
+        } else {
+            Err(Status::failed_precondition(
+                "Parameter memory is not enabled",
+            ))
+        }
+    }
+
+    async fn remembered_values(&self, email: &str) -> Result<RememberedValues, Status> {
+        let values = self.parameter_memory()?.values(email).await.map_err(|e| {
+            app_log!(error, "Failed to read remembered values: {}", e);
+            Status::internal("Failed to read remembered values")
+        })?;
+        Ok(RememberedValues {
+            values: values
+                .into_iter()
+                .map(|remembered| RememberedValue {
+                    name: remembered.name,
+                    value: remembered.value,
+                    source: remembered.source.to_string(),
+                    uses: remembered.uses,
+                    updated_at: remembered.updated_at.to_rfc3339(),
+                })
+                .collect(),
+        })
+    }
+
+    /// The caller's pending requests; those of other users are never listed
+    async fn pending_requests(
+        &self,
+        conversation_id: &str,
+        email: &str,
+    ) -> Result<PendingRequests, Status> {
+        let matches = self
+            .progressive_manager()?
+            .pending_matches(conversation_id, email)
+            .await
+            .map_err(|e| {
+                app_log!(error, "Failed to read pending requests: {}", e);
+                Status::internal("Failed to read pending requests")
+            })?;
+
+        let requests = matches
+            .into_iter()
+            .map(|ongoing| PendingRequest {
+                parameters: serde_json::from_str::<Vec<ParameterValue>>(&ongoing.parameters)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|param| Parameter {
+                        name: param.name,
+                        description: param.description,
+                        semantic_value: Some(param.value),
+                        default_source: None,
+                    })
+                    .collect(),
+                history: serde_json::from_str::<Vec<ParameterRevision>>(&ongoing.history)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|revision| ProtoParameterRevision {
+                        name: revision.name,
+                        value: revision.value,
+                        source: revision.source,
+                        at: revision.at.to_rfc3339(),
+                    })
+                    .collect(),
+                endpoint_id: ongoing.endpoint_id,
+                created_at: ongoing.created_at.to_rfc3339(),
+                updated_at: ongoing.updated_at.to_rfc3339(),
+            })
+            .collect();
+        Ok(PendingRequests { requests })
     }
 
     async fn ensure_conversation_id(
@@ -113,12 +276,11 @@ impl SentenceService for SentenceAnalyzeService {
         request: Request<SentenceRequest>,
     ) -> Result<Response<Self::AnalyzeSentenceStream>, Status> {
         let metadata = request.metadata().clone();
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
         let sentence_request = request.into_inner();
 
-        app_log!(info, "Request metadata: {:?}", metadata);
-
         let client_id = Self::get_client_id(&metadata);
-        let email = match self.get_email_validated(&metadata) {
+        let email = match self.get_email_validated(identity.as_ref(), &metadata) {
             Ok(email) => email,
             Err(status) => {
                 app_log!(error, "Email validation failed: {}", status);
@@ -126,7 +288,19 @@ impl SentenceService for SentenceAnalyzeService {
             }
         };
 
+        let explicit_client_id = Self::get_explicit_client_id(&metadata);
+        if let Err(exceeded) = self
+            .rate_limiter
+            .check(Some(&email), explicit_client_id.as_deref())
+            .await
+        {
+            return Err(exceeded.into());
+        }
+
         let input_sentence = sentence_request.sentence;
+        let recorder = self
+            .traces
+            .recorder(&email, &input_sentence, sentence_request.debug);
 
         let conversation_id = match self
             .ensure_conversation_id(sentence_request.conversation_id.clone(), &email)
@@ -149,9 +323,56 @@ impl SentenceService for SentenceAnalyzeService {
         let (tx, rx) = mpsc::channel(10);
 
         let analyzer = self.analyzer.clone();
+        let rate_limiter = self.rate_limiter.clone();
+        let traces = self.traces.clone();
+        let usage_ledger = self.usage_ledger.clone();
+        let detected_language = language::detect_confident(
+            &input_sentence,
+            load_language_detection_config()
+                .await
+                .unwrap_or_default()
+                .min_confidence,
+        )
+        .map(str::to_string);
+        let scope = UsageScope {
+            email: Some(email.clone()),
+            conversation_id: Some(conversation_id.clone()),
+            rpc: "AnalyzeSentence".to_string(),
+            language: self
+                .analyzer
+                .conversation_manager
+                .language(&conversation_id, detected_language)
+                .await,
+            tenant: identity.as_ref().map(|identity| identity.tenant.clone()),
+        };
         tokio::spawn(async move {
-            analyzer
-                .analyze_sentence_stream(input_sentence, conversation_id, email, client_id, tx)
+            let analysis = usage::scoped(scope, async {
+                let analysis = analyzer
+                    .analyze_sentence_stream(
+                        input_sentence,
+                        conversation_id,
+                        email.clone(),
+                        client_id,
+                        tx,
+                    )
+                    .await;
+                let ledger = usage_ledger.as_ref().and_then(|slot| slot.get());
+                experiments::record_outcome(ledger.as_deref(), analysis.outcome).await;
+                analysis.tokens_used
+            });
+            let tokens_used = match &recorder {
+                Some(recorder) => trace::recorded(recorder.clone(), analysis).await,
+                None => analysis.await,
+            };
+            if let Some(recorder) = recorder {
+                traces.insert(recorder.snapshot());
+            }
+            rate_limiter
+                .record_tokens(
+                    Some(&email),
+                    explicit_client_id.as_deref(),
+                    tokens_used as u64,
+                )
                 .await;
         });
 
@@ -162,6 +383,12 @@ impl SentenceService for SentenceAnalyzeService {
         &self,
         request: Request<MessageRequest>,
     ) -> Result<Response<MessageResponse>, Status> {
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
+        // Messages do not require an email, but count against it when one is known
+        let email = self
+            .get_email_validated(identity.as_ref(), request.metadata())
+            .ok();
+        let client_id = Self::get_explicit_client_id(request.metadata());
         let message_request = request.into_inner();
         let message = message_request.message;
 
@@ -169,6 +396,10 @@ impl SentenceService for SentenceAnalyzeService {
             return Err(Status::invalid_argument("Message cannot be empty"));
         }
 
+        self.rate_limiter
+            .check(email.as_deref(), client_id.as_deref())
+            .await?;
+
         let conversation_id = message_request
             .conversation_id
             .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
@@ -189,14 +420,32 @@ impl SentenceService for SentenceAnalyzeService {
 
         let model_config = &models_config.default;
 
-        match self
-            .analyzer
-            .provider
-            .generate(&message, model_config)
-            .await
-        {
+        let scope = UsageScope {
+            email: email.clone(),
+            conversation_id: Some(conversation_id.clone()),
+            rpc: "SendMessage".to_string(),
+            language: None,
+            tenant: identity.as_ref().map(|identity| identity.tenant.clone()),
+        };
+        let generation = usage::scoped(
+            scope,
+            usage::in_step(
+                "send_message",
+                self.analyzer.provider.generate(&message, model_config),
+            ),
+        )
+        .await;
+
+        match generation {
             Ok(result) => {
                 app_log!(info, "Successfully generated response");
+                self.rate_limiter
+                    .record_tokens(
+                        email.as_deref(),
+                        client_id.as_deref(),
+                        result.usage.total_tokens as u64,
+                    )
+                    .await;
                 Ok(Response::new(MessageResponse {
                     response: result.content,
                     success: true,
@@ -205,8 +454,244 @@ impl SentenceService for SentenceAnalyzeService {
             }
             Err(e) => {
                 app_log!(error, "Failed to generate response: {}", e);
-                Err(Status::internal("Failed to generate response"))
+                Err(SemanticError::from(e).to_status(None))
             }
         }
     }
+
+    async fn get_usage(
+        &self,
+        request: Request<UsageRequest>,
+    ) -> Result<Response<UsageReport>, Status> {
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
+        let email = self.get_email_validated(identity.as_ref(), request.metadata())?;
+        let usage_request = request.into_inner();
+
+        let ledger = self
+            .usage_ledger
+            .as_ref()
+            .ok_or_else(|| Status::failed_precondition("Usage metering is not enabled"))?
+            .get()
+            .ok_or_else(|| Status::unavailable("Usage ledger database is unavailable"))?;
+
+        let parse = |value: Option<String>| {
+            value
+                .filter(|v| !v.trim().is_empty())
+                .map(|v| usage::parse_bound(v.trim()))
+                .transpose()
+                .map_err(|e| Status::invalid_argument(e.to_string()))
+        };
+        let until = parse(usage_request.until)?.unwrap_or_else(Utc::now);
+        let since = parse(usage_request.since)?.unwrap_or_else(|| until - Duration::days(30));
+        if since >= until {
+            return Err(Status::invalid_argument("'since' must be before 'until'"));
+        }
+
+        let summaries = ledger.summarize(&email, since, until).await.map_err(|e| {
+            app_log!(error, "Failed to read usage ledger: {}", e);
+            Status::internal("Failed to read usage")
+        })?;
+
+        let report = UsageReport {
+            email,
+            since: since.to_rfc3339(),
+            until: until.to_rfc3339(),
+            total_tokens: summaries
+                .iter()
+                .map(|s| s.input_tokens + s.output_tokens)
+                .sum(),
+            total_cost: summaries.iter().map(|s| s.cost).sum(),
+            csv: usage::summaries_to_csv(&summaries),
+            buckets: summaries
+                .into_iter()
+                .map(|s| UsageBucket {
+                    day: s.day.to_string(),
+                    model: s.model,
+                    rpc: s.rpc,
+                    calls: s.calls,
+                    input_tokens: s.input_tokens,
+                    output_tokens: s.output_tokens,
+                    estimated_calls: s.estimated_calls,
+                    cost: s.cost,
+                })
+                .collect(),
+        };
+
+        Ok(Response::new(report))
+    }
+
+    async fn get_trace(
+        &self,
+        request: Request<TraceRequest>,
+    ) -> Result<Response<ExecutionTrace>, Status> {
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
+        let email = self.get_email_validated(identity.as_ref(), request.metadata())?;
+        let request_id = request.into_inner().request_id;
+
+        // Traces of other callers are reported as missing, not as forbidden
+        self.traces
+            .get(request_id.trim(), &email)
+            .map(|trace| Response::new(trace.into()))
+            .ok_or_else(|| Status::not_found(format!("No trace found for request {request_id}")))
+    }
+
+    async fn list_pending_requests(
+        &self,
+        request: Request<PendingRequestsRequest>,
+    ) -> Result<Response<PendingRequests>, Status> {
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
+        let email = self.get_email_validated(identity.as_ref(), request.metadata())?;
+        let conversation_id = request.into_inner().conversation_id;
+        self.pending_requests(&conversation_id, &email)
+            .await
+            .map(Response::new)
+    }
+
+    async fn resume_pending_request(
+        &self,
+        request: Request<PendingRequestAction>,
+    ) -> Result<Response<PendingRequests>, Status> {
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
+        let email = self.get_email_validated(identity.as_ref(), request.metadata())?;
+        let action = request.into_inner();
+        let resumed = self
+            .progressive_manager()?
+            .resume_match(&action.conversation_id, &action.endpoint_id, &email)
+            .await
+            .map_err(|e| {
+                app_log!(error, "Failed to resume pending request: {}", e);
+                Status::internal("Failed to resume pending request")
+            })?;
+        if !resumed {
+            return Err(Status::not_found(format!(
+                "No pending request for endpoint {}",
+                action.endpoint_id
+            )));
+        }
+        metrics::record_progressive_match("resumed");
+        self.pending_requests(&action.conversation_id, &email)
+            .await
+            .map(Response::new)
+    }
+
+    async fn abandon_pending_request(
+        &self,
+        request: Request<PendingRequestAction>,
+    ) -> Result<Response<PendingRequests>, Status> {
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
+        let email = self.get_email_validated(identity.as_ref(), request.metadata())?;
+        let action = request.into_inner();
+        let abandoned = self
+            .progressive_manager()?
+            .abandon_match(&action.conversation_id, &action.endpoint_id, &email)
+            .await
+            .map_err(|e| {
+                app_log!(error, "Failed to abandon pending request: {}", e);
+                Status::internal("Failed to abandon pending request")
+            })?;
+        if !abandoned {
+            return Err(Status::not_found(format!(
+                "No pending request for endpoint {}",
+                action.endpoint_id
+            )));
+        }
+        metrics::record_progressive_match("abandoned");
+        self.pending_requests(&action.conversation_id, &email)
+            .await
+            .map(Response::new)
+    }
+
+    async fn get_remembered_values(
+        &self,
+        request: Request<RememberedValuesRequest>,
+    ) -> Result<Response<RememberedValues>, Status> {
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
+        let email = self.get_email_validated(identity.as_ref(), request.metadata())?;
+        self.remembered_values(&email).await.map(Response::new)
+    }
+
+    async fn set_remembered_values(
+        &self,
+        request: Request<SetRememberedValuesRequest>,
+    ) -> Result<Response<RememberedValues>, Status> {
+        let identity = request.extensions().get::<AuthenticatedIdentity>().cloned();
+        let email = self.get_email_validated(identity.as_ref(), request.metadata())?;
+        let update = request.into_inner();
+        let memory = self.parameter_memory()?;
+        if update
+            .profile
+            .iter()
+            .any(|(name, value)| name.trim().is_empty() || value.trim().is_empty())
+        {
+            return Err(Status::invalid_argument(
+                "Profile values need a name and a value",
+            ));
+        }
+
+        for name in &update.forget {
+            memory.forget(&email, name).await.map_err(|e| {
+                app_log!(error, "Failed to forget {}: {}", name, e);
+                Status::internal("Failed to update remembered values")
+            })?;
+        }
+        for (name, value) in &update.profile {
+            memory.set_profile(&email, name, value).await.map_err(|e| {
+                app_log!(error, "Failed to set {}: {}", name, e);
+                Status::internal("Failed to update remembered values")
+            })?;
+        }
+        self.remembered_values(&email).await.map(Response::new)
+    }
+}
+
+impl From<trace::ExecutionTrace> for ExecutionTrace {
+    fn from(trace: trace::ExecutionTrace) -> Self {
+        Self {
+            request_id: trace.request_id,
+            sentence: trace.sentence,
+            started_at: trace.started_at.to_rfc3339(),
+            duration_ms: trace.duration_ms,
+            steps: trace
+                .steps
+                .into_iter()
+                .map(|step| StepTrace {
+                    name: step.name,
+                    status: step.status.as_str().to_string(),
+                    started_ms: step.started_ms,
+                    duration_ms: step.duration_ms,
+                    attempts: step.attempts,
+                    detail: step.detail,
+                    input_tokens: step.input_tokens,
+                    output_tokens: step.output_tokens,
+                    context_diff: step
+                        .context_diff
+                        .into_iter()
+                        .map(|change| ContextChange {
+                            field: change.field,
+                            before: change.before,
+                            after: change.after,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            llm_calls: trace
+                .llm_calls
+                .into_iter()
+                .map(|call| LlmCallTrace {
+                    step: call.step,
+                    model: call.model,
+                    prompt_version: call.prompt_version,
+                    experiment: call.experiment,
+                    prompt_override: call.prompt_override,
+                    prompt: call.prompt,
+                    raw_response: call.raw_response,
+                    error: call.error,
+                    input_tokens: call.input_tokens,
+                    output_tokens: call.output_tokens,
+                    estimated: call.estimated,
+                    latency_ms: call.latency_ms,
+                })
+                .collect(),
+        }
+    }
 }
@@ -0,0 +1,498 @@
+// src/trace.rs - Structured per-request execution traces for debugging wrong matches
+use crate::models::config::TraceConfig;
+use crate::models::providers::{GenerationResult, ModelConfig, ModelProvider};
+use crate::usage;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+tokio::task_local! {
+    static RECORDER: Arc<TraceRecorder>;
+}
+
+/// Everything that happened while answering one request
+#[derive(Debug, Clone)]
+pub struct ExecutionTrace {
+    pub request_id: String,
+    pub email: String,
+    pub sentence: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub steps: Vec<StepTrace>,
+    pub llm_calls: Vec<LlmCallTrace>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepStatus {
+    Completed,
+    Skipped,
+    Failed,
+    /// The step failed and its `on_error` fallback ran instead
+    Fallback,
+}
+
+impl StepStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepStatus::Completed => "completed",
+            StepStatus::Skipped => "skipped",
+            StepStatus::Failed => "failed",
+            StepStatus::Fallback => "fallback",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StepTrace {
+    pub name: String,
+    pub status: StepStatus,
+    /// Offset from the start of the request
+    pub started_ms: u64,
+    pub duration_ms: u64,
+    pub attempts: u32,
+    /// Error message, or why the step was skipped
+    pub detail: Option<String>,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub context_diff: Vec<ContextChange>,
+}
+
+/// A `WorkflowContext` field changed by a step, values as JSON
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextChange {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmCallTrace {
+    pub step: String,
+    pub model: String,
+    /// `<prompt>@<version>` of the last template rendered in this step
+    pub prompt_version: Option<String>,
+    /// Prompt experiment that picked that version, if any
+    pub experiment: Option<String>,
+    /// Tenant or language override that picked that version, e.g. `tenant=acme language=fr`
+    pub prompt_override: Option<String>,
+    pub prompt: String,
+    pub raw_response: Option<String>,
+    pub error: Option<String>,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub estimated: bool,
+    pub latency_ms: u64,
+}
+
+/// Collects the trace of the request it is scoped to, see `recorded`
+pub struct TraceRecorder {
+    request_id: String,
+    email: String,
+    sentence: String,
+    /// The caller asked for the trace in the response
+    debug: bool,
+    max_text_chars: usize,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    data: Mutex<TraceData>,
+}
+
+#[derive(Default)]
+struct TraceData {
+    steps: Vec<StepTrace>,
+    llm_calls: Vec<LlmCallTrace>,
+    /// Prompt rendered by each step and not yet sent, with the override that picked it
+    pending_prompts: HashMap<&'static str, (String, Option<String>)>,
+}
+
+impl TraceRecorder {
+    pub fn new(email: &str, sentence: &str, debug: bool, config: &TraceConfig) -> Self {
+        Self {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            email: email.to_string(),
+            sentence: sentence.to_string(),
+            debug,
+            max_text_chars: config.max_text_chars,
+            started_at: Utc::now(),
+            started: Instant::now(),
+            data: Mutex::new(TraceData::default()),
+        }
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    pub fn debug(&self) -> bool {
+        self.debug
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    pub fn snapshot(&self) -> ExecutionTrace {
+        let data = self.data.lock().unwrap();
+        ExecutionTrace {
+            request_id: self.request_id.clone(),
+            email: self.email.clone(),
+            sentence: self.sentence.clone(),
+            started_at: self.started_at,
+            duration_ms: self.elapsed_ms(),
+            steps: data.steps.clone(),
+            llm_calls: data.llm_calls.clone(),
+        }
+    }
+
+    fn record_llm_call(
+        &self,
+        prompt: &str,
+        model: String,
+        result: &Result<GenerationResult, Box<dyn Error + Send + Sync>>,
+        latency: Duration,
+    ) {
+        let step = usage::current_step();
+        let mut data = self.data.lock().unwrap();
+        let (prompt_version, prompt_override) = data.pending_prompts.remove(step).unzip();
+        let (raw_response, error, input_tokens, output_tokens, estimated) = match result {
+            Ok(result) => (
+                Some(truncate(&result.content, self.max_text_chars)),
+                None,
+                result.usage.input_tokens,
+                result.usage.output_tokens,
+                result.usage.estimated,
+            ),
+            Err(e) => (None, Some(e.to_string()), 0, 0, false),
+        };
+        data.llm_calls.push(LlmCallTrace {
+            step: step.to_string(),
+            model,
+            prompt_version,
+            experiment: usage::current_variant().map(|(experiment, _)| experiment),
+            prompt_override: prompt_override.flatten(),
+            prompt: truncate(prompt, self.max_text_chars),
+            raw_response,
+            error,
+            input_tokens,
+            output_tokens,
+            estimated,
+            latency_ms: latency.as_millis() as u64,
+        });
+    }
+}
+
+/// Run `future` with its workflow steps and LLM calls recorded by `recorder`
+pub async fn recorded<F: Future>(recorder: Arc<TraceRecorder>, future: F) -> F::Output {
+    RECORDER.scope(recorder, future).await
+}
+
+/// The recorder of the current request, if it is being traced
+pub fn current() -> Option<Arc<TraceRecorder>> {
+    RECORDER.try_with(|recorder| recorder.clone()).ok()
+}
+
+pub fn record_step(step: StepTrace) {
+    let _ = RECORDER.try_with(|recorder| recorder.data.lock().unwrap().steps.push(step));
+}
+
+/// Remember which prompt template and version the current step rendered
+pub fn note_prompt(name: &str, version: &str, prompt_override: Option<&str>) {
+    let _ = RECORDER.try_with(|recorder| {
+        recorder.data.lock().unwrap().pending_prompts.insert(
+            usage::current_step(),
+            (
+                format!("{name}@{version}"),
+                prompt_override.map(str::to_string),
+            ),
+        );
+    });
+}
+
+/// Run a single-attempt step outside the workflow engine, e.g. intent classification,
+/// attributing its usage and recording its outcome
+pub async fn step<F, T, E>(name: &'static str, future: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let Some(recorder) = current() else {
+        return usage::in_step(name, future).await;
+    };
+
+    let started_ms = recorder.elapsed_ms();
+    let started = Instant::now();
+    let first_call = recorder.data.lock().unwrap().llm_calls.len();
+    let result = usage::in_step(name, future).await;
+
+    let (input_tokens, output_tokens) = {
+        let data = recorder.data.lock().unwrap();
+        data.llm_calls[first_call..]
+            .iter()
+            .filter(|call| call.step == name)
+            .fold((0, 0), |(i, o), call| {
+                (i + call.input_tokens, o + call.output_tokens)
+            })
+    };
+    record_step(StepTrace {
+        name: name.to_string(),
+        status: if result.is_ok() {
+            StepStatus::Completed
+        } else {
+            StepStatus::Failed
+        },
+        started_ms,
+        duration_ms: started.elapsed().as_millis() as u64,
+        attempts: 1,
+        detail: result.as_ref().err().map(|e| e.to_string()),
+        input_tokens,
+        output_tokens,
+        context_diff: Vec::new(),
+    });
+    result
+}
+
+/// Fields whose value differs between two context snapshots
+pub fn diff_snapshots(
+    before: &Map<String, Value>,
+    after: &Map<String, Value>,
+) -> Vec<ContextChange> {
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter(|field| before.get(*field) != after.get(*field))
+        .map(|field| ContextChange {
+            field: field.clone(),
+            before: before
+                .get(field)
+                .filter(|v| !v.is_null())
+                .map(Value::to_string),
+            after: after
+                .get(field)
+                .filter(|v| !v.is_null())
+                .map(Value::to_string),
+        })
+        .collect()
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!(
+            "{}... [{} more chars]",
+            &text[..cut],
+            text[cut..].chars().count()
+        ),
+        None => text.to_string(),
+    }
+}
+
+/// Recent traces kept in memory for `GetTrace`
+pub struct TraceStore {
+    config: TraceConfig,
+    traces: Mutex<VecDeque<(Instant, ExecutionTrace)>>,
+}
+
+impl TraceStore {
+    pub fn new(config: TraceConfig) -> Self {
+        Self {
+            config,
+            traces: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// A recorder for the request when it is debugged or every request is traced
+    pub fn recorder(&self, email: &str, sentence: &str, debug: bool) -> Option<Arc<TraceRecorder>> {
+        (debug || self.config.record_all)
+            .then(|| Arc::new(TraceRecorder::new(email, sentence, debug, &self.config)))
+    }
+
+    pub fn insert(&self, trace: ExecutionTrace) {
+        if self.config.capacity == 0 {
+            return;
+        }
+        let mut traces = self.traces.lock().unwrap();
+        self.evict_expired(&mut traces);
+        while traces.len() >= self.config.capacity {
+            traces.pop_front();
+        }
+        traces.push_back((Instant::now(), trace));
+    }
+
+    /// A trace by request id, only to the caller it belongs to
+    pub fn get(&self, request_id: &str, email: &str) -> Option<ExecutionTrace> {
+        let mut traces = self.traces.lock().unwrap();
+        self.evict_expired(&mut traces);
+        traces
+            .iter()
+            .map(|(_, trace)| trace)
+            .find(|trace| trace.request_id == request_id && trace.email == email)
+            .cloned()
+    }
+
+    fn evict_expired(&self, traces: &mut VecDeque<(Instant, ExecutionTrace)>) {
+        let retention = Duration::from_secs(self.config.retention_secs);
+        while traces
+            .front()
+            .is_some_and(|(stored, _)| stored.elapsed() > retention)
+        {
+            traces.pop_front();
+        }
+    }
+}
+
+/// Provider decorator that records prompts and raw responses of traced requests
+pub struct TracingProvider {
+    inner: Arc<dyn ModelProvider>,
+}
+
+impl TracingProvider {
+    pub fn new(inner: Arc<dyn ModelProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for TracingProvider {
+    async fn generate(
+        &self,
+        prompt: &str,
+        config: &ModelConfig,
+    ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>> {
+        let Some(recorder) = current() else {
+            return self.inner.generate(prompt, config).await;
+        };
+
+        let started = Instant::now();
+        let result = self.inner.generate(prompt, config).await;
+        let model = config.model_for(self.inner.get_model_name()).to_string();
+        recorder.record_llm_call(prompt, model, &result, started.elapsed());
+        result
+    }
+
+    fn get_model_name(&self) -> &str {
+        self.inner.get_model_name()
+    }
+
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::providers::token_counter::TokenUsage;
+
+    struct EchoProvider;
+
+    #[async_trait]
+    impl ModelProvider for EchoProvider {
+        async fn generate(
+            &self,
+            prompt: &str,
+            _model: &ModelConfig,
+        ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>> {
+            Ok(GenerationResult {
+                content: format!("echo: {prompt}"),
+                usage: TokenUsage {
+                    input_tokens: 7,
+                    output_tokens: 3,
+                    total_tokens: 10,
+                    estimated: false,
+                },
+            })
+        }
+
+        fn get_model_name(&self) -> &str {
+            "claude"
+        }
+    }
+
+    fn config() -> TraceConfig {
+        TraceConfig {
+            capacity: 2,
+            max_text_chars: 12,
+            ..TraceConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_steps_and_llm_calls_are_recorded() {
+        let provider = TracingProvider::new(Arc::new(EchoProvider));
+        let recorder = Arc::new(TraceRecorder::new("a@example.com", "hi", true, &config()));
+        let model = ModelConfig {
+            claude: "claude-sonnet".to_string(),
+            ..ModelConfig::default()
+        };
+
+        let result = recorded(recorder.clone(), async {
+            step("classify_intent", async {
+                note_prompt("intent_classification", "v3", Some("language=fr"));
+                provider.generate("classify this sentence", &model).await
+            })
+            .await
+        })
+        .await;
+        assert!(result.is_ok());
+
+        let trace = recorder.snapshot();
+        assert_eq!(trace.steps.len(), 1);
+        assert_eq!(trace.steps[0].name, "classify_intent");
+        assert_eq!(trace.steps[0].status, StepStatus::Completed);
+        assert_eq!(trace.steps[0].input_tokens, 7);
+
+        let call = &trace.llm_calls[0];
+        assert_eq!(call.step, "classify_intent");
+        assert_eq!(call.model, "claude-sonnet");
+        assert_eq!(
+            call.prompt_version.as_deref(),
+            Some("intent_classification@v3")
+        );
+        assert_eq!(call.prompt_override.as_deref(), Some("language=fr"));
+        assert_eq!(call.prompt, "classify thi... [10 more chars]");
+
+        // Outside a recorded scope nothing is collected
+        provider.generate("untraced", &model).await.unwrap();
+        assert_eq!(recorder.snapshot().llm_calls.len(), 1);
+    }
+
+    #[test]
+    fn test_store_is_bounded_and_scoped_to_owner() {
+        let store = TraceStore::new(config());
+        assert!(store.recorder("a@example.com", "hi", false).is_none());
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let recorder = store.recorder("a@example.com", "hi", true).unwrap();
+            ids.push(recorder.request_id().to_string());
+            store.insert(recorder.snapshot());
+        }
+
+        assert!(store.get(&ids[0], "a@example.com").is_none());
+        assert!(store.get(&ids[2], "a@example.com").is_some());
+        assert!(store.get(&ids[2], "b@example.com").is_none());
+    }
+
+    #[test]
+    fn test_context_diff() {
+        let before = serde_json::json!({"endpoint_id": null, "parameters": []});
+        let after = serde_json::json!({"endpoint_id": "send_email", "parameters": []});
+        let diff = diff_snapshots(before.as_object().unwrap(), after.as_object().unwrap());
+        assert_eq!(
+            diff,
+            vec![ContextChange {
+                field: "endpoint_id".to_string(),
+                before: None,
+                after: Some("\"send_email\"".to_string()),
+            }]
+        );
+    }
+}
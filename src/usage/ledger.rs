@@ -0,0 +1,254 @@
+// src/usage/ledger.rs - PostgreSQL usage ledger
+use super::{ExperimentSummary, PriceTable, UsageRecord, UsageSummary};
+use crate::app_log;
+use crate::health::{PendingStore, StoreSlot};
+use crate::models::config::UsageConfig;
+use chrono::{DateTime, NaiveDate, Utc};
+use deadpool_postgres::{Manager, Pool};
+use std::error::Error;
+use std::sync::Arc;
+use tokio_postgres::Config as PgConfig;
+use tokio_postgres::NoTls;
+
+pub struct UsageLedger {
+    pool: Pool,
+    prices: PriceTable,
+}
+
+impl UsageLedger {
+    pub async fn new(
+        database_url: &str,
+        prices: PriceTable,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let pg_config: PgConfig = database_url.parse()?;
+        let mgr = Manager::new(pg_config, NoTls);
+        let pool = Pool::builder(mgr)
+            .max_size(10)
+            .runtime(deadpool_postgres::Runtime::Tokio1)
+            .build()?;
+
+        let mut client = pool.get().await?;
+        crate::migrations::prepare(&mut client).await?;
+
+        Ok(Self { pool, prices })
+    }
+
+    /// Open the ledger when metering is enabled, `None` otherwise
+    pub async fn from_config(
+        config: &UsageConfig,
+    ) -> Result<Option<Self>, Box<dyn Error + Send + Sync>> {
+        if !config.enabled {
+            app_log!(info, "Usage metering is disabled");
+            return Ok(None);
+        }
+
+        let database_url = crate::progressive_matching::get_postgres_url()?;
+        let ledger = Self::new(&database_url, PriceTable::new(config.prices.clone())).await?;
+        app_log!(
+            info,
+            "Usage metering enabled with {} priced models",
+            config.prices.len()
+        );
+        Ok(Some(ledger))
+    }
+
+    /// Like `from_config`, for the server: when PostgreSQL is unreachable the slot
+    /// stays empty and is connected later by the database health probe
+    pub async fn from_config_deferred(
+        config: &UsageConfig,
+        pending: &mut Vec<PendingStore>,
+    ) -> Option<Arc<StoreSlot<Self>>> {
+        if !config.enabled {
+            app_log!(info, "Usage metering is disabled");
+            return None;
+        }
+
+        let slot = Arc::new(StoreSlot::empty());
+        let prices = PriceTable::new(config.prices.clone());
+        PendingStore::connect_or_retry("usage ledger", &slot, pending, move || {
+            let prices = prices.clone();
+            async move {
+                let database_url = crate::progressive_matching::get_postgres_url()?;
+                let ledger = Self::new(&database_url, prices).await?;
+                Ok::<_, Box<dyn Error + Send + Sync>>(Arc::new(ledger))
+            }
+        })
+        .await;
+        app_log!(
+            info,
+            "Usage metering enabled with {} priced models",
+            config.prices.len()
+        );
+        Some(slot)
+    }
+
+    pub fn prices(&self) -> &PriceTable {
+        &self.prices
+    }
+
+    pub async fn record(&self, record: &UsageRecord) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                r#"
+                INSERT INTO usage_ledger (
+                    recorded_at, email, conversation_id, rpc, step, provider, model,
+                    input_tokens, output_tokens, estimated, latency_ms, cost, experiment, variant
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                "#,
+                &[
+                    &record.recorded_at,
+                    &record.email,
+                    &record.conversation_id,
+                    &record.rpc,
+                    &record.step,
+                    &record.provider,
+                    &record.model,
+                    &(record.input_tokens as i64),
+                    &(record.output_tokens as i64),
+                    &record.estimated,
+                    &(record.latency_ms as i64),
+                    &record.cost,
+                    &record.experiment,
+                    &record.variant,
+                ],
+            )
+            .await?;
+
+        app_log!(
+            debug,
+            "Recorded usage: rpc={}, step={}, model={}, tokens={}/{}, cost={:.6}",
+            record.rpc,
+            record.step,
+            record.model,
+            record.input_tokens,
+            record.output_tokens,
+            record.cost
+        );
+        Ok(())
+    }
+
+    /// Totals per UTC day, model and rpc for `[since, until)`
+    pub async fn summarize(
+        &self,
+        email: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<UsageSummary>, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                r#"
+                SELECT (recorded_at AT TIME ZONE 'UTC')::date AS day,
+                       model,
+                       rpc,
+                       COUNT(*) AS calls,
+                       SUM(input_tokens)::BIGINT AS input_tokens,
+                       SUM(output_tokens)::BIGINT AS output_tokens,
+                       COUNT(*) FILTER (WHERE estimated) AS estimated_calls,
+                       SUM(cost) AS cost
+                FROM usage_ledger
+                WHERE LOWER(email) = LOWER($1) AND recorded_at >= $2 AND recorded_at < $3
+                GROUP BY day, model, rpc
+                ORDER BY day, model, rpc
+                "#,
+                &[&email, &since, &until],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| UsageSummary {
+                day: row.get::<_, NaiveDate>("day"),
+                model: row.get("model"),
+                rpc: row.get("rpc"),
+                calls: row.get::<_, i64>("calls").max(0) as u64,
+                input_tokens: row.get::<_, i64>("input_tokens").max(0) as u64,
+                output_tokens: row.get::<_, i64>("output_tokens").max(0) as u64,
+                estimated_calls: row.get::<_, i64>("estimated_calls").max(0) as u64,
+                cost: row.get("cost"),
+            })
+            .collect())
+    }
+
+    /// How a request assigned to an experiment variant ended
+    pub async fn record_experiment_outcome(
+        &self,
+        experiment: &str,
+        variant: &str,
+        email: Option<&str>,
+        outcome: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                r#"
+                INSERT INTO prompt_experiment_outcomes (recorded_at, experiment, variant, email, outcome)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                &[&Utc::now(), &experiment, &variant, &email, &outcome],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Requests, match rate and token cost per experiment variant for `[since, until)`
+    pub async fn summarize_experiments(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<ExperimentSummary>, Box<dyn Error + Send + Sync>> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .query(
+                r#"
+                WITH outcomes AS (
+                    SELECT experiment, variant,
+                           COUNT(*) AS requests,
+                           COUNT(*) FILTER (WHERE outcome = 'matched') AS matched
+                    FROM prompt_experiment_outcomes
+                    WHERE recorded_at >= $1 AND recorded_at < $2
+                    GROUP BY experiment, variant
+                ),
+                calls AS (
+                    SELECT experiment, variant,
+                           COUNT(*) AS calls,
+                           SUM(input_tokens)::BIGINT AS input_tokens,
+                           SUM(output_tokens)::BIGINT AS output_tokens,
+                           SUM(cost) AS cost
+                    FROM usage_ledger
+                    WHERE experiment IS NOT NULL AND recorded_at >= $1 AND recorded_at < $2
+                    GROUP BY experiment, variant
+                )
+                SELECT COALESCE(o.experiment, c.experiment) AS experiment,
+                       COALESCE(o.variant, c.variant) AS variant,
+                       COALESCE(o.requests, 0) AS requests,
+                       COALESCE(o.matched, 0) AS matched,
+                       COALESCE(c.calls, 0) AS calls,
+                       COALESCE(c.input_tokens, 0) AS input_tokens,
+                       COALESCE(c.output_tokens, 0) AS output_tokens,
+                       COALESCE(c.cost, 0) AS cost
+                FROM outcomes o
+                FULL OUTER JOIN calls c ON o.experiment = c.experiment AND o.variant = c.variant
+                ORDER BY experiment, variant
+                "#,
+                &[&since, &until],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ExperimentSummary {
+                experiment: row.get("experiment"),
+                variant: row.get("variant"),
+                requests: row.get::<_, i64>("requests").max(0) as u64,
+                matched: row.get::<_, i64>("matched").max(0) as u64,
+                calls: row.get::<_, i64>("calls").max(0) as u64,
+                input_tokens: row.get::<_, i64>("input_tokens").max(0) as u64,
+                output_tokens: row.get::<_, i64>("output_tokens").max(0) as u64,
+                cost: row.get("cost"),
+            })
+            .collect())
+    }
+}
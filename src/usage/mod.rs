@@ -0,0 +1,465 @@
+// src/usage/mod.rs - Per-call usage metering and billing export
+pub mod ledger;
+
+use crate::app_log;
+use crate::health::StoreSlot;
+use crate::models::config::ModelPrice;
+use crate::models::providers::token_counter::TokenUsage;
+use crate::models::providers::{GenerationResult, ModelConfig, ModelProvider};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use ledger::UsageLedger;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Who a call is billed to, set once per request
+#[derive(Debug, Clone, Default)]
+pub struct UsageScope {
+    pub email: Option<String>,
+    pub conversation_id: Option<String>,
+    /// gRPC method the request came in through, e.g. `AnalyzeSentence` or
+    /// `SendMessage`, or `cli`. Not the endpoint the request matched.
+    pub rpc: String,
+    /// Language code of the request's sentence, for prompt overrides
+    pub language: Option<String>,
+    /// Tenant from verified credentials, when the caller authenticated
+    pub tenant: Option<String>,
+}
+
+/// Prompt experiment variants assigned while serving one scoped request
+#[derive(Debug, Default)]
+struct PromptVariants {
+    /// Experiment and version of the prompt each step rendered
+    by_step: HashMap<&'static str, (String, String)>,
+    /// Experiment to version, for every experiment the request took part in
+    assigned: BTreeMap<String, String>,
+}
+
+tokio::task_local! {
+    static SCOPE: UsageScope;
+    static STEP: &'static str;
+    static VARIANTS: Mutex<PromptVariants>;
+    /// Every LLM call of the scoped request, including those of failed steps
+    static SPENT: Mutex<TokenUsage>;
+}
+
+/// Run `future` with every LLM call inside it attributed to `scope`
+pub async fn scoped<F: Future>(scope: UsageScope, future: F) -> F::Output {
+    let future = SPENT.scope(Mutex::default(), future);
+    SCOPE
+        .scope(scope, VARIANTS.scope(Mutex::default(), future))
+        .await
+}
+
+/// Usage of every LLM call made so far in the enclosing `scoped` request, whether
+/// or not its result was used. Calls are counted by `MeteredProvider`.
+pub fn request_usage() -> TokenUsage {
+    SPENT
+        .try_with(|spent| spent.lock().unwrap().clone())
+        .unwrap_or_default()
+}
+
+fn count_call(usage: &TokenUsage) {
+    let _ = SPENT.try_with(|spent| spent.lock().unwrap().add(usage));
+}
+
+/// The scope set by the enclosing `scoped`, if any
+pub fn current_scope() -> Option<UsageScope> {
+    SCOPE.try_with(|scope| scope.clone()).ok()
+}
+
+/// Attribute the current step's LLM calls to a prompt experiment variant
+pub fn note_variant(experiment: &str, version: &str) {
+    let _ = VARIANTS.try_with(|variants| {
+        let mut variants = variants.lock().unwrap();
+        variants.by_step.insert(
+            current_step(),
+            (experiment.to_string(), version.to_string()),
+        );
+        variants
+            .assigned
+            .insert(experiment.to_string(), version.to_string());
+    });
+}
+
+/// Experiment and version of the prompt rendered by the current step
+pub fn current_variant() -> Option<(String, String)> {
+    VARIANTS
+        .try_with(|variants| {
+            variants
+                .lock()
+                .unwrap()
+                .by_step
+                .get(current_step())
+                .cloned()
+        })
+        .ok()
+        .flatten()
+}
+
+/// Every experiment the current request was assigned to, with its version
+pub fn assigned_variants() -> Vec<(String, String)> {
+    VARIANTS
+        .try_with(|variants| {
+            let variants = variants.lock().unwrap();
+            variants
+                .assigned
+                .iter()
+                .map(|(experiment, version)| (experiment.clone(), version.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Run `future` with every LLM call inside it attributed to the named step
+pub async fn in_step<F: Future>(step: &'static str, future: F) -> F::Output {
+    STEP.scope(step, future).await
+}
+
+/// The step set by the innermost `in_step`, or `unscoped`
+pub fn current_step() -> &'static str {
+    STEP.try_with(|step| *step).unwrap_or("unscoped")
+}
+
+/// One LLM call as stored in the ledger
+#[derive(Debug, Clone)]
+pub struct UsageRecord {
+    pub recorded_at: DateTime<Utc>,
+    pub email: Option<String>,
+    pub conversation_id: Option<String>,
+    pub rpc: String,
+    pub step: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub estimated: bool,
+    pub latency_ms: u64,
+    pub cost: f64,
+    /// Prompt experiment and version the call's prompt was assigned
+    pub experiment: Option<String>,
+    pub variant: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PriceTable {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    pub fn new(prices: HashMap<String, ModelPrice>) -> Self {
+        Self { prices }
+    }
+
+    /// Cost in USD, zero for models without a configured price
+    pub fn cost(&self, model: &str, input_tokens: u32, output_tokens: u32) -> f64 {
+        match self.prices.get(model) {
+            Some(price) => {
+                (input_tokens as f64 * price.input_per_million
+                    + output_tokens as f64 * price.output_per_million)
+                    / 1_000_000.0
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Ledger totals for one (day, model, rpc) group
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageSummary {
+    pub day: NaiveDate,
+    pub model: String,
+    pub rpc: String,
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated_calls: u64,
+    pub cost: f64,
+}
+
+/// Ledger totals for one prompt experiment variant
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperimentSummary {
+    pub experiment: String,
+    pub variant: String,
+    /// Requests served with this variant, and how many of them matched an endpoint
+    pub requests: u64,
+    pub matched: u64,
+    /// LLM calls made with this variant's prompt
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost: f64,
+}
+
+pub fn summaries_to_csv(summaries: &[UsageSummary]) -> String {
+    let mut csv = String::from(
+        "day,model,rpc,calls,input_tokens,output_tokens,total_tokens,estimated_calls,cost_usd\n",
+    );
+    for s in summaries {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{:.6}\n",
+            s.day,
+            csv_field(&s.model),
+            csv_field(&s.rpc),
+            s.calls,
+            s.input_tokens,
+            s.output_tokens,
+            s.input_tokens + s.output_tokens,
+            s.estimated_calls,
+            s.cost
+        ));
+    }
+    csv
+}
+
+/// Parse a report bound given as RFC 3339 or as a `YYYY-MM-DD` UTC date
+pub fn parse_bound(value: &str) -> Result<DateTime<Utc>, Box<dyn Error + Send + Sync>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{value}', expected YYYY-MM-DD or RFC 3339"))?;
+    Ok(date.and_time(NaiveTime::MIN).and_utc())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Provider decorator that counts every call toward its request's usage and writes
+/// it to the usage ledger
+pub struct MeteredProvider {
+    inner: Arc<dyn ModelProvider>,
+    /// Calls made before the ledger connects are not recorded
+    ledger: Arc<StoreSlot<UsageLedger>>,
+}
+
+impl MeteredProvider {
+    pub fn new(inner: Arc<dyn ModelProvider>, ledger: Arc<StoreSlot<UsageLedger>>) -> Self {
+        Self { inner, ledger }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for MeteredProvider {
+    async fn generate(
+        &self,
+        prompt: &str,
+        config: &ModelConfig,
+    ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>> {
+        let started = Instant::now();
+        let result = self.inner.generate(prompt, config).await?;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        count_call(&result.usage);
+        let Some(ledger) = self.ledger.get() else {
+            return Ok(result);
+        };
+
+        let scope = SCOPE.try_with(|scope| scope.clone()).unwrap_or_default();
+        let step = current_step();
+        let provider = self.inner.get_model_name().to_string();
+        let model = config.model_for(&provider).to_string();
+        let (experiment, variant) = current_variant().unzip();
+
+        let record = UsageRecord {
+            recorded_at: Utc::now(),
+            email: scope.email,
+            conversation_id: scope.conversation_id,
+            rpc: scope.rpc,
+            step: step.to_string(),
+            cost: ledger.prices().cost(
+                &model,
+                result.usage.input_tokens,
+                result.usage.output_tokens,
+            ),
+            provider,
+            model,
+            input_tokens: result.usage.input_tokens,
+            output_tokens: result.usage.output_tokens,
+            estimated: result.usage.estimated,
+            latency_ms,
+            experiment,
+            variant,
+        };
+
+        // Metering must never slow down or fail the request itself
+        tokio::spawn(async move {
+            if let Err(e) = ledger.record(&record).await {
+                app_log!(warn, "Failed to write usage record: {}", e);
+            }
+        });
+
+        Ok(result)
+    }
+
+    fn get_model_name(&self) -> &str {
+        self.inner.get_model_name()
+    }
+
+    async fn health_check(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedProvider;
+
+    #[async_trait]
+    impl ModelProvider for FixedProvider {
+        async fn generate(
+            &self,
+            _prompt: &str,
+            _model: &ModelConfig,
+        ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>> {
+            Ok(GenerationResult {
+                content: "NO_MATCH".to_string(),
+                usage: TokenUsage {
+                    input_tokens: 7,
+                    output_tokens: 3,
+                    total_tokens: 10,
+                    estimated: false,
+                },
+            })
+        }
+
+        fn get_model_name(&self) -> &str {
+            "claude"
+        }
+    }
+
+    #[test]
+    fn test_cost_from_price_table() {
+        let prices = PriceTable::new(HashMap::from([(
+            "claude-sonnet-4-20250514".to_string(),
+            ModelPrice {
+                input_per_million: 3.0,
+                output_per_million: 15.0,
+            },
+        )]));
+
+        let cost = prices.cost("claude-sonnet-4-20250514", 2_000, 500);
+        assert!((cost - 0.0135).abs() < 1e-9);
+        assert_eq!(prices.cost("unpriced-model", 2_000, 500), 0.0);
+    }
+
+    #[test]
+    fn test_summaries_csv_export() {
+        let csv = summaries_to_csv(&[UsageSummary {
+            day: NaiveDate::from_ymd_opt(2025, 3, 14).unwrap(),
+            model: "command-r7b-12-2024".to_string(),
+            rpc: "AnalyzeSentence".to_string(),
+            calls: 3,
+            input_tokens: 1200,
+            output_tokens: 300,
+            estimated_calls: 1,
+            cost: 0.000123,
+        }]);
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("day,model,rpc"));
+        assert_eq!(
+            lines[1],
+            "2025-03-14,command-r7b-12-2024,AnalyzeSentence,3,1200,300,1500,1,0.000123"
+        );
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn test_parse_bound() {
+        assert_eq!(
+            parse_bound("2025-03-14").unwrap().to_rfc3339(),
+            "2025-03-14T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_bound("2025-03-14T10:00:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2025-03-14T08:00:00+00:00"
+        );
+        assert!(parse_bound("last week").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scope_and_step_are_visible_inside_calls() {
+        let scope = UsageScope {
+            email: Some("alice@example.com".to_string()),
+            conversation_id: None,
+            rpc: "cli".to_string(),
+            language: None,
+            tenant: None,
+        };
+
+        let (email, step) = scoped(scope, async {
+            in_step("classify_intent", async {
+                (
+                    SCOPE.with(|s| s.email.clone()),
+                    STEP.try_with(|s| *s).unwrap_or("unscoped"),
+                )
+            })
+            .await
+        })
+        .await;
+
+        assert_eq!(email.as_deref(), Some("alice@example.com"));
+        assert_eq!(step, "classify_intent");
+        assert!(SCOPE.try_with(|_| ()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_variants_are_attributed_per_step() {
+        let (matching, other, assigned) = scoped(UsageScope::default(), async {
+            in_step("endpoint_matching", async {
+                note_variant("find_endpoint_v3", "v3")
+            })
+            .await;
+            (
+                in_step("endpoint_matching", async { current_variant() }).await,
+                in_step("json_generation", async { current_variant() }).await,
+                assigned_variants(),
+            )
+        })
+        .await;
+
+        assert_eq!(
+            matching,
+            Some(("find_endpoint_v3".to_string(), "v3".to_string()))
+        );
+        assert_eq!(other, None);
+        assert_eq!(
+            assigned,
+            vec![("find_endpoint_v3".to_string(), "v3".to_string())]
+        );
+        assert!(assigned_variants().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_request_usage_counts_every_call() {
+        // Without a ledger the calls are still counted toward the request
+        let provider = MeteredProvider::new(Arc::new(FixedProvider), Arc::new(StoreSlot::empty()));
+        let model = ModelConfig::default();
+
+        let spent = scoped(UsageScope::default(), async {
+            for _ in 0..2 {
+                provider.generate("which endpoint?", &model).await.unwrap();
+            }
+            request_usage()
+        })
+        .await;
+
+        assert_eq!(spent.total_tokens, 20);
+        assert_eq!(spent.input_tokens, 14);
+        assert_eq!(request_usage(), TokenUsage::default());
+    }
+}
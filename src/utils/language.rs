@@ -0,0 +1,302 @@
+// src/utils/language.rs - Offline language identification
+//
+// Scripts decide Russian, Arabic, Japanese, Korean and Chinese on their own. Text in
+// Latin script is scored against character trigram profiles built from the samples
+// below with a naive Bayes model. The confidence is the model's probability for the
+// winner, scaled down by the share of letters outside its script; short or mixed
+// text comes out less confident.
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Languages the identifier can return, the same codes the prompts ask an LLM for
+pub const LANGUAGES: [&str; 12] = [
+    "en", "fr", "es", "de", "it", "pt", "nl", "ru", "ja", "zh", "ko", "ar",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedLanguage {
+    pub code: &'static str,
+    /// Between 0 and 1
+    pub confidence: f32,
+}
+
+const SAMPLES: [(&str, &str); 7] = [
+    (
+        "en",
+        "What can I do? Help me please. Send an email to John about the meeting and \
+         the new report. Schedule a meeting for tomorrow with the team at three. Create \
+         a support ticket because the server is down. Generate the monthly report for \
+         the sales department. I would like to know what you can do for me and how it \
+         works. The weather is nice today, so we are going to the park with our \
+         friends. Please tell me which actions are available, what they need and where \
+         I can find them. This is the best way to get things done quickly and without \
+         any trouble. Could you show me all the things that I am allowed to use here?",
+    ),
+    (
+        "fr",
+        "Que puis-je faire ? Aidez-moi s'il vous plaît. Envoyer un email à Jean au sujet \
+         de la réunion et du nouveau rapport. Planifier une réunion pour demain avec \
+         l'équipe à quinze heures. Créer un ticket de support parce que le serveur est \
+         en panne. Générer le rapport mensuel pour le service des ventes. Je voudrais \
+         savoir ce que vous pouvez faire pour moi et comment cela fonctionne. Il fait \
+         beau aujourd'hui, alors nous allons au parc avec nos amis. Dites-moi quelles \
+         actions sont disponibles, ce dont elles ont besoin et où je peux les trouver. \
+         C'est la meilleure façon de faire les choses rapidement et sans problème. \
+         Pouvez-vous me montrer toutes les choses que je peux utiliser ici ?",
+    ),
+    (
+        "es",
+        "¿Qué puedo hacer? Ayúdame por favor. Enviar un correo a Juan sobre la reunión \
+         y el nuevo informe. Programar una reunión para mañana con el equipo a las tres. \
+         Crear un ticket de soporte porque el servidor está caído. Generar el informe \
+         mensual para el departamento de ventas. Me gustaría saber qué puedes hacer por \
+         mí y cómo funciona. Hoy hace buen tiempo, así que vamos al parque con nuestros \
+         amigos. Dime qué acciones están disponibles, qué necesitan y dónde puedo \
+         encontrarlas. Es la mejor manera de hacer las cosas rápidamente y sin ningún \
+         problema. ¿Puedes mostrarme todas las cosas que puedo usar aquí?",
+    ),
+    (
+        "de",
+        "Was kann ich tun? Hilf mir bitte. Sende eine E-Mail an Johann über das Meeting \
+         und den neuen Bericht. Plane ein Meeting für morgen mit dem Team um drei Uhr. \
+         Erstelle ein Support-Ticket, weil der Server ausgefallen ist. Erzeuge den \
+         monatlichen Bericht für die Verkaufsabteilung. Ich möchte wissen, was Sie für \
+         mich tun können und wie es funktioniert. Das Wetter ist heute schön, also gehen \
+         wir mit unseren Freunden in den Park. Sag mir, welche Aktionen verfügbar sind, \
+         was sie brauchen und wo ich sie finden kann. Das ist der beste Weg, um Dinge \
+         schnell und ohne Probleme zu erledigen. Kannst du mir alle Dinge zeigen, die \
+         ich hier benutzen darf?",
+    ),
+    (
+        "it",
+        "Cosa posso fare? Aiutami per favore. Inviare una email a Giovanni sulla \
+         riunione e sul nuovo rapporto. Pianificare una riunione per domani con la \
+         squadra alle tre. Creare un ticket di supporto perché il server non funziona. \
+         Generare il rapporto mensile per il reparto vendite. Vorrei sapere cosa puoi \
+         fare per me e come funziona. Oggi il tempo è bello, quindi andiamo al parco con \
+         i nostri amici. Dimmi quali azioni sono disponibili, di cosa hanno bisogno e \
+         dove posso trovarle. Questo è il modo migliore per fare le cose velocemente e \
+         senza problemi. Puoi mostrarmi tutte le cose che posso usare qui?",
+    ),
+    (
+        "pt",
+        "O que posso fazer? Ajude-me por favor. Enviar um email ao João sobre a reunião \
+         e o novo relatório. Agendar uma reunião para amanhã com a equipe às três. Criar \
+         um ticket de suporte porque o servidor está fora do ar. Gerar o relatório mensal \
+         para o departamento de vendas. Gostaria de saber o que você pode fazer por mim e \
+         como funciona. O tempo está bom hoje, então vamos ao parque com os nossos \
+         amigos. Diga-me quais ações estão disponíveis, do que elas precisam e onde \
+         posso encontrá-las. Esta é a melhor maneira de fazer as coisas rapidamente e \
+         sem nenhum problema. Você pode me mostrar todas as coisas que posso usar aqui?",
+    ),
+    (
+        "nl",
+        "Wat kan ik doen? Help me alsjeblieft. Stuur een e-mail naar Jan over de \
+         vergadering en het nieuwe rapport. Plan een vergadering voor morgen met het team \
+         om drie uur. Maak een supportticket aan omdat de server niet werkt. Genereer het \
+         maandelijkse rapport voor de verkoopafdeling. Ik wil graag weten wat jij voor mij \
+         kunt doen en hoe het werkt. Het weer is mooi vandaag, dus we gaan met onze \
+         vrienden naar het park. Vertel me welke acties beschikbaar zijn, wat ze nodig \
+         hebben en waar ik ze kan vinden. Dit is de beste manier om dingen snel en zonder \
+         problemen te doen. Kun je me alle dingen laten zien die ik hier mag gebruiken?",
+    ),
+];
+
+/// Log probabilities of character trigrams for one language
+struct Profile {
+    code: &'static str,
+    log_probs: HashMap<String, f64>,
+    unseen: f64,
+}
+
+fn profiles() -> &'static [Profile] {
+    static PROFILES: OnceLock<Vec<Profile>> = OnceLock::new();
+    PROFILES.get_or_init(|| {
+        SAMPLES
+            .iter()
+            .map(|(code, sample)| {
+                let mut counts: HashMap<String, u32> = HashMap::new();
+                for trigram in trigrams(sample) {
+                    *counts.entry(trigram).or_default() += 1;
+                }
+                // Add-one smoothing over a vocabulary larger than any sample
+                let total = counts.values().sum::<u32>() as f64 + 4096.0;
+                Profile {
+                    code,
+                    log_probs: counts
+                        .into_iter()
+                        .map(|(trigram, count)| (trigram, ((count + 1) as f64 / total).ln()))
+                        .collect(),
+                    unseen: (1.0 / total).ln(),
+                }
+            })
+            .collect()
+    })
+}
+
+/// Trigrams of every word, padded with spaces so word starts and ends count
+fn trigrams(text: &str) -> Vec<String> {
+    let mut trigrams = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+    {
+        let chars: Vec<char> = format!(" {} ", word.to_lowercase()).chars().collect();
+        trigrams.extend(chars.windows(3).map(|w| w.iter().collect::<String>()));
+    }
+    trigrams
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Arabic,
+    Hangul,
+    Kana,
+    Han,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c as u32 {
+        0x0400..=0x04FF => Some(Script::Cyrillic),
+        0x0600..=0x06FF | 0x0750..=0x077F => Some(Script::Arabic),
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Some(Script::Hangul),
+        0x3040..=0x30FF => Some(Script::Kana),
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF => Some(Script::Han),
+        _ if c.is_alphabetic() && (c.is_ascii() || (c as u32) < 0x0250) => Some(Script::Latin),
+        _ => None,
+    }
+}
+
+/// Identify the language of `text`, `None` when it has no letters
+pub fn detect(text: &str) -> Option<DetectedLanguage> {
+    let mut counts: HashMap<Script, usize> = HashMap::new();
+    for c in text.chars() {
+        if let Some(script) = script(c) {
+            *counts.entry(script).or_default() += 1;
+        }
+    }
+    let letters: usize = counts.values().sum();
+    if letters == 0 {
+        return None;
+    }
+    let share = |scripts: &[Script]| {
+        scripts
+            .iter()
+            .map(|s| counts.get(s).copied().unwrap_or(0))
+            .sum::<usize>() as f32
+            / letters as f32
+    };
+
+    // Japanese mixes kana with Han characters, any kana at all rules out Chinese
+    let candidates = [
+        (
+            "ja",
+            share(&[Script::Kana, Script::Han]),
+            counts.contains_key(&Script::Kana),
+        ),
+        (
+            "zh",
+            share(&[Script::Han]),
+            !counts.contains_key(&Script::Kana),
+        ),
+        ("ko", share(&[Script::Hangul]), true),
+        ("ru", share(&[Script::Cyrillic]), true),
+        ("ar", share(&[Script::Arabic]), true),
+    ];
+    if let Some((code, confidence, _)) = candidates
+        .into_iter()
+        .filter(|(_, share, eligible)| *eligible && *share >= 0.5)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+    {
+        return Some(DetectedLanguage { code, confidence });
+    }
+
+    let (code, probability) = classify_latin(text)?;
+    Some(DetectedLanguage {
+        code,
+        confidence: probability * share(&[Script::Latin]),
+    })
+}
+
+/// Language code of `text` when identified with at least `min_confidence`
+pub fn detect_confident(text: &str, min_confidence: f32) -> Option<&'static str> {
+    detect(text)
+        .filter(|detected| detected.confidence >= min_confidence)
+        .map(|detected| detected.code)
+}
+
+/// Most likely Latin-script language with its posterior probability
+fn classify_latin(text: &str) -> Option<(&'static str, f32)> {
+    let trigrams = trigrams(text);
+    if trigrams.is_empty() {
+        return None;
+    }
+    let scores: Vec<(&'static str, f64)> = profiles()
+        .iter()
+        .map(|profile| {
+            let score = trigrams
+                .iter()
+                .map(|t| profile.log_probs.get(t).copied().unwrap_or(profile.unseen))
+                .sum::<f64>();
+            (profile.code, score)
+        })
+        .collect();
+
+    let (best, best_score) = scores.iter().copied().max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let total: f64 = scores.iter().map(|(_, s)| (s - best_score).exp()).sum();
+    Some((best, (1.0 / total) as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(text: &str) -> &'static str {
+        detect(text).map(|d| d.code).unwrap_or("none")
+    }
+
+    #[test]
+    fn test_detects_the_supported_languages() {
+        let cases = [
+            ("en", "Show me the open tickets assigned to my team"),
+            (
+                "fr",
+                "Envoyer un courriel à Marie pour confirmer le rendez-vous",
+            ),
+            ("es", "Necesito crear una factura para el cliente nuevo"),
+            (
+                "de",
+                "Bitte erstelle einen Termin mit der Buchhaltung nächste Woche",
+            ),
+            (
+                "it",
+                "Vorrei prenotare una sala riunioni per giovedì pomeriggio",
+            ),
+            (
+                "pt",
+                "Preciso enviar a fatura para o cliente até sexta-feira",
+            ),
+            ("nl", "Ik wil een afspraak maken met de klant volgende week"),
+            ("ru", "Отправь письмо Ивану о встрече"),
+            ("ja", "明日の会議の予定を教えてください"),
+            ("zh", "请帮我安排明天的会议"),
+            ("ko", "내일 회의 일정을 잡아 주세요"),
+            ("ar", "أرسل بريدا إلكترونيا إلى أحمد"),
+        ];
+        for (expected, text) in cases {
+            assert_eq!(code(text), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn test_confidence_is_low_for_short_or_unclear_text() {
+        assert!(detect("1234 !!").is_none());
+        let long =
+            detect("Pouvez-vous planifier une réunion avec l'équipe demain matin ?").unwrap();
+        assert!(long.confidence > 0.9, "{long:?}");
+        let short = detect("ok").unwrap();
+        assert!(short.confidence < long.confidence, "{short:?}");
+    }
+}
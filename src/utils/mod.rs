@@ -1,3 +1,5 @@
 pub mod email;
+pub mod language;
 pub mod path_params;
 pub mod token_calculator;
+pub mod tokenizer;
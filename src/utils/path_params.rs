@@ -16,6 +16,7 @@ pub fn add_path_parameters_to_list(
                     description: format!("URL path parameter: {}", param_name),
                     semantic_value: None,
                     alternatives: None,
+                    examples: None,
                     required: Some(true),
                 });
             }
@@ -25,6 +26,7 @@ pub fn add_path_parameters_to_list(
                     name: param_name.clone(),
                     description: format!("URL path parameter: {}", param_name),
                     value: None,
+                    default_source: None,
                 });
             }
         }
@@ -1,6 +1,8 @@
 // src/utils/token_calculator.rs
 use std::collections::HashMap;
 use crate::app_log;
+use crate::utils::language;
+use crate::utils::tokenizer;
 
 pub struct EnhancedTokenCalculator {
     // More accurate token estimation ratios per provider
@@ -80,6 +82,11 @@ impl EnhancedTokenCalculator {
         provider: &str,
         language: Option<&str>,
     ) -> u32 {
+        // A loaded vocabulary gives the exact count, the ratios below are the fallback
+        if let Some(count) = tokenizer::count_tokens(provider, text) {
+            return count as u32;
+        }
+
         let ratio = self
             .provider_rates
             .get(provider)
@@ -118,36 +125,9 @@ impl EnhancedTokenCalculator {
         }
     }
 
-    /// Detect language from text content (simple heuristic)
+    /// Detect language from text content, English when there is no text to go by
     pub fn detect_language(&self, text: &str) -> &str {
-        let text_lower = text.to_lowercase();
-
-        // Simple language detection based on common words
-        if text_lower.contains("the ")
-            || text_lower.contains(" and ")
-            || text_lower.contains(" is ")
-        {
-            "en"
-        } else if text_lower.contains(" le ")
-            || text_lower.contains(" la ")
-            || text_lower.contains(" et ")
-            || text_lower.contains(" pour ")
-            || text_lower.contains(" avec ")
-        {
-            "fr"
-        } else if text_lower.contains(" el ")
-            || text_lower.contains(" la ")
-            || text_lower.contains(" y ")
-        {
-            "es"
-        } else if text_lower.contains(" der ")
-            || text_lower.contains(" die ")
-            || text_lower.contains(" und ")
-        {
-            "de"
-        } else {
-            "en" // Default to English
-        }
+        language::detect(text).map_or("en", |detected| detected.code)
     }
 
     /// Calculate tokens for both input and output with context
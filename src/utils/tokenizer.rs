@@ -0,0 +1,319 @@
+// src/utils/tokenizer.rs - Exact token counts from BPE vocabularies loaded from disk
+use crate::app_log;
+use crate::models::config::{TokenizerConfig, TokenizerFormat};
+use base64::Engine;
+use fancy_regex::Regex;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// Pre-tokenization of OpenAI `cl100k_base` style vocabularies
+pub const CL100K_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+/// Pre-tokenization of GPT-2 style byte-level BPE, used by most `tokenizer.json` files
+pub const GPT2_PATTERN: &str =
+    r"'s|'t|'re|'ve|'m|'ll|'d| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+(?!\S)|\s+";
+
+pub trait Tokenizer: Send + Sync {
+    fn encode(&self, text: &str) -> Vec<u32>;
+
+    fn count_tokens(&self, text: &str) -> usize {
+        self.encode(text).len()
+    }
+}
+
+/// Byte-level BPE: split with a regex, then merge byte pairs by rank
+pub struct BpeTokenizer {
+    /// Token bytes to token id
+    encoder: HashMap<Vec<u8>, u32>,
+    /// Merge priority of a token's bytes, lowest merges first
+    ranks: HashMap<Vec<u8>, u32>,
+    pattern: Regex,
+}
+
+impl BpeTokenizer {
+    pub fn load(config: &TokenizerConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let contents = std::fs::read_to_string(&config.path)
+            .map_err(|e| format!("Cannot read tokenizer file {}: {e}", config.path))?;
+        match config.format {
+            TokenizerFormat::Tiktoken => Self::from_tiktoken(
+                &contents,
+                config.pattern.as_deref().unwrap_or(CL100K_PATTERN),
+            ),
+            TokenizerFormat::Huggingface => {
+                Self::from_huggingface(&contents, config.pattern.as_deref().unwrap_or(GPT2_PATTERN))
+            }
+        }
+    }
+
+    /// tiktoken rank file: one `<base64 token> <rank>` per line
+    pub fn from_tiktoken(
+        contents: &str,
+        pattern: &str,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut encoder = HashMap::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (token, rank) = line
+                .split_once(' ')
+                .ok_or_else(|| format!("Invalid tiktoken line {}", number + 1))?;
+            let token = base64::engine::general_purpose::STANDARD
+                .decode(token)
+                .map_err(|e| format!("Invalid token on line {}: {e}", number + 1))?;
+            let rank: u32 = rank
+                .trim()
+                .parse()
+                .map_err(|e| format!("Invalid rank on line {}: {e}", number + 1))?;
+            encoder.insert(token, rank);
+        }
+
+        Ok(Self {
+            ranks: encoder.clone(),
+            encoder,
+            pattern: Regex::new(pattern)?,
+        })
+    }
+
+    /// Hugging Face `tokenizer.json` with a byte-level BPE model
+    pub fn from_huggingface(
+        contents: &str,
+        pattern: &str,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let json: serde_json::Value = serde_json::from_str(contents)?;
+        let model = json.get("model").ok_or("tokenizer.json has no model")?;
+        if model.get("type").and_then(|t| t.as_str()) != Some("BPE") {
+            return Err("Only BPE models are supported in tokenizer.json".into());
+        }
+
+        let byte_decoder = byte_decoder();
+        let decode = |token: &str| -> Option<Vec<u8>> {
+            token
+                .chars()
+                .map(|c| byte_decoder.get(&c).copied())
+                .collect()
+        };
+
+        let mut encoder = HashMap::new();
+        for (token, id) in model
+            .get("vocab")
+            .and_then(|v| v.as_object())
+            .ok_or("tokenizer.json has no vocab")?
+        {
+            // Entries outside the byte alphabet (e.g. special tokens) never come out of a merge
+            if let (Some(bytes), Some(id)) = (decode(token), id.as_u64()) {
+                encoder.insert(bytes, id as u32);
+            }
+        }
+
+        let mut ranks = HashMap::new();
+        let merges = model
+            .get("merges")
+            .and_then(|m| m.as_array())
+            .ok_or("tokenizer.json has no merges")?;
+        for (rank, merge) in merges.iter().enumerate() {
+            // Older files store "a b", newer ones ["a", "b"]
+            let pair = match merge {
+                serde_json::Value::String(pair) => pair.split_once(' '),
+                serde_json::Value::Array(pair) => pair
+                    .first()
+                    .and_then(|left| left.as_str())
+                    .zip(pair.get(1).and_then(|right| right.as_str())),
+                _ => None,
+            };
+            let Some((left, right)) = pair else { continue };
+            if let (Some(mut merged), Some(right)) = (decode(left), decode(right)) {
+                merged.extend(right);
+                ranks.entry(merged).or_insert(rank as u32);
+            }
+        }
+
+        Ok(Self {
+            encoder,
+            ranks,
+            pattern: Regex::new(pattern)?,
+        })
+    }
+
+    fn encode_piece(&self, piece: &[u8], ids: &mut Vec<u32>) {
+        if let Some(id) = self.encoder.get(piece) {
+            ids.push(*id);
+            return;
+        }
+
+        let mut parts: Vec<Vec<u8>> = piece.iter().map(|b| vec![*b]).collect();
+        loop {
+            let best = parts
+                .windows(2)
+                .enumerate()
+                .filter_map(|(i, pair)| {
+                    let merged = [pair[0].as_slice(), pair[1].as_slice()].concat();
+                    self.ranks.get(&merged).map(|rank| (*rank, i))
+                })
+                .min();
+            let Some((_, i)) = best else { break };
+            let right = parts.remove(i + 1);
+            parts[i].extend(right);
+        }
+
+        // Bytes missing from the vocabulary still count as one token each
+        ids.extend(
+            parts
+                .iter()
+                .map(|part| self.encoder.get(part).copied().unwrap_or(u32::MAX)),
+        );
+    }
+}
+
+impl Tokenizer for BpeTokenizer {
+    fn encode(&self, text: &str) -> Vec<u32> {
+        let mut ids = Vec::new();
+        let mut encoded_up_to = 0;
+        for piece in self.pattern.find_iter(text) {
+            match piece {
+                Ok(piece) => {
+                    self.encode_piece(piece.as_str().as_bytes(), &mut ids);
+                    encoded_up_to = piece.end();
+                }
+                // Backtracking limit hit: fall back to the raw bytes of the rest
+                Err(_) => {
+                    ids.extend(
+                        text[encoded_up_to..]
+                            .bytes()
+                            .map(|b| self.encoder.get(&vec![b]).copied().unwrap_or(u32::MAX)),
+                    );
+                    break;
+                }
+            }
+        }
+        ids
+    }
+}
+
+/// GPT-2 byte-to-unicode table, reversed
+fn byte_decoder() -> HashMap<char, u8> {
+    let printable = |b: u8| matches!(b, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF);
+    let mut decoder = HashMap::new();
+    let mut shifted = 0;
+    for byte in 0..=255u8 {
+        let c = if printable(byte) {
+            char::from(byte)
+        } else {
+            shifted += 1;
+            char::from_u32(255 + shifted).unwrap()
+        };
+        decoder.insert(c, byte);
+    }
+    decoder
+}
+
+static TOKENIZERS: LazyLock<RwLock<HashMap<String, Arc<dyn Tokenizer>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Use `tokenizer` for every count made for `provider`
+pub fn register(provider: &str, tokenizer: Arc<dyn Tokenizer>) {
+    TOKENIZERS
+        .write()
+        .unwrap()
+        .insert(provider.to_string(), tokenizer);
+}
+
+/// Load the configured vocabularies; providers whose file cannot be loaded keep the
+/// character heuristics
+pub fn load_configured(configs: &HashMap<String, TokenizerConfig>) {
+    for (provider, config) in configs {
+        match BpeTokenizer::load(config) {
+            Ok(tokenizer) => {
+                app_log!(
+                    info,
+                    "Loaded {:?} tokenizer for {} from {}",
+                    config.format,
+                    provider,
+                    config.path
+                );
+                register(provider, Arc::new(tokenizer));
+            }
+            Err(e) => {
+                app_log!(
+                    warn,
+                    "Tokenizer for {} not loaded, estimating instead: {}",
+                    provider,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Exact token count when a tokenizer is loaded for the provider
+pub fn count_tokens(provider: &str, text: &str) -> Option<usize> {
+    let tokenizer = TOKENIZERS.read().unwrap().get(provider).cloned()?;
+    Some(tokenizer.count_tokens(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiktoken_file(tokens: &[&str]) -> String {
+        let mut lines: Vec<String> = (0..=255u8)
+            .map(|b| {
+                format!(
+                    "{} {}",
+                    base64::engine::general_purpose::STANDARD.encode([b]),
+                    b
+                )
+            })
+            .collect();
+        for (i, token) in tokens.iter().enumerate() {
+            lines.push(format!(
+                "{} {}",
+                base64::engine::general_purpose::STANDARD.encode(token),
+                256 + i
+            ));
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_tiktoken_merges_by_rank() {
+        let tokenizer = BpeTokenizer::from_tiktoken(
+            &tiktoken_file(&["he", "ll", "hell", "hello"]),
+            CL100K_PATTERN,
+        )
+        .unwrap();
+
+        assert_eq!(tokenizer.encode("hello"), vec![259]);
+        // "hellx" -> "hell" + "x"
+        assert_eq!(tokenizer.encode("hellx"), vec![258, b'x' as u32]);
+        // " world" is one regex piece of single bytes without merges
+        assert_eq!(tokenizer.count_tokens("hello world"), 1 + 6);
+    }
+
+    #[test]
+    fn test_huggingface_byte_level_bpe() {
+        let json = serde_json::json!({
+            "model": {
+                "type": "BPE",
+                "vocab": {"h": 0, "i": 1, "Ġ": 2, "hi": 3, "Ġhi": 4, "<|endoftext|>": 5},
+                // Malformed entries are skipped
+                "merges": ["h i", ["Ġ", "hi"], ["x"], [], "hi"]
+            }
+        });
+        let tokenizer = BpeTokenizer::from_huggingface(&json.to_string(), GPT2_PATTERN).unwrap();
+
+        assert_eq!(tokenizer.encode("hi hi"), vec![3, 4]);
+        assert_eq!(tokenizer.count_tokens("hih"), 2);
+    }
+
+    #[test]
+    fn test_registry_counts_only_for_loaded_providers() {
+        let tokenizer = BpeTokenizer::from_tiktoken(&tiktoken_file(&[]), CL100K_PATTERN).unwrap();
+        register("test-provider", Arc::new(tokenizer));
+
+        assert_eq!(count_tokens("test-provider", "abc"), Some(3));
+        assert_eq!(count_tokens("unknown-provider", "abc"), None);
+    }
+}
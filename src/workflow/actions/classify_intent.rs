@@ -1,10 +1,13 @@
+use crate::app_log;
 use crate::models::config::load_models_config;
+use crate::models::providers::token_counter::TokenUsage;
 use crate::models::providers::ModelProvider;
+use crate::prompts::budget::{self, PromptSection};
+use crate::prompts::template::Vars;
 use crate::prompts::PromptManager;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::sync::Arc;
-use crate::app_log;
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub enum IntentType {
@@ -17,34 +20,59 @@ pub async fn classify_intent(
     sentence: &str,
     available_endpoints: &[String],
     provider: Arc<dyn ModelProvider>,
-) -> Result<IntentType, Box<dyn Error + Send + Sync>> {
+) -> Result<(IntentType, TokenUsage), Box<dyn Error + Send + Sync>> {
     app_log!(info, "Classifying intent for: {}", sentence);
 
     let prompt_manager = PromptManager::new().await?;
-    let endpoints_list = available_endpoints.join("\n- ");
-
-    // Use v3 prompt that supports HELP classification
-    let prompt = prompt_manager.format_intent_classification(sentence, &endpoints_list, Some("v3"));
-    app_log!(debug, "Generated intent classification prompt: {}", prompt);
-
     let models_config = load_models_config().await?;
     let model_config = &models_config.default;
 
-    let response = provider.generate(&prompt, model_config).await?;
+    let endpoints: Vec<Vars> = available_endpoints
+        .iter()
+        .map(|description| Vars::from([("description".to_string(), description.as_str().into())]))
+        .collect();
+
+    // The default version (v3) supports HELP classification
+    let template = prompt_manager
+        .get_prompt("intent_classification", None)
+        .ok_or("intent_classification prompt not found")?;
+    let prompt = budget::fit(
+        template,
+        vec![
+            PromptSection::fixed("sentence", sentence),
+            PromptSection::records("endpoints", endpoints).shorten_items_to(120),
+        ],
+        provider.get_model_name(),
+        model_config,
+    )
+    .await?;
+    app_log!(
+        debug,
+        "Generated intent classification prompt: {}",
+        prompt.prompt
+    );
+
+    let response = provider
+        .generate(&prompt.prompt, &prompt.model_config)
+        .await?;
     app_log!(debug, "Intent classification response: {:?}", response);
 
     // Direct keyword extraction - search entire response
     let response_upper = response.content.to_uppercase();
+    let usage = response.usage;
 
     if response_upper.contains("ACTIONABLE") {
-        app_log!(info, "Found 'ACTIONABLE' - classified as actionable request");
-        Ok(IntentType::ActionableRequest)
+        app_log!(
+            info,
+            "Found 'ACTIONABLE' - classified as actionable request"
+        );
+        Ok((IntentType::ActionableRequest, usage))
     } else if response_upper.contains("HELP") {
         app_log!(info, "Found 'HELP' - classified as help request");
-        Ok(IntentType::HelpRequest)
+        Ok((IntentType::HelpRequest, usage))
     } else if response_upper.contains("GENERAL") {
         app_log!(info, "Found 'GENERAL' - classified as general question");
-        Ok(IntentType::GeneralQuestion)
+        Ok((IntentType::GeneralQuestion, usage))
     } else {
         // Enhanced fallback logic for better classification
         let sentence_lower = sentence.to_lowercase();
@@ -86,12 +114,18 @@ pub async fn classify_intent(
             .iter()
             .any(|keyword| sentence_lower.contains(keyword))
         {
-            app_log!(info, "Fallback: detected help keywords, classifying as help request");
-            Ok(IntentType::HelpRequest)
+            app_log!(
+                info,
+                "Fallback: detected help keywords, classifying as help request"
+            );
+            Ok((IntentType::HelpRequest, usage))
         } else {
             // Default to general if no clear classification
-            app_log!(info, "No clear classification found, defaulting to general question");
-            Ok(IntentType::GeneralQuestion)
+            app_log!(
+                info,
+                "No clear classification found, defaulting to general question"
+            );
+            Ok((IntentType::GeneralQuestion, usage))
         }
     }
 }
@@ -1,18 +1,24 @@
+use crate::app_log;
+use crate::error::SemanticError;
+use crate::metrics;
 use std::error::Error;
 use std::sync::Arc;
-use crate::app_log;
 
 use crate::models::config::load_models_config;
+use crate::models::providers::token_counter::TokenUsage;
 use crate::models::providers::ModelProvider;
-use crate::models::{Endpoint, EnhancedEndpoint};
+use crate::models::{Endpoint, EnhancedEndpoint, RiskLevel};
+use crate::prompts::budget::{self, PromptSection};
+use crate::prompts::template::Vars;
 use crate::prompts::PromptManager;
 
 pub async fn find_closest_endpoint_pure_llm(
     enhanced_endpoints: &[EnhancedEndpoint],
     input_sentence: &str,
     provider: Arc<dyn ModelProvider>,
-) -> Result<EnhancedEndpoint, Box<dyn Error + Send + Sync>> {
-    app_log!(info, 
+) -> Result<(EnhancedEndpoint, TokenUsage), Box<dyn Error + Send + Sync>> {
+    app_log!(
+        info,
         "Starting pure LLM endpoint matching for input: {}",
         input_sentence
     );
@@ -29,31 +35,54 @@ pub async fn find_closest_endpoint_pure_llm(
     // Initialize the PromptManager
     let prompt_manager = PromptManager::new().await?;
 
-    // Create structured endpoints list for the prompt
-    let mut endpoints_list = String::new();
-    for endpoint in enhanced_endpoints.iter() {
-        endpoints_list.push_str(&format!(
-            "- {} ({})\n", // Remove numbering, use bullet points
-            endpoint.id, endpoint.description
-        ));
-    }
+    // One record per endpoint for the template's endpoints loop
+    let endpoints: Vec<Vars> = enhanced_endpoints
+        .iter()
+        .map(|endpoint| {
+            Vars::from([
+                ("id".to_string(), endpoint.id.as_str().into()),
+                (
+                    "description".to_string(),
+                    endpoint.description.as_str().into(),
+                ),
+            ])
+        })
+        .collect();
 
-    // Get formatted prompt from PromptManager using v2
-    let prompt =
-        prompt_manager.format_find_endpoint_v2(input_sentence, &endpoints_list, Some("v2"));
-    app_log!(debug, "Generated prompt:\n{}", prompt);
+    // Get the template from PromptManager and fit it to the context window
+    let template = prompt_manager
+        .get_prompt("find_endpoint", None)
+        .ok_or("find_endpoint prompt not found")?;
+    let prompt = budget::fit(
+        template,
+        vec![
+            PromptSection::fixed("input_sentence", input_sentence),
+            PromptSection::records("endpoints", endpoints).shorten_items_to(200),
+        ],
+        provider.get_model_name(),
+        model_config,
+    )
+    .await?;
+    app_log!(debug, "Generated prompt:\n{}", prompt.prompt);
 
     // Use the provider to get LLM response
     app_log!(info, "Using LLM for semantic endpoint selection");
-    let raw_response = provider.generate(&prompt, model_config).await?;
+    let raw_response = provider
+        .generate(&prompt.prompt, &prompt.model_config)
+        .await?;
     app_log!(debug, "Raw LLM response: '{:?}'", raw_response);
 
     // Extract endpoint ID from response
+    let usage = raw_response.usage.clone();
     let endpoint_id = raw_response.content.trim();
 
     if endpoint_id == "NO_MATCH" {
-        app_log!(error, "LLM determined no suitable endpoint matches the input");
-        return Err("No suitable endpoint found for the given input".into());
+        metrics::record_endpoint_match("no_match");
+        app_log!(
+            error,
+            "LLM determined no suitable endpoint matches the input"
+        );
+        return Err(Box::new(SemanticError::NoMatch));
     }
 
     // Find the matching endpoint by ID
@@ -64,15 +93,18 @@ pub async fn find_closest_endpoint_pure_llm(
 
     match matched_endpoint {
         Some(endpoint) => {
+            metrics::record_endpoint_match("matched");
             app_log!(info, "Successfully matched endpoint: {}", endpoint.id);
-            Ok(endpoint)
+            Ok((endpoint, usage))
         }
         None => {
-            app_log!(warn, 
+            app_log!(
+                warn,
                 "LLM returned endpoint ID '{}' which doesn't exist in available endpoints",
                 endpoint_id
             );
-            app_log!(error, 
+            app_log!(
+                error,
                 "Available endpoint IDs: {:?}",
                 enhanced_endpoints.iter().map(|e| &e.id).collect::<Vec<_>>()
             );
@@ -88,19 +120,21 @@ pub async fn find_closest_endpoint_pure_llm(
 
             match fallback_match {
                 Some(endpoint) => {
+                    metrics::record_endpoint_match("fallback");
                     app_log!(warn, "Found fallback match: {}", endpoint.id);
-                    Ok(endpoint)
+                    Ok((endpoint, usage))
+                }
+                None => {
+                    metrics::record_endpoint_match("unknown_id");
+                    Err(Box::new(SemanticError::UnknownEndpoint {
+                        id: endpoint_id.to_string(),
+                        available: enhanced_endpoints
+                            .iter()
+                            .map(|e| e.id.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    }))
                 }
-                None => Err(format!(
-                    "Endpoint ID '{}' not found in available endpoints. Available IDs: [{}]",
-                    endpoint_id,
-                    enhanced_endpoints
-                        .iter()
-                        .map(|e| e.id.as_str())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                )
-                .into()),
             }
         }
     }
@@ -111,7 +145,7 @@ pub async fn find_closest_endpoint(
     config: &crate::models::ConfigFile,
     input_sentence: &str,
     provider: Arc<dyn ModelProvider>,
-) -> Result<Endpoint, Box<dyn Error + Send + Sync>> {
+) -> Result<(Endpoint, TokenUsage), Box<dyn Error + Send + Sync>> {
     // Convert ConfigFile endpoints to EnhancedEndpoint format for the new function
     let enhanced_endpoints: Vec<EnhancedEndpoint> = config
         .endpoints
@@ -128,17 +162,19 @@ pub async fn find_closest_endpoint(
             api_group_id: "default".to_string(),
             api_group_name: "Default Group".to_string(),
             parameters: e.parameters.clone(),
+            risk_level: RiskLevel::Medium,
         })
         .collect();
 
-    let enhanced_result =
+    let (enhanced_result, usage) =
         find_closest_endpoint_pure_llm(&enhanced_endpoints, input_sentence, provider).await?;
 
     // Convert back to regular Endpoint
-    Ok(Endpoint {
+    let endpoint = Endpoint {
         id: enhanced_result.id,
         text: enhanced_result.text,
         description: enhanced_result.description,
         parameters: enhanced_result.parameters,
-    })
+    };
+    Ok((endpoint, usage))
 }
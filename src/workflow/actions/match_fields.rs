@@ -1,24 +1,35 @@
 // src/workflow/actions/match_fields.rs - Generic industry-agnostic implementation
 
+use crate::app_log;
 use crate::json_helper::sanitize_json;
 use crate::models::config::load_models_config;
 use crate::models::Endpoint;
+use crate::prompts::budget::{self, PromptSection};
+use crate::prompts::template::Vars;
 use crate::prompts::PromptManager;
 use serde_json::Value;
 use std::error::Error;
-use crate::app_log;
 
+use crate::models::providers::token_counter::TokenUsage;
 use crate::models::providers::ModelProvider;
 use std::sync::Arc;
 
+/// (parameter name, description, matched value) for every endpoint parameter
+pub type FieldMatches = Vec<(String, String, Option<String>)>;
+
 pub async fn match_fields_semantic(
     input_json: &Value,
     endpoint: &Endpoint,
     provider: Arc<dyn ModelProvider>,
-) -> Result<Vec<(String, String, Option<String>)>, Box<dyn Error + Send + Sync>> {
+) -> Result<(FieldMatches, TokenUsage), Box<dyn Error + Send + Sync>> {
     app_log!(debug, "Starting generic semantic field matching");
-    app_log!(debug, "Input JSON: {}", serde_json::to_string_pretty(input_json)?);
-    app_log!(debug, 
+    app_log!(
+        debug,
+        "Input JSON: {}",
+        serde_json::to_string_pretty(input_json)?
+    );
+    app_log!(
+        debug,
         "Endpoint: {} with {} parameters",
         endpoint.id,
         endpoint.parameters.len()
@@ -28,10 +39,14 @@ pub async fn match_fields_semantic(
     let extracted_fields = extract_fields_from_json(input_json)?;
     if extracted_fields.is_empty() {
         app_log!(debug, "No fields extracted from input JSON");
-        return create_empty_matches(&endpoint.parameters);
+        return Ok((
+            create_empty_matches(&endpoint.parameters)?,
+            TokenUsage::default(),
+        ));
     }
 
-    app_log!(debug, 
+    app_log!(
+        debug,
         "Extracted fields: {:?}",
         extracted_fields.keys().collect::<Vec<_>>()
     );
@@ -43,25 +58,27 @@ pub async fn match_fields_semantic(
     let unmatched_required = count_unmatched_required_params(&endpoint.parameters, &direct_matches);
 
     if unmatched_required == 0 {
-        app_log!(debug, "All required parameters matched directly, skipping semantic matching");
-        return Ok(direct_matches);
+        app_log!(
+            debug,
+            "All required parameters matched directly, skipping semantic matching"
+        );
+        return Ok((direct_matches, TokenUsage::default()));
     }
 
-    app_log!(debug, 
+    app_log!(
+        debug,
         "Found {} unmatched required parameters, attempting semantic matching",
         unmatched_required
     );
 
     // Use LLM for semantic matching
-    let semantic_matches = try_semantic_matching(
+    try_semantic_matching(
         &endpoint.parameters,
         &extracted_fields,
         &direct_matches,
         provider,
     )
-    .await?;
-
-    Ok(semantic_matches)
+    .await
 }
 
 fn extract_fields_from_json(
@@ -97,7 +114,12 @@ fn try_direct_matching(
         // Try exact parameter name match
         if let Some(value) = extracted_fields.get(&param.name) {
             matched_value = extract_string_value(value);
-            app_log!(debug, "Direct match for '{}': {:?}", param.name, matched_value);
+            app_log!(
+                debug,
+                "Direct match for '{}': {:?}",
+                param.name,
+                matched_value
+            );
         }
 
         // Try alternatives if provided and no direct match
@@ -106,9 +128,12 @@ fn try_direct_matching(
                 for alt in alternatives {
                     if let Some(value) = extracted_fields.get(alt) {
                         matched_value = extract_string_value(value);
-                        app_log!(debug, 
+                        app_log!(
+                            debug,
                             "Alternative match '{}' -> '{}': {:?}",
-                            alt, param.name, matched_value
+                            alt,
+                            param.name,
+                            matched_value
                         );
                         break;
                     }
@@ -146,51 +171,61 @@ async fn try_semantic_matching(
     extracted_fields: &serde_json::Map<String, Value>,
     direct_matches: &[(String, String, Option<String>)],
     provider: Arc<dyn ModelProvider>,
-) -> Result<Vec<(String, String, Option<String>)>, Box<dyn Error + Send + Sync>> {
+) -> Result<(FieldMatches, TokenUsage), Box<dyn Error + Send + Sync>> {
     // Prepare input for LLM
     let input_fields_str = serde_json::to_string_pretty(extracted_fields)?;
 
-    let parameters_str = endpoint_params
+    let parameters = endpoint_params
         .iter()
         .map(|p| {
-            let required_str = if p.required.unwrap_or(false) {
-                " (REQUIRED)"
-            } else {
-                " (optional)"
-            };
-            let alternatives_str = if let Some(alts) = &p.alternatives {
-                if !alts.is_empty() {
-                    format!(" [alternatives: {}]", alts.join(", "))
-                } else {
-                    String::new()
-                }
-            } else {
-                String::new()
-            };
-            format!(
-                "- {}{}: {}{}",
-                p.name, required_str, p.description, alternatives_str
-            )
+            Vars::from([
+                ("name".to_string(), p.name.as_str().into()),
+                ("description".to_string(), p.description.as_str().into()),
+                ("required".to_string(), p.required.unwrap_or(false).into()),
+                (
+                    "alternatives".to_string(),
+                    p.alternatives
+                        .as_deref()
+                        .unwrap_or_default()
+                        .join(", ")
+                        .into(),
+                ),
+            ])
         })
-        .collect::<Vec<_>>()
-        .join("\n");
+        .collect::<Vec<_>>();
 
     let prompt_manager = PromptManager::new().await?;
-    let prompt = prompt_manager
-        .get_prompt("match_fields", Some("v1"))
-        .ok_or("match_fields v3 prompt not found")?
-        .replace("{input_fields}", &input_fields_str)
-        .replace("{parameters}", &parameters_str);
-
-    app_log!(debug, 
-        "Semantic matching prompt generated, length: {} chars",
-        prompt.len()
-    );
+    let template = prompt_manager
+        .get_prompt("match_fields", None)
+        .ok_or("match_fields prompt not found")?;
 
     let models_config = load_models_config().await?;
     let model_config = &models_config.default;
 
-    let result = provider.generate(&prompt, model_config).await?;
+    // Every parameter has to stay in the prompt, only long descriptions may be shortened
+    let parameter_count = parameters.len();
+    let prompt = budget::fit(
+        template,
+        vec![
+            PromptSection::fixed("input_fields", input_fields_str),
+            PromptSection::records("parameters", parameters)
+                .keep_at_least(parameter_count)
+                .shorten_items_to(200),
+        ],
+        provider.get_model_name(),
+        model_config,
+    )
+    .await?;
+
+    app_log!(
+        debug,
+        "Semantic matching prompt generated, length: {} chars",
+        prompt.prompt.len()
+    );
+
+    let result = provider
+        .generate(&prompt.prompt, &prompt.model_config)
+        .await?;
     app_log!(debug, "Semantic matching raw response: {}", result.content);
 
     // Parse the LLM response
@@ -214,7 +249,12 @@ async fn try_semantic_matching(
                 .unwrap_or(false)
             {
                 final_value = direct_value.clone();
-                app_log!(debug, "Using direct match for '{}': {:?}", param.name, final_value);
+                app_log!(
+                    debug,
+                    "Using direct match for '{}': {:?}",
+                    param.name,
+                    final_value
+                );
             }
         }
 
@@ -222,9 +262,11 @@ async fn try_semantic_matching(
         if final_value.is_none() {
             if let Some(semantic_value) = semantic_json.get(&param.name) {
                 final_value = extract_string_value(semantic_value);
-                app_log!(debug, 
+                app_log!(
+                    debug,
                     "Using semantic match for '{}': {:?}",
-                    param.name, final_value
+                    param.name,
+                    final_value
                 );
             }
         }
@@ -232,19 +274,20 @@ async fn try_semantic_matching(
         final_matches.push((param.name.clone(), param.description.clone(), final_value));
     }
 
-    app_log!(debug, 
+    app_log!(
+        debug,
         "Final semantic matches: {:?}",
         final_matches
             .iter()
             .map(|(n, _, v)| (n, v))
             .collect::<Vec<_>>()
     );
-    Ok(final_matches)
+    Ok((final_matches, result.usage))
 }
 
 fn create_empty_matches(
     endpoint_params: &[crate::models::EndpointParameter],
-) -> Result<Vec<(String, String, Option<String>)>, Box<dyn Error + Send + Sync>> {
+) -> Result<FieldMatches, Box<dyn Error + Send + Sync>> {
     Ok(endpoint_params
         .iter()
         .map(|param| (param.name.clone(), param.description.clone(), None))
@@ -301,6 +344,7 @@ mod tests {
                 description: "".to_string(),
                 required: Some(true),
                 alternatives: None,
+                examples: None,
                 semantic_value: None,
             },
             crate::models::EndpointParameter {
@@ -308,6 +352,7 @@ mod tests {
                 description: "".to_string(),
                 required: Some(false),
                 alternatives: None,
+                examples: None,
                 semantic_value: None,
             },
         ];
@@ -1,21 +1,35 @@
 use crate::app_log;
 use crate::json_helper::sanitize_json;
 use crate::models::config::load_models_config;
+use crate::models::providers::token_counter::TokenUsage;
 use crate::models::providers::ModelProvider;
+use crate::prompts::budget::{self, PromptSection};
 use crate::prompts::PromptManager;
 use std::{error::Error, sync::Arc};
 
 pub async fn sentence_to_json(
     sentence: &str,
     provider: Arc<dyn ModelProvider>,
-) -> Result<serde_json::Value, Box<dyn Error + Send + Sync>> {
+) -> Result<(serde_json::Value, TokenUsage), Box<dyn Error + Send + Sync>> {
     let prompt_manager = PromptManager::new().await?;
-    let full_prompt = prompt_manager.format_sentence_to_json(sentence, Some("v1"));
+    let template = prompt_manager
+        .get_prompt("sentence_to_json", None)
+        .ok_or("sentence_to_json prompt not found")?;
 
     let models_config = load_models_config().await?;
     let model_config = &models_config.default;
 
-    let result = provider.generate(&full_prompt, model_config).await?;
+    let full_prompt = budget::fit(
+        template,
+        vec![PromptSection::fixed("sentence", sentence)],
+        provider.get_model_name(),
+        model_config,
+    )
+    .await?;
+
+    let result = provider
+        .generate(&full_prompt.prompt, &full_prompt.model_config)
+        .await?;
 
     // Log token usage
     app_log!(
@@ -52,5 +66,5 @@ pub async fn sentence_to_json(
     }
 
     app_log!(info, "Successfully generated and validated JSON");
-    Ok(parsed_json)
+    Ok((parsed_json, result.usage))
 }
@@ -0,0 +1,217 @@
+// src/workflow/condition.rs - `when:` predicates over the workflow context
+//
+// Grammar: terms joined by `&&` / `||` (`&&` binds tighter), where a term is
+// `field`, `!field` or `field <op> value` with op one of == != < <= > >=.
+// A bare field is true when set and non-empty; numbers compare numerically.
+use crate::models::{MatchingInfo, ParameterMatch};
+use crate::workflow::WorkflowContext;
+
+const FIELDS: &[&str] = &[
+    "sentence",
+    "email",
+    "endpoint_id",
+    "endpoint_description",
+    "json_output",
+    "parameters.count",
+    "matching_info.status",
+    "matching_info.completion_percentage",
+    "matching_info.missing_required",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    IsSet(String),
+    IsUnset(String),
+    Compare(String, Op, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    /// Disjunction of conjunctions
+    any_of: Vec<Vec<Term>>,
+}
+
+impl Condition {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let mut any_of = Vec::new();
+        for alternative in expression.split("||") {
+            let mut all_of = Vec::new();
+            for term in alternative.split("&&") {
+                all_of.push(parse_term(term.trim(), expression)?);
+            }
+            any_of.push(all_of);
+        }
+        Ok(Self { any_of })
+    }
+
+    pub fn evaluate(&self, context: &WorkflowContext) -> bool {
+        self.any_of
+            .iter()
+            .any(|all_of| all_of.iter().all(|term| evaluate_term(term, context)))
+    }
+}
+
+fn parse_term(term: &str, expression: &str) -> Result<Term, String> {
+    if term.is_empty() {
+        return Err(format!("Empty term in condition '{expression}'"));
+    }
+
+    // Two-character operators first so `<=` is not read as `<`
+    const OPS: &[(&str, Op)] = &[
+        ("==", Op::Eq),
+        ("!=", Op::Ne),
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+    ];
+    for (symbol, op) in OPS {
+        if let Some((field, value)) = term.split_once(symbol) {
+            let field = check_field(field.trim(), expression)?;
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            return Ok(Term::Compare(field, *op, value.to_string()));
+        }
+    }
+
+    match term.strip_prefix('!') {
+        Some(field) => Ok(Term::IsUnset(check_field(field.trim(), expression)?)),
+        None => Ok(Term::IsSet(check_field(term, expression)?)),
+    }
+}
+
+fn check_field(field: &str, expression: &str) -> Result<String, String> {
+    if FIELDS.contains(&field) || field.starts_with("json_output.") {
+        Ok(field.to_string())
+    } else {
+        Err(format!(
+            "Unknown field '{field}' in condition '{expression}'. Known fields: {}, json_output.<key>",
+            FIELDS.join(", ")
+        ))
+    }
+}
+
+fn evaluate_term(term: &Term, context: &WorkflowContext) -> bool {
+    match term {
+        Term::IsSet(field) => resolve(field, context).is_some_and(|v| !v.is_empty()),
+        Term::IsUnset(field) => resolve(field, context).is_none_or(|v| v.is_empty()),
+        Term::Compare(field, op, expected) => {
+            let Some(actual) = resolve(field, context) else {
+                return *op == Op::Ne;
+            };
+            match (actual.parse::<f64>(), expected.parse::<f64>()) {
+                (Ok(a), Ok(b)) => match op {
+                    Op::Eq => a == b,
+                    Op::Ne => a != b,
+                    Op::Lt => a < b,
+                    Op::Le => a <= b,
+                    Op::Gt => a > b,
+                    Op::Ge => a >= b,
+                },
+                _ => match op {
+                    Op::Eq => actual.eq_ignore_ascii_case(expected),
+                    Op::Ne => !actual.eq_ignore_ascii_case(expected),
+                    // Ordering only makes sense for numbers
+                    _ => false,
+                },
+            }
+        }
+    }
+}
+
+fn resolve(field: &str, context: &WorkflowContext) -> Option<String> {
+    match field {
+        "sentence" => Some(context.sentence.clone()),
+        "email" => context.email.clone(),
+        "endpoint_id" => context.endpoint_id.clone(),
+        "endpoint_description" => context.endpoint_description.clone(),
+        "json_output" => context.json_output.as_ref().map(|v| v.to_string()),
+        "parameters.count" => Some(context.parameters.len().to_string()),
+        "matching_info.status" => Some(format!("{:?}", matching_info(context).status)),
+        "matching_info.completion_percentage" => {
+            Some(matching_info(context).completion_percentage.to_string())
+        }
+        "matching_info.missing_required" => Some(
+            matching_info(context)
+                .missing_required_fields
+                .len()
+                .to_string(),
+        ),
+        _ => {
+            let key = field.strip_prefix("json_output.")?;
+            match context.json_output.as_ref()?.get(key)? {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(s) => Some(s.clone()),
+                other => Some(other.to_string()),
+            }
+        }
+    }
+}
+
+fn matching_info(context: &WorkflowContext) -> MatchingInfo {
+    let matches: Vec<ParameterMatch> = context
+        .parameters
+        .iter()
+        .map(|p| ParameterMatch {
+            name: p.name.clone(),
+            description: p.description.clone(),
+            value: p.semantic_value.clone(),
+            default_source: None,
+        })
+        .collect();
+    MatchingInfo::compute(&matches, &context.parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EndpointParameter;
+    use crate::workflow::engine::tests::NoopProvider;
+    use std::sync::Arc;
+
+    fn context() -> WorkflowContext {
+        let mut context = WorkflowContext::new("send a mail".to_string(), Arc::new(NoopProvider));
+        context.endpoint_id = Some("send_email".to_string());
+        context.json_output = Some(serde_json::json!({"to": "bob@example.com", "cc": null}));
+        context.parameters = vec![EndpointParameter {
+            name: "to".to_string(),
+            description: "Recipient".to_string(),
+            required: Some(true),
+            alternatives: None,
+            examples: None,
+            semantic_value: None,
+        }];
+        context
+    }
+
+    #[test]
+    fn test_conditions() {
+        let context = context();
+        let eval = |expression: &str| Condition::parse(expression).unwrap().evaluate(&context);
+
+        assert!(eval("matching_info.status != Complete"));
+        assert!(eval("endpoint_id == send_email && parameters.count >= 1"));
+        assert!(!eval("endpoint_id == 'other'"));
+        assert!(eval("json_output.to"));
+        assert!(eval("!json_output.cc"));
+        assert!(eval(
+            "endpoint_id == other || matching_info.missing_required > 0"
+        ));
+        assert!(!eval("email"));
+    }
+
+    #[test]
+    fn test_invalid_conditions_rejected() {
+        assert!(Condition::parse("matching_status != Complete").is_err());
+        assert!(Condition::parse("endpoint_id == a &&").is_err());
+    }
+}
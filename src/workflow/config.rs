@@ -1,15 +1,118 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 
-#[derive(Debug, Deserialize)]
+/// Actionable-request workflow used when `config.yaml` defines none
+pub const DEFAULT_ACTIONABLE_WORKFLOW: &str = r#"
+steps:
+  - name: enhanced_configuration_loading
+    retry:
+      max_attempts: 3
+      delay_ms: 1000
+  - name: endpoint_matching
+    retry:
+      max_attempts: 2
+      delay_ms: 500
+  - name: path_parameter_extraction
+    retry:
+      max_attempts: 1
+      delay_ms: 0
+  - name: json_generation
+    retry:
+      max_attempts: 3
+      delay_ms: 1000
+  - name: field_matching
+    retry:
+      max_attempts: 2
+      delay_ms: 500
+"#;
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct WorkflowConfig {
-    pub steps: Vec<StepConfig>,
+    pub steps: Vec<WorkflowNode>,
+}
+
+impl WorkflowConfig {
+    pub fn default_actionable() -> Self {
+        serde_yaml::from_str(DEFAULT_ACTIONABLE_WORKFLOW)
+            .expect("built-in workflow definition is valid")
+    }
+}
+
+impl WorkflowConfig {
+    /// Every step, including parallel group members, in definition order
+    pub fn all_steps(&self) -> Vec<&StepConfig> {
+        self.steps
+            .iter()
+            .flat_map(|node| match node {
+                WorkflowNode::Step(step) => vec![step],
+                WorkflowNode::Parallel { parallel } => parallel.iter().collect(),
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// One entry of `steps`: a single step, or a group whose steps run concurrently
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum WorkflowNode {
+    Step(StepConfig),
+    Parallel { parallel: Vec<StepConfig> },
+}
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct StepConfig {
     pub name: String,
+    #[serde(default = "default_enabled")]
     pub enabled: bool,
     pub retry: Option<RetryConfig>,
+    /// Run the step only when this predicate over the context holds, see `condition.rs`
+    pub when: Option<String>,
+    /// Time limit for each attempt
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub on_error: OnError,
+}
+
+/// What to do once a step has failed all its attempts.
+/// Written as `on_error: fail`, `on_error: skip` or `on_error: {fallback: <step>}`.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(try_from = "OnErrorRepr")]
+pub enum OnError {
+    /// Abort the workflow
+    #[default]
+    Fail,
+    /// Log and continue with the next step
+    Skip,
+    /// Run the named step instead
+    Fallback(String),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OnErrorRepr {
+    Policy(String),
+    Fallback { fallback: String },
+}
+
+impl TryFrom<OnErrorRepr> for OnError {
+    type Error = String;
+
+    fn try_from(repr: OnErrorRepr) -> Result<Self, Self::Error> {
+        match repr {
+            OnErrorRepr::Policy(policy) => match policy.as_str() {
+                "fail" => Ok(OnError::Fail),
+                "skip" => Ok(OnError::Skip),
+                other => Err(format!(
+                    "unknown on_error policy '{other}', expected fail, skip or {{fallback: <step>}}"
+                )),
+            },
+            OnErrorRepr::Fallback { fallback } => Ok(OnError::Fallback(fallback)),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -17,3 +120,42 @@ pub struct RetryConfig {
     pub max_attempts: u32,
     pub delay_ms: u64,
 }
+
+/// The `workflows` section of `config.yaml`.
+/// Lookup order: tenant, then intent, then `default`, then the built-in workflow.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct WorkflowsConfig {
+    pub default: Option<WorkflowConfig>,
+    /// Keyed by intent, e.g. `actionable_request`
+    #[serde(default)]
+    pub intents: HashMap<String, WorkflowConfig>,
+    /// Keyed by tenant: the verified tenant claim, or else the caller email domain
+    #[serde(default)]
+    pub tenants: HashMap<String, WorkflowConfig>,
+}
+
+impl WorkflowsConfig {
+    pub fn resolve(&self, tenant: Option<&str>, intent: &str) -> WorkflowConfig {
+        tenant
+            .and_then(|tenant| self.tenants.get(tenant))
+            .or_else(|| self.intents.get(intent))
+            .or(self.default.as_ref())
+            .cloned()
+            .unwrap_or_else(WorkflowConfig::default_actionable)
+    }
+
+    /// Every definition with a label for error messages
+    pub fn definitions(&self) -> Vec<(String, &WorkflowConfig)> {
+        let mut definitions = Vec::new();
+        if let Some(default) = &self.default {
+            definitions.push(("default".to_string(), default));
+        }
+        for (intent, workflow) in &self.intents {
+            definitions.push((format!("intents.{intent}"), workflow));
+        }
+        for (tenant, workflow) in &self.tenants {
+            definitions.push((format!("tenants.{tenant}"), workflow));
+        }
+        definitions
+    }
+}
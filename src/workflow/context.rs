@@ -1,8 +1,11 @@
+use crate::app_log;
+use crate::models::providers::token_counter::TokenUsage;
 use crate::models::{
     providers::ModelProvider, ConfigFile, Endpoint, EndpointParameter, EnhancedEndpoint,
     ModelsConfig,
 };
-use serde_json::Value;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 // Remove the Debug derive since dyn ModelProvider doesn't implement Debug
@@ -24,8 +27,11 @@ pub struct WorkflowContext {
     pub endpoint_id: Option<String>,
     pub endpoint_description: Option<String>,
     pub provider: Arc<dyn ModelProvider>,
-    pub total_input_tokens: u32,
-    pub total_output_tokens: u32,
+
+    // Token usage as reported by the provider, estimated only when it reports none
+    pub usage: TokenUsage,
+    pub step_usage: BTreeMap<String, TokenUsage>,
+    pub provider_usage: BTreeMap<String, TokenUsage>,
 }
 
 impl WorkflowContext {
@@ -43,15 +49,122 @@ impl WorkflowContext {
             parameters: vec![],
             endpoint_id: None,
             endpoint_description: None,
-            total_input_tokens: 0,
-            total_output_tokens: 0,
+            usage: TokenUsage::default(),
+            step_usage: BTreeMap::new(),
+            provider_usage: BTreeMap::new(),
         }
     }
 
-    // pub fn add_token_usage(&mut self, usage: &TokenUsage) {
-    //     self.total_input_tokens += usage.input_tokens;
-    //     self.total_output_tokens += usage.output_tokens;
-    // }
+    /// Apply the changes a parallel branch made to its copy of `base`.
+    /// When two branches change the same field, the later branch wins.
+    pub fn merge_branch(&mut self, base: &WorkflowContext, branch: WorkflowContext, step: &str) {
+        macro_rules! merge_fields {
+            ($($field:ident),*) => {
+                $(
+                    if branch.$field != base.$field {
+                        if self.$field != base.$field {
+                            app_log!(
+                                warn,
+                                "Parallel step {} overwrites '{}' set by another branch",
+                                step,
+                                stringify!($field)
+                            );
+                        }
+                        self.$field = branch.$field;
+                    }
+                )*
+            };
+        }
+
+        merge_fields!(
+            email,
+            models_config,
+            endpoints_config,
+            enhanced_endpoints,
+            json_output,
+            matched_endpoint,
+            parameters,
+            endpoint_id,
+            endpoint_description
+        );
+
+        self.usage.add(&branch.usage.since(&base.usage));
+        merge_usage(&mut self.step_usage, &base.step_usage, &branch.step_usage);
+        merge_usage(
+            &mut self.provider_usage,
+            &base.provider_usage,
+            &branch.provider_usage,
+        );
+    }
+
+    /// Account the usage of one LLM call made by `step`
+    pub fn record_usage(&mut self, step: &str, usage: &TokenUsage) {
+        self.usage.add(usage);
+        self.step_usage
+            .entry(step.to_string())
+            .or_default()
+            .add(usage);
+        self.provider_usage
+            .entry(self.provider.get_model_name().to_string())
+            .or_default()
+            .add(usage);
+    }
+
+    /// The processing state as JSON, for execution trace diffs.
+    /// Loaded configurations are summarized by size.
+    pub fn snapshot(&self) -> Map<String, Value> {
+        let mut snapshot = Map::new();
+        snapshot.insert("email".to_string(), json!(self.email));
+        snapshot.insert(
+            "models_config_loaded".to_string(),
+            json!(self.models_config.is_some()),
+        );
+        snapshot.insert(
+            "endpoints_config".to_string(),
+            json!(self.endpoints_config.as_ref().map(|c| c.endpoints.len())),
+        );
+        snapshot.insert(
+            "enhanced_endpoints".to_string(),
+            json!(self.enhanced_endpoints.as_ref().map(Vec::len)),
+        );
+        snapshot.insert("json_output".to_string(), json!(self.json_output));
+        snapshot.insert(
+            "matched_endpoint".to_string(),
+            json!(self.matched_endpoint.as_ref().map(|e| &e.id)),
+        );
+        snapshot.insert("parameters".to_string(), json!(self.parameters));
+        snapshot.insert("endpoint_id".to_string(), json!(self.endpoint_id));
+        snapshot.insert(
+            "endpoint_description".to_string(),
+            json!(self.endpoint_description),
+        );
+        snapshot.insert(
+            "total_input_tokens".to_string(),
+            json!(self.usage.input_tokens),
+        );
+        snapshot.insert(
+            "total_output_tokens".to_string(),
+            json!(self.usage.output_tokens),
+        );
+        snapshot.insert("usage_estimated".to_string(), json!(self.usage.estimated));
+        snapshot
+    }
+}
+
+fn merge_usage(
+    target: &mut BTreeMap<String, TokenUsage>,
+    base: &BTreeMap<String, TokenUsage>,
+    branch: &BTreeMap<String, TokenUsage>,
+) {
+    for (key, usage) in branch {
+        let delta = match base.get(key) {
+            Some(before) => usage.since(before),
+            None => usage.clone(),
+        };
+        if delta.total_tokens > 0 {
+            target.entry(key.clone()).or_default().add(&delta);
+        }
+    }
 }
 
 // Manually implement Debug to handle the provider field
@@ -67,6 +180,8 @@ impl std::fmt::Debug for WorkflowContext {
             .field("parameters", &self.parameters)
             .field("endpoint_id", &self.endpoint_id)
             .field("endpoint_description", &self.endpoint_description)
+            .field("usage", &self.usage)
+            .field("step_usage", &self.step_usage)
             .field("provider", &"<dyn ModelProvider>")
             .finish()
     }
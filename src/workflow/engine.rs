@@ -1,54 +1,275 @@
-use super::config::RetryConfig;
+use super::config::{OnError, RetryConfig};
 use crate::app_log;
+use crate::error::SemanticError;
+use crate::metrics;
+use crate::models::providers::error::ProviderError;
 use crate::models::providers::ModelProvider;
+use crate::trace::{self, StepStatus, StepTrace};
+use crate::usage;
+use crate::workflow::condition::Condition;
 use crate::workflow::config::StepConfig;
 use crate::workflow::WorkflowContext;
 use crate::workflow::WorkflowStep;
 
-use std::error::Error;
+use futures::future::join_all;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A configured step ready to run, with its fallback when `on_error: {fallback: ...}`
+pub struct EngineStep {
+    pub config: StepConfig,
+    pub step: Arc<dyn WorkflowStep>,
+    pub fallback: Option<Arc<dyn WorkflowStep>>,
+}
+
+enum EngineNode {
+    Single(EngineStep),
+    /// Steps run concurrently on copies of the context, merged back in order
+    Parallel(Vec<EngineStep>),
+}
+
 pub struct WorkflowEngine {
-    steps: Vec<(StepConfig, Arc<dyn WorkflowStep>)>,
+    nodes: Vec<EngineNode>,
 }
 
 impl WorkflowEngine {
     pub fn new() -> Self {
-        Self { steps: Vec::new() }
+        Self { nodes: Vec::new() }
     }
 
     pub fn register_step(&mut self, config: StepConfig, step: Arc<dyn WorkflowStep>) {
-        self.steps.push((config, step));
+        self.nodes.push(EngineNode::Single(EngineStep {
+            config,
+            step,
+            fallback: None,
+        }));
+    }
+
+    pub fn register_engine_step(&mut self, step: EngineStep) {
+        self.nodes.push(EngineNode::Single(step));
+    }
+
+    pub fn register_parallel(&mut self, steps: Vec<EngineStep>) {
+        self.nodes.push(EngineNode::Parallel(steps));
     }
 
     pub async fn execute(
         &self,
         sentence: String,
         provider: Arc<dyn ModelProvider>,
-    ) -> Result<WorkflowContext, Box<dyn Error + Send + Sync>> {
+    ) -> Result<WorkflowContext, SemanticError> {
         let mut context = WorkflowContext::new(sentence, provider);
 
-        for (config, step) in &self.steps {
-            if !config.enabled {
-                continue;
+        for node in &self.nodes {
+            match node {
+                EngineNode::Single(step) => self.run_step(step, &mut context).await?,
+                EngineNode::Parallel(steps) => self.run_parallel(steps, &mut context).await?,
             }
+        }
 
-            app_log!(info, "Executing step: {}", step.name());
+        Ok(context)
+    }
 
-            let result = match &config.retry {
-                Some(retry) => {
-                    self.execute_with_retry(step.as_ref(), &mut context, retry)
-                        .await
+    async fn run_parallel(
+        &self,
+        steps: &[EngineStep],
+        context: &mut WorkflowContext,
+    ) -> Result<(), SemanticError> {
+        app_log!(
+            info,
+            "Executing parallel group: {}",
+            steps
+                .iter()
+                .map(|s| s.step.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let base = context.clone();
+        let branches = steps.iter().map(|step| {
+            let mut branch = base.clone();
+            async move {
+                let result = self.run_step(step, &mut branch).await;
+                (step.step.name(), result, branch)
+            }
+        });
+
+        // Wait for every branch so none is cancelled halfway, then merge in definition order
+        let mut first_error = None;
+        for (name, result, branch) in join_all(branches).await {
+            match result {
+                Ok(()) => context.merge_branch(&base, branch, name),
+                Err(e) => {
+                    first_error.get_or_insert(e);
                 }
-                None => step.execute(&mut context).await,
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    async fn run_step(
+        &self,
+        engine_step: &EngineStep,
+        context: &mut WorkflowContext,
+    ) -> Result<(), SemanticError> {
+        let config = &engine_step.config;
+        let step = engine_step.step.as_ref();
+
+        if !config.enabled {
+            return Ok(());
+        }
+
+        // Only traced requests pay for the context snapshots
+        let recorder = trace::current();
+        let started_ms = recorder.as_ref().map_or(0, |r| r.elapsed_ms());
+        let before = recorder.as_ref().map(|_| context.snapshot());
+        let started = Instant::now();
+        let mut attempts = 0;
+
+        let (status, detail, result) = self
+            .run_with_policy(engine_step, context, &mut attempts)
+            .await;
+
+        if let Some(before) = before {
+            let after = context.snapshot();
+            let tokens = |field: &str| {
+                let value = |snapshot: &serde_json::Map<String, serde_json::Value>| {
+                    snapshot.get(field).and_then(|v| v.as_u64()).unwrap_or(0)
+                };
+                value(&after).saturating_sub(value(&before)) as u32
             };
+            trace::record_step(StepTrace {
+                name: step.name().to_string(),
+                status,
+                started_ms,
+                duration_ms: started.elapsed().as_millis() as u64,
+                attempts,
+                detail,
+                input_tokens: tokens("total_input_tokens"),
+                output_tokens: tokens("total_output_tokens"),
+                context_diff: trace::diff_snapshots(&before, &after),
+            });
+        }
 
-            if let Err(e) = result {
+        result
+    }
+
+    /// Evaluate `when`, run the step and apply `on_error`
+    async fn run_with_policy(
+        &self,
+        engine_step: &EngineStep,
+        context: &mut WorkflowContext,
+        attempts: &mut u32,
+    ) -> (StepStatus, Option<String>, Result<(), SemanticError>) {
+        let config = &engine_step.config;
+        let step = engine_step.step.as_ref();
+
+        if let Some(expression) = &config.when {
+            let condition = match Condition::parse(expression) {
+                Ok(condition) => condition,
+                Err(e) => return (StepStatus::Failed, Some(e.clone()), Err(e.into())),
+            };
+            if !condition.evaluate(context) {
+                app_log!(
+                    info,
+                    "Skipping step {}: condition '{}' is false",
+                    step.name(),
+                    expression
+                );
+                let detail = format!("condition '{expression}' is false");
+                return (StepStatus::Skipped, Some(detail), Ok(()));
+            }
+        }
+
+        app_log!(info, "Executing step: {}", step.name());
+
+        let Err(e) = self.run_timed(step, config, context, attempts).await else {
+            return (StepStatus::Completed, None, Ok(()));
+        };
+        let detail = Some(e.to_string());
+
+        match &config.on_error {
+            OnError::Fail => {
                 app_log!(error, "Step {} failed: {}", step.name(), e);
-                return Err(e);
+                (StepStatus::Failed, detail, Err(e))
+            }
+            OnError::Skip => {
+                app_log!(warn, "Step {} failed, skipping: {}", step.name(), e);
+                (StepStatus::Skipped, detail, Ok(()))
+            }
+            OnError::Fallback(name) => {
+                let Some(fallback) = engine_step.fallback.as_ref() else {
+                    let e = format!("Fallback step '{name}' is not registered");
+                    return (StepStatus::Failed, Some(e.clone()), Err(e.into()));
+                };
+                app_log!(
+                    warn,
+                    "Step {} failed, running fallback {}: {}",
+                    step.name(),
+                    fallback.name(),
+                    e
+                );
+                let result = self
+                    .run_timed(fallback.as_ref(), config, context, attempts)
+                    .await;
+                match result {
+                    Ok(()) => (StepStatus::Fallback, detail, Ok(())),
+                    Err(e) => {
+                        app_log!(error, "Fallback step {} failed: {}", fallback.name(), e);
+                        (StepStatus::Failed, Some(e.to_string()), Err(e))
+                    }
+                }
             }
         }
+    }
 
-        Ok(context)
+    /// Run a step with retries, recording usage and latency under its name
+    async fn run_timed(
+        &self,
+        step: &dyn WorkflowStep,
+        config: &StepConfig,
+        context: &mut WorkflowContext,
+        attempts: &mut u32,
+    ) -> Result<(), SemanticError> {
+        let timeout = config.timeout_ms.map(Duration::from_millis);
+        let execution = async {
+            match &config.retry {
+                Some(retry) => {
+                    self.execute_with_retry(step, context, retry, timeout, attempts)
+                        .await
+                }
+                None => {
+                    *attempts += 1;
+                    Self::execute_once(step, context, timeout).await
+                }
+            }
+        };
+
+        let started = Instant::now();
+        let result = usage::in_step(step.name(), execution).await;
+        metrics::record_step(step.name(), result.is_ok(), started.elapsed());
+        result
+    }
+
+    async fn execute_once(
+        step: &dyn WorkflowStep,
+        context: &mut WorkflowContext,
+        timeout: Option<Duration>,
+    ) -> Result<(), SemanticError> {
+        match timeout {
+            Some(limit) => match tokio::time::timeout(limit, step.execute(context)).await {
+                Ok(result) => result,
+                Err(_) => Err(SemanticError::StepTimeout {
+                    step: step.name().to_string(),
+                    after: limit,
+                }),
+            },
+            None => step.execute(context).await,
+        }
     }
 
     async fn execute_with_retry(
@@ -56,14 +277,17 @@ impl WorkflowEngine {
         step: &dyn WorkflowStep,
         context: &mut WorkflowContext,
         retry: &RetryConfig,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let mut attempts = 0;
+        timeout: Option<Duration>,
+        attempts: &mut u32,
+    ) -> Result<(), SemanticError> {
+        let mut failures = 0;
         loop {
-            match step.execute(context).await {
+            *attempts += 1;
+            match Self::execute_once(step, context, timeout).await {
                 Ok(_) => return Ok(()),
                 Err(e) => {
-                    attempts += 1;
-                    if attempts >= retry.max_attempts {
+                    failures += 1;
+                    if failures >= retry.max_attempts || !worth_retrying(&e) {
                         return Err(e);
                     }
                     tokio::time::sleep(tokio::time::Duration::from_millis(retry.delay_ms)).await;
@@ -72,3 +296,187 @@ impl WorkflowEngine {
         }
     }
 }
+
+/// Provider failures were already retried by the HTTP layer, except for a malformed
+/// response that a second generation may get right
+fn worth_retrying(error: &SemanticError) -> bool {
+    !matches!(
+        error,
+        SemanticError::Provider(e) if !matches!(e, ProviderError::InvalidResponse { .. })
+    )
+}
+
+impl Default for WorkflowEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::models::providers::token_counter::TokenUsage;
+    use crate::models::providers::{GenerationResult, ModelConfig};
+    use async_trait::async_trait;
+    use std::error::Error;
+
+    /// Provider for tests that never reach an LLM
+    pub struct NoopProvider;
+
+    #[async_trait]
+    impl ModelProvider for NoopProvider {
+        async fn generate(
+            &self,
+            _prompt: &str,
+            _model: &ModelConfig,
+        ) -> Result<GenerationResult, Box<dyn Error + Send + Sync>> {
+            Err("no provider in tests".into())
+        }
+
+        fn get_model_name(&self) -> &str {
+            "noop"
+        }
+    }
+
+    enum Behavior {
+        SetEndpoint(&'static str),
+        SetJson,
+        Fail,
+        Sleep(u64),
+    }
+
+    struct TestStep {
+        name: &'static str,
+        behavior: Behavior,
+    }
+
+    #[async_trait]
+    impl WorkflowStep for TestStep {
+        async fn execute(&self, context: &mut WorkflowContext) -> Result<(), SemanticError> {
+            match self.behavior {
+                Behavior::SetEndpoint(id) => context.endpoint_id = Some(id.to_string()),
+                Behavior::SetJson => context.json_output = Some(serde_json::json!({"ok": true})),
+                Behavior::Fail => return Err(format!("{} failed", self.name).into()),
+                Behavior::Sleep(ms) => tokio::time::sleep(Duration::from_millis(ms)).await,
+            }
+            context.record_usage(
+                self.name,
+                &TokenUsage {
+                    input_tokens: 10,
+                    output_tokens: 2,
+                    total_tokens: 12,
+                    estimated: false,
+                },
+            );
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            self.name
+        }
+    }
+
+    fn step(name: &'static str, behavior: Behavior, yaml: &str) -> EngineStep {
+        let config: StepConfig = serde_yaml::from_str(&format!("name: {name}\n{yaml}")).unwrap();
+        EngineStep {
+            config,
+            step: Arc::new(TestStep { name, behavior }),
+            fallback: None,
+        }
+    }
+
+    async fn run(engine: WorkflowEngine) -> Result<WorkflowContext, SemanticError> {
+        engine
+            .execute("sentence".to_string(), Arc::new(NoopProvider))
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_condition_skips_step() {
+        let mut engine = WorkflowEngine::new();
+        engine.register_engine_step(step("match", Behavior::SetEndpoint("a"), ""));
+        engine.register_engine_step(step(
+            "rematch",
+            Behavior::SetEndpoint("b"),
+            "when: endpoint_id != a",
+        ));
+
+        let context = run(engine).await.unwrap();
+        assert_eq!(context.endpoint_id.as_deref(), Some("a"));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_group_merges_branches() {
+        let mut engine = WorkflowEngine::new();
+        engine.register_parallel(vec![
+            step("match", Behavior::SetEndpoint("a"), ""),
+            step("json", Behavior::SetJson, ""),
+        ]);
+
+        let context = run(engine).await.unwrap();
+        assert_eq!(context.endpoint_id.as_deref(), Some("a"));
+        assert!(context.json_output.is_some());
+        assert_eq!(context.usage.input_tokens, 20);
+        assert!(!context.usage.estimated);
+        assert_eq!(context.step_usage["match"].total_tokens, 12);
+        assert_eq!(context.step_usage["json"].total_tokens, 12);
+        assert_eq!(context.provider_usage["noop"].total_tokens, 24);
+    }
+
+    #[tokio::test]
+    async fn test_on_error_policies_and_timeout() {
+        let mut engine = WorkflowEngine::new();
+        engine.register_engine_step(step("optional", Behavior::Fail, "on_error: skip"));
+        let mut with_fallback = step("primary", Behavior::Fail, "on_error:\n  fallback: backup");
+        with_fallback.fallback = Some(Arc::new(TestStep {
+            name: "backup",
+            behavior: Behavior::SetEndpoint("from_fallback"),
+        }));
+        engine.register_engine_step(with_fallback);
+
+        let context = run(engine).await.unwrap();
+        assert_eq!(context.endpoint_id.as_deref(), Some("from_fallback"));
+
+        let mut engine = WorkflowEngine::new();
+        engine.register_engine_step(step("slow", Behavior::Sleep(500), "timeout_ms: 20"));
+        let err = run(engine).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_traced_steps_record_attempts_and_diff() {
+        let mut engine = WorkflowEngine::new();
+        engine.register_engine_step(step(
+            "flaky",
+            Behavior::Fail,
+            "on_error: skip\nretry:\n  max_attempts: 2\n  delay_ms: 0",
+        ));
+        engine.register_engine_step(step("match", Behavior::SetEndpoint("a"), ""));
+
+        let recorder = Arc::new(trace::TraceRecorder::new(
+            "a@example.com",
+            "sentence",
+            true,
+            &Default::default(),
+        ));
+        trace::recorded(recorder.clone(), run(engine))
+            .await
+            .unwrap();
+
+        let steps = recorder.snapshot().steps;
+        assert_eq!(steps[0].status, StepStatus::Skipped);
+        assert_eq!(steps[0].attempts, 2);
+        assert_eq!(steps[0].detail.as_deref(), Some("flaky failed"));
+        assert_eq!(steps[1].status, StepStatus::Completed);
+        assert_eq!(steps[1].input_tokens, 10);
+        let changed: Vec<&str> = steps[1]
+            .context_diff
+            .iter()
+            .map(|c| c.field.as_str())
+            .collect();
+        assert_eq!(
+            changed,
+            vec!["endpoint_id", "total_input_tokens", "total_output_tokens"]
+        );
+    }
+}
@@ -1,22 +1,24 @@
 pub mod actions;
+pub mod condition;
 mod config;
 pub mod context;
 pub mod engine;
+pub mod registry;
 
 pub use actions::*;
-pub use config::WorkflowConfig;
+pub use config::{OnError, StepConfig, WorkflowConfig, WorkflowNode, WorkflowsConfig};
 pub use context::WorkflowContext;
 pub use engine::WorkflowEngine;
 
+use crate::error::SemanticError;
 use async_trait::async_trait;
-use std::error::Error;
 
 #[async_trait]
 pub trait WorkflowStep: Send + Sync {
     async fn execute(
         &self,
         context: &mut WorkflowContext,
-    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+    ) -> Result<(), SemanticError>;
     fn name(&self) -> &'static str;
 }
 pub mod steps;
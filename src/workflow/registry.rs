@@ -0,0 +1,276 @@
+// src/workflow/registry.rs - Workflow steps by name
+use crate::workflow::condition::Condition;
+use crate::workflow::config::{OnError, StepConfig, WorkflowConfig, WorkflowNode, WorkflowsConfig};
+use crate::workflow::engine::EngineStep;
+use crate::workflow::steps::endpoint_matching::EndpointMatchingStep;
+use crate::workflow::steps::enhanced_config_loading::EnhancedConfigurationLoadingStep;
+use crate::workflow::steps::field_matching::FieldMatchingStep;
+use crate::workflow::steps::json_generation::JsonGenerationStep;
+use crate::workflow::steps::path_parameter_extraction::PathParameterExtractionStep;
+use crate::workflow::{WorkflowEngine, WorkflowStep};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::Arc;
+
+/// Per-request values a step may need at construction time
+#[derive(Debug, Clone, Default)]
+pub struct StepBuildContext {
+    pub api_url: Option<String>,
+    pub email: String,
+}
+
+type StepFactory = Arc<dyn Fn(&StepBuildContext) -> Arc<dyn WorkflowStep> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct StepRegistry {
+    factories: BTreeMap<String, StepFactory>,
+}
+
+impl StepRegistry {
+    pub fn new() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// Registry with every step shipped with the service
+    pub fn with_builtin_steps() -> Self {
+        let mut registry = Self::new();
+        registry.register("enhanced_configuration_loading", |ctx| {
+            Arc::new(EnhancedConfigurationLoadingStep {
+                api_url: ctx.api_url.clone(),
+                email: ctx.email.clone(),
+            })
+        });
+        registry.register("endpoint_matching", |_| Arc::new(EndpointMatchingStep));
+        registry.register("path_parameter_extraction", |_| {
+            Arc::new(PathParameterExtractionStep)
+        });
+        registry.register("json_generation", |_| Arc::new(JsonGenerationStep));
+        registry.register("field_matching", |_| Arc::new(FieldMatchingStep));
+        registry
+    }
+
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&StepBuildContext) -> Arc<dyn WorkflowStep> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), Arc::new(factory));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    pub fn step_names(&self) -> Vec<&str> {
+        self.factories.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// Check that every step and fallback is registered and every `when` parses
+    pub fn validate(&self, workflow: &WorkflowConfig) -> Result<(), String> {
+        let steps = workflow.all_steps();
+        let unknown: Vec<&str> = steps
+            .iter()
+            .flat_map(|step| {
+                let fallback = match &step.on_error {
+                    OnError::Fallback(name) => Some(name.as_str()),
+                    _ => None,
+                };
+                std::iter::once(step.name.as_str()).chain(fallback)
+            })
+            .filter(|name| !self.contains(name))
+            .collect();
+
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Unknown workflow step(s): {}. Available steps: {}",
+                unknown.join(", "),
+                self.step_names().join(", ")
+            ));
+        }
+
+        for step in steps {
+            if let Some(expression) = &step.when {
+                Condition::parse(expression).map_err(|e| format!("Step {}: {e}", step.name))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate every workflow definition, reporting all problems at once
+    pub fn validate_all(&self, workflows: &WorkflowsConfig) -> Result<(), String> {
+        let errors: Vec<String> = workflows
+            .definitions()
+            .into_iter()
+            .filter_map(|(label, workflow)| {
+                self.validate(workflow)
+                    .err()
+                    .map(|e| format!("workflows.{label}: {e}"))
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    pub fn build(
+        &self,
+        workflow: WorkflowConfig,
+        ctx: &StepBuildContext,
+    ) -> Result<WorkflowEngine, Box<dyn Error + Send + Sync>> {
+        self.validate(&workflow)?;
+
+        let mut engine = WorkflowEngine::new();
+        for node in workflow.steps {
+            match node {
+                WorkflowNode::Step(config) => {
+                    engine.register_engine_step(self.engine_step(config, ctx))
+                }
+                WorkflowNode::Parallel { parallel } => engine.register_parallel(
+                    parallel
+                        .into_iter()
+                        .map(|config| self.engine_step(config, ctx))
+                        .collect(),
+                ),
+            }
+        }
+        Ok(engine)
+    }
+
+    fn engine_step(&self, config: StepConfig, ctx: &StepBuildContext) -> EngineStep {
+        let step = (self.factories[&config.name])(ctx);
+        let fallback = match &config.on_error {
+            OnError::Fallback(name) => Some((self.factories[name])(ctx)),
+            _ => None,
+        };
+        EngineStep {
+            config,
+            step,
+            fallback,
+        }
+    }
+}
+
+impl Default for StepRegistry {
+    fn default() -> Self {
+        Self::with_builtin_steps()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workflow(yaml: &str) -> WorkflowConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_workflow_is_valid() {
+        let registry = StepRegistry::with_builtin_steps();
+        assert!(registry
+            .validate(&WorkflowConfig::default_actionable())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_unknown_step_rejected() {
+        let registry = StepRegistry::with_builtin_steps();
+        let err = registry
+            .validate(&workflow(
+                "steps:\n  - name: endpoint_matching\n  - name: sentiment_analysis\n",
+            ))
+            .unwrap_err();
+        assert!(err.starts_with("Unknown workflow step(s): sentiment_analysis."));
+
+        let workflows: WorkflowsConfig =
+            serde_yaml::from_str("tenants:\n  acme.com:\n    steps:\n      - name: translate\n")
+                .unwrap();
+        let err = registry.validate_all(&workflows).unwrap_err();
+        assert!(err.starts_with("workflows.tenants.acme.com"));
+
+        let err = registry
+            .validate(&workflow(
+                "steps:\n  - name: endpoint_matching\n    on_error:\n      fallback: retry_harder\n",
+            ))
+            .unwrap_err();
+        assert!(err.starts_with("Unknown workflow step(s): retry_harder."));
+    }
+
+    #[test]
+    fn test_parallel_group_and_conditions() {
+        let registry = StepRegistry::with_builtin_steps();
+        let parsed = workflow(
+            r#"
+steps:
+  - name: endpoint_matching
+  - parallel:
+      - name: json_generation
+        timeout_ms: 30000
+      - name: path_parameter_extraction
+        on_error: skip
+  - name: field_matching
+    when: matching_info.status != Complete
+"#,
+        );
+        assert_eq!(parsed.steps.len(), 3);
+        assert_eq!(parsed.all_steps().len(), 4);
+        assert_eq!(parsed.all_steps()[2].on_error, OnError::Skip);
+        assert!(registry.validate(&parsed).is_ok());
+        assert!(registry.build(parsed, &StepBuildContext::default()).is_ok());
+
+        let err = registry
+            .validate(&workflow(
+                "steps:\n  - name: field_matching\n    when: status == done\n",
+            ))
+            .unwrap_err();
+        assert!(err.starts_with("Step field_matching: Unknown field 'status'"));
+    }
+
+    #[test]
+    fn test_resolution_order() {
+        let workflows: WorkflowsConfig = serde_yaml::from_str(
+            r#"
+default:
+  steps:
+    - name: enhanced_configuration_loading
+    - name: endpoint_matching
+intents:
+  actionable_request:
+    steps:
+      - name: enhanced_configuration_loading
+      - name: endpoint_matching
+      - name: json_generation
+        enabled: false
+tenants:
+  acme.com:
+    steps:
+      - name: enhanced_configuration_loading
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            workflows
+                .resolve(Some("acme.com"), "actionable_request")
+                .steps
+                .len(),
+            1
+        );
+        let by_intent = workflows.resolve(Some("other.org"), "actionable_request");
+        assert_eq!(by_intent.steps.len(), 3);
+        assert!(!by_intent.all_steps()[2].enabled);
+        assert!(by_intent.all_steps()[0].enabled);
+        assert_eq!(workflows.resolve(None, "help_request").steps.len(), 2);
+        assert_eq!(
+            WorkflowsConfig::default()
+                .resolve(None, "actionable_request")
+                .steps
+                .len(),
+            5
+        );
+    }
+}
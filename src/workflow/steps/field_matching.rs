@@ -1,20 +1,16 @@
 use crate::app_log;
-use crate::utils::token_calculator::EnhancedTokenCalculator;
+use crate::error::SemanticError;
 use crate::workflow::match_fields::match_fields_semantic;
 use crate::workflow::WorkflowContext;
 use crate::workflow::WorkflowStep;
 use async_trait::async_trait;
-use std::error::Error;
 
 // Reuse existing workflow steps
 pub struct FieldMatchingStep;
 
 #[async_trait]
 impl WorkflowStep for FieldMatchingStep {
-    async fn execute(
-        &self,
-        context: &mut WorkflowContext,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    async fn execute(&self, context: &mut WorkflowContext) -> Result<(), SemanticError> {
         let json_output = context
             .json_output
             .as_ref()
@@ -24,7 +20,7 @@ impl WorkflowStep for FieldMatchingStep {
             .as_ref()
             .ok_or("Matched endpoint not available")?;
 
-        let semantic_results =
+        let (semantic_results, step_usage) =
             match_fields_semantic(json_output, endpoint, context.provider.clone()).await?;
 
         // Update existing parameters (including path parameters added in previous step) with semantic values
@@ -36,18 +32,7 @@ impl WorkflowStep for FieldMatchingStep {
                 param.semantic_value = value.clone();
             }
         }
-
-        // Estimate tokens for field matching step
-        let enhanced_calculator = EnhancedTokenCalculator::new();
-        let step_usage = enhanced_calculator.calculate_usage(
-            &context.sentence,
-            "",
-            context.provider.get_model_name(),
-        );
-
-        // Add tokens to context
-        context.total_input_tokens += step_usage.input_tokens;
-        context.total_output_tokens += step_usage.output_tokens;
+        context.record_usage(self.name(), &step_usage);
 
         app_log!(
             debug,
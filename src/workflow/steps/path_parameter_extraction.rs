@@ -1,19 +1,16 @@
 use crate::app_log;
+use crate::error::SemanticError;
 use crate::models::EndpointParameter;
 use crate::utils::path_params::extract_path_params_from_path;
 use crate::workflow::WorkflowContext;
 use crate::workflow::WorkflowStep;
 use async_trait::async_trait;
-use std::error::Error;
 
 pub struct PathParameterExtractionStep;
 
 #[async_trait]
 impl WorkflowStep for PathParameterExtractionStep {
-    async fn execute(
-        &self,
-        context: &mut WorkflowContext,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    async fn execute(&self, context: &mut WorkflowContext) -> Result<(), SemanticError> {
         app_log!(debug, "Step 3: Extracting path parameters");
 
         let endpoint_id = context
@@ -29,7 +26,7 @@ impl WorkflowStep for PathParameterExtractionStep {
 
         let enhanced_endpoint = enhanced_endpoints
             .iter()
-            .find(|e| &e.id == &endpoint_id)
+            .find(|e| e.id == endpoint_id)
             .ok_or("Enhanced endpoint not found")?
             .clone();
 
@@ -58,6 +55,7 @@ impl WorkflowStep for PathParameterExtractionStep {
                         description: format!("URL path parameter: {}", param_name),
                         semantic_value: None,
                         alternatives: None,
+                        examples: None,
                         required: Some(true),
                     });
                 } else {